@@ -3,11 +3,14 @@ use std::fs;
 use std::fs::File;
 use std::path::Path;
 use std::process::Command;
+use yaml_rust::YamlLoader;
 
 const FONTAWESOME_VERSION: &str = "5.12.0";
 
 fn main() {
     println!("cargo:rerun-if-changed=src/icons.gresource");
+    println!("cargo:rerun-if-changed=resources/redmine_locales");
+    generate_redmine_locales();
     let target_foldername = format!("fontawesome-free-{}-desktop", FONTAWESOME_VERSION);
     if !Path::new(&target_foldername).exists() {
         fetch_fontawesome_icons(&target_foldername);
@@ -37,3 +40,43 @@ fn fetch_fontawesome_icons(target_foldername: &str) {
     fs::rename("package", target_foldername).expect("folder rename");
     fs::remove_file("fontawesome.tgz").expect("remove tgz");
 }
+
+// the redmine plugin needs the date format and the translation for 'today'
+// for each redmine locale. We vendor these from redmine's config/locales/*.yml
+// (see helpers/redmine_locales) and generate the table here.
+fn generate_redmine_locales() {
+    let mut locale_files: Vec<_> = fs::read_dir("resources/redmine_locales")
+        .expect("listing the redmine locales")
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("yml"))
+        .collect();
+    locale_files.sort();
+    let mut generated = "vec![\n".to_string();
+    for locale_file in locale_files {
+        let contents = fs::read_to_string(&locale_file).expect("reading a redmine locale");
+        let yaml = YamlLoader::load_from_str(&contents)
+            .expect("parsing a redmine locale")
+            .pop() // only one element
+            .expect("empty redmine locale");
+        let (locale_name, locale) = yaml
+            .as_hash()
+            .and_then(|h| h.iter().next())
+            .expect("redmine locale without a language");
+        let locale_name = locale_name.as_str().expect("redmine locale name");
+        let date_format = locale["date"]["formats"]["default"]
+            .as_str()
+            .unwrap_or_else(|| panic!("no date format for redmine locale {}", locale_name));
+        let today_translation = locale["label_today"]
+            .as_str()
+            .unwrap_or_else(|| panic!("no label_today for redmine locale {}", locale_name));
+        generated.push_str(&format!(
+            "    ({:?}, LocaleInfo::new({:?}, {:?})),\n",
+            locale_name, date_format, today_translation
+        ));
+    }
+    generated.push(']');
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR");
+    fs::write(Path::new(&out_dir).join("redmine_locales.rs"), generated)
+        .expect("writing the redmine locales");
+}
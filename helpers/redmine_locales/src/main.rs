@@ -55,10 +55,19 @@ fn main() {
             [&Yaml::from_str("label_today")]
             .as_str()
             .unwrap();
-        println!(
-            "locales.insert(\"{}\", LocaleInfo::new(\"{}\", \"{}\"));",
-            locale_name, date_format, today_translation
-        );
+        // we only vendor the parts of the locale that cigale needs
+        let target = format!("../../resources/redmine_locales/{}.yml", locale_name);
+        println!("Writing {}", target);
+        std::fs::write(
+            target,
+            format!(
+                "# extracted from https://github.com/redmine/redmine/blob/master/config/locales/{}.yml\n\
+                 # by helpers/redmine_locales\n\
+                 {}:\n  date:\n    formats:\n      default: \"{}\"\n  label_today: \"{}\"\n",
+                locale_name, locale_name, date_format, today_translation
+            ),
+        )
+        .unwrap();
         contents.clear();
     }
 }
@@ -37,6 +37,36 @@ pub struct Config {
     pub gitlab: HashMap<String, crate::events::gitlab::GitlabConfig>,
     #[serde(default)] // stackexchange was added later, after 0.4.0
     pub stackexchange: HashMap<String, crate::events::stackexchange::StackExchangeConfig>,
+    #[serde(default)] // github was added later, after 0.5.4
+    pub github: HashMap<String, crate::events::github::GithubConfig>,
+    #[serde(default)] // gitea was added later, after 0.5.4
+    pub gitea: HashMap<String, crate::events::gitea::GiteaConfig>,
+    #[serde(default)] // jira was added later, after 0.5.4
+    pub jira: HashMap<String, crate::events::jira::JiraConfig>,
+    #[serde(default)] // shell_history was added later, after 0.5.4
+    pub shell_history: HashMap<String, crate::events::shell_history::ShellHistoryConfig>,
+    #[serde(default)] // browser_history was added later, after 0.5.4
+    pub browser_history: HashMap<String, crate::events::browser_history::BrowserHistoryConfig>,
+    #[serde(default)] // desktop_session was added later, after 0.5.4
+    pub desktop_session: HashMap<String, crate::events::desktop_session::DesktopSessionConfig>,
+    #[serde(default)] // file_modifications was added later, after 0.5.4
+    pub file_modifications:
+        HashMap<String, crate::events::file_modifications::FileModificationsConfig>,
+    #[serde(default)] // mercurial was added later, after 0.5.4
+    pub mercurial: HashMap<String, crate::events::mercurial::MercurialConfig>,
+    #[serde(default)] // svn was added later, after 0.5.4
+    pub svn: HashMap<String, crate::events::svn::SvnConfig>,
+    #[serde(default)] // timewarrior was added later, after 0.5.4
+    pub timewarrior: HashMap<String, crate::events::timewarrior::TimewarriorConfig>,
+    #[serde(default)] // journal was added later, after 0.5.4
+    pub journal: HashMap<String, crate::events::journal::JournalConfig>,
+    #[serde(default)] // custom_command was added later, after 0.5.4
+    pub custom_command: HashMap<String, crate::events::custom_command::CustomCommandConfig>,
+    // git remote host => url to display a commit in the browser, for forges
+    // we don't recognize. "{base_url}" and "{path}" get replaced by the
+    // server url and the repo path, the commit sha is appended to the url.
+    #[serde(default)] // was added later, after 0.5.4
+    pub git_commit_url_templates: HashMap<String, String>,
 }
 
 impl Config {
@@ -53,6 +83,19 @@ impl Config {
             redmine: HashMap::new(),
             gitlab: HashMap::new(),
             stackexchange: HashMap::new(),
+            github: HashMap::new(),
+            gitea: HashMap::new(),
+            jira: HashMap::new(),
+            shell_history: HashMap::new(),
+            browser_history: HashMap::new(),
+            desktop_session: HashMap::new(),
+            file_modifications: HashMap::new(),
+            mercurial: HashMap::new(),
+            svn: HashMap::new(),
+            timewarrior: HashMap::new(),
+            journal: HashMap::new(),
+            custom_command: HashMap::new(),
+            git_commit_url_templates: HashMap::new(),
             prefer_dark_theme: false,
             prev_next_day_skip_weekends: PrevNextDaySkipWeekends::Skip,
         }
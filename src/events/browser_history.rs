@@ -0,0 +1,448 @@
+// the browsers keep their history database locked while they're running,
+// so we work on a copy of the database.
+use super::events::{
+    format_duration, ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode,
+};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// visits to the same site further apart than this are different events
+const VISIT_GAP_MINS: i64 = 30;
+// chromium counts microseconds from 1601-01-01
+const CHROMIUM_EPOCH_OFFSET_SECS: i64 = 11_644_473_600;
+
+#[derive(
+    serde_derive::Deserialize, serde_derive::Serialize, Clone, Copy, Debug, PartialEq, Default,
+)]
+pub enum BrowserKind {
+    #[default]
+    Firefox,
+    Chromium,
+}
+
+impl BrowserKind {
+    fn desc(self) -> &'static str {
+        match self {
+            BrowserKind::Firefox => "Firefox (places.sqlite)",
+            BrowserKind::Chromium => "Chromium, Chrome (History)",
+        }
+    }
+
+    fn from_desc(desc: &str) -> BrowserKind {
+        if desc == BrowserKind::Chromium.desc() {
+            BrowserKind::Chromium
+        } else {
+            BrowserKind::Firefox
+        }
+    }
+}
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct BrowserHistoryConfig {
+    pub browser: BrowserKind,
+    pub history_file: String,
+    // comma-separated domains, empty to allow all. Subdomains match too.
+    pub allowed_domains: String,
+    pub denied_domains: String,
+}
+
+pub struct BrowserHistory;
+const BROWSER_KEY: &str = "Browser";
+const HISTORY_FILE_KEY: &str = "History database";
+const ALLOWED_DOMAINS_KEY: &str = "Only these domains (empty for all)";
+const DENIED_DOMAINS_KEY: &str = "Ignore these domains";
+
+#[derive(Clone, Debug, PartialEq)]
+struct Visit {
+    time: DateTime<Local>,
+    url: String,
+    title: Option<String>,
+    // only chromium records it
+    duration: Option<chrono::Duration>,
+}
+
+impl BrowserHistory {
+    // the host of http urls, None for other urls (about:, file:, chrome:...)
+    fn url_host(url: &str) -> Option<&str> {
+        let rest = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))?;
+        let host_port = rest.split(&['/', '?', '#'][..]).next()?;
+        let host = host_port.rsplit('@').next()?.split(':').next()?;
+        Some(host.strip_prefix("www.").unwrap_or(host)).filter(|h| !h.is_empty())
+    }
+
+    fn parse_domains(domains: &str) -> Vec<String> {
+        domains
+            .split(',')
+            .map(|d| d.trim().trim_start_matches("www.").to_lowercase())
+            .filter(|d| !d.is_empty())
+            .collect()
+    }
+
+    fn domain_matches(host: &str, domain: &str) -> bool {
+        host == domain || host.ends_with(&format!(".{}", domain))
+    }
+
+    fn is_host_allowed(browser_config: &BrowserHistoryConfig, host: &str) -> bool {
+        let host = host.to_lowercase();
+        let allowed = Self::parse_domains(&browser_config.allowed_domains);
+        let denied = Self::parse_domains(&browser_config.denied_domains);
+        (allowed.is_empty() || allowed.iter().any(|d| Self::domain_matches(&host, d)))
+            && !denied.iter().any(|d| Self::domain_matches(&host, d))
+    }
+
+    // copy the database (and its write-ahead log which has the most recent
+    // visits) to avoid the lock of the running browser
+    fn copy_database(history_file: &str) -> Result<PathBuf> {
+        let copy_path = std::env::temp_dir().join(format!(
+            "cigale_history_{}_{}.sqlite",
+            std::process::id(),
+            Local::now().timestamp_nanos()
+        ));
+        std::fs::copy(history_file, &copy_path)?;
+        let wal_path = format!("{}-wal", history_file);
+        if Path::new(&wal_path).exists() {
+            std::fs::copy(&wal_path, format!("{}-wal", copy_path.to_string_lossy()))?;
+        }
+        Ok(copy_path)
+    }
+
+    fn remove_database_copy(copy_path: &Path) {
+        let wal_path = format!("{}-wal", copy_path.to_string_lossy());
+        for path in &[copy_path, Path::new(&wal_path)] {
+            if path.exists() {
+                if let Err(e) = std::fs::remove_file(path) {
+                    log::error!("Failed removing {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+
+    fn chromium_time(time: i64) -> DateTime<Local> {
+        Local.timestamp_nanos((time - CHROMIUM_EPOCH_OFFSET_SECS * 1_000_000) * 1000)
+    }
+
+    fn to_chromium_time(time: &DateTime<Local>) -> i64 {
+        (time.timestamp() + CHROMIUM_EPOCH_OFFSET_SECS) * 1_000_000
+    }
+
+    fn read_visits(
+        browser: BrowserKind,
+        db_path: &Path,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Result<Vec<Visit>> {
+        // we can't open the copy read-only, sqlite must replay the write-ahead log
+        let conn = rusqlite::Connection::open_with_flags(
+            db_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        let (sql, from, to) = match browser {
+            // microseconds since the unix epoch
+            BrowserKind::Firefox => (
+                "SELECT v.visit_date, p.url, p.title, NULL FROM moz_historyvisits v \
+                 JOIN moz_places p ON p.id = v.place_id \
+                 WHERE v.visit_date >= ?1 AND v.visit_date < ?2 ORDER BY v.visit_date",
+                day_start.timestamp() * 1_000_000,
+                next_day_start.timestamp() * 1_000_000,
+            ),
+            BrowserKind::Chromium => (
+                "SELECT v.visit_time, u.url, u.title, v.visit_duration FROM visits v \
+                 JOIN urls u ON u.id = v.url \
+                 WHERE v.visit_time >= ?1 AND v.visit_time < ?2 ORDER BY v.visit_time",
+                Self::to_chromium_time(&day_start),
+                Self::to_chromium_time(&next_day_start),
+            ),
+        };
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(rusqlite::params![from, to], |row| {
+            let time: i64 = row.get(0)?;
+            let duration: Option<i64> = row.get(3)?;
+            Ok(Visit {
+                time: match browser {
+                    BrowserKind::Firefox => Local.timestamp_nanos(time * 1000),
+                    BrowserKind::Chromium => Self::chromium_time(time),
+                },
+                url: row.get(1)?,
+                title: row.get(2)?,
+                duration: duration
+                    .filter(|d| *d > 0)
+                    .map(chrono::Duration::microseconds),
+            })
+        })?;
+        let mut visits = vec![];
+        for row in rows {
+            visits.push(row?);
+        }
+        Ok(visits)
+    }
+
+    // consecutive visits to the same site, without long pauses
+    fn collapse_visits(visits: Vec<Visit>) -> Vec<(String, Vec<Visit>)> {
+        let mut groups: Vec<(String, Vec<Visit>)> = vec![];
+        for visit in visits {
+            let host = match Self::url_host(&visit.url) {
+                Some(h) => h.to_string(),
+                None => continue,
+            };
+            match groups.last_mut() {
+                Some((group_host, group_visits))
+                    if *group_host == host
+                        && visit.time - group_visits.iter().map(Self::visit_end).max().unwrap()
+                            <= chrono::Duration::minutes(VISIT_GAP_MINS) =>
+                {
+                    group_visits.push(visit)
+                }
+                _ => groups.push((host, vec![visit])),
+            }
+        }
+        groups
+    }
+
+    fn visit_end(visit: &Visit) -> DateTime<Local> {
+        visit.time + visit.duration.unwrap_or_else(chrono::Duration::zero)
+    }
+
+    fn build_event(host: &str, visits: &[Visit]) -> Event {
+        let start = visits.first().unwrap().time;
+        let end = visits.iter().map(Self::visit_end).max().unwrap();
+        let duration = format_duration(end - start);
+        // a page reloaded several times is listed once
+        let mut pages: Vec<&Visit> = vec![];
+        for visit in visits {
+            if !pages.iter().any(|p| p.url == visit.url) {
+                pages.push(visit);
+            }
+        }
+        let pages_markup = pages
+            .iter()
+            .map(|v| {
+                format!(
+                    "{} <a href=\"{}\">{}</a>",
+                    v.time.format("%H:%M"),
+                    glib::markup_escape_text(&v.url),
+                    glib::markup_escape_text(
+                        v.title
+                            .as_deref()
+                            .filter(|t| !t.is_empty())
+                            .unwrap_or(&v.url)
+                    )
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let first_title = pages
+            .iter()
+            .find_map(|p| p.title.as_deref().filter(|t| !t.is_empty()))
+            .unwrap_or(host);
+        Event::new(
+            "Browser",
+            Icon::GLOBE,
+            start.time(),
+            first_title.to_string(),
+            format!("{}: {} page(s)", host, pages.len()),
+            EventBody::Markup(pages_markup, WordWrapMode::WordWrap),
+            Some(duration),
+        )
+    }
+}
+
+impl EventProvider for BrowserHistory {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (BROWSER_KEY, ConfigType::Combo),
+            (HISTORY_FILE_KEY, ConfigType::File),
+            (ALLOWED_DOMAINS_KEY, ConfigType::Text("")),
+            (DENIED_DOMAINS_KEY, ConfigType::Text("")),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Browser history"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::GLOBE
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.browser_history.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        if field_name == BROWSER_KEY {
+            Ok(vec![
+                BrowserKind::Firefox.desc().to_string(),
+                BrowserKind::Chromium.desc().to_string(),
+            ])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                BROWSER_KEY,
+                config.browser_history[config_name]
+                    .browser
+                    .desc()
+                    .to_string(),
+            ),
+            (
+                HISTORY_FILE_KEY,
+                config.browser_history[config_name].history_file.to_string(),
+            ),
+            (
+                ALLOWED_DOMAINS_KEY,
+                config.browser_history[config_name]
+                    .allowed_domains
+                    .to_string(),
+            ),
+            (
+                DENIED_DOMAINS_KEY,
+                config.browser_history[config_name]
+                    .denied_domains
+                    .to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.browser_history.insert(
+            config_name,
+            BrowserHistoryConfig {
+                browser: BrowserKind::from_desc(
+                    &config_values.remove(BROWSER_KEY).unwrap_or_default(),
+                ),
+                history_file: config_values.remove(HISTORY_FILE_KEY).unwrap(),
+                allowed_domains: config_values.remove(ALLOWED_DOMAINS_KEY).unwrap(),
+                denied_domains: config_values.remove(DENIED_DOMAINS_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.browser_history.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let browser_config = &config.browser_history[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let db_copy = Self::copy_database(&browser_config.history_file)?;
+        let visits = Self::read_visits(browser_config.browser, &db_copy, day_start, next_day_start);
+        Self::remove_database_copy(&db_copy);
+        let visits = visits?
+            .into_iter()
+            .filter(|v| {
+                Self::url_host(&v.url)
+                    .filter(|h| Self::is_host_allowed(browser_config, h))
+                    .is_some()
+            })
+            .collect();
+        Ok(Self::collapse_visits(visits)
+            .iter()
+            .map(|(host, visits)| Self::build_event(host, visits))
+            .collect())
+    }
+}
+
+#[test]
+fn it_extracts_url_hosts() {
+    assert_eq!(
+        Some("wiki.mycompany.com"),
+        BrowserHistory::url_host("https://wiki.mycompany.com:8443/page?x=1")
+    );
+    assert_eq!(
+        Some("docs.rs"),
+        BrowserHistory::url_host("http://www.docs.rs#top")
+    );
+    assert_eq!(None, BrowserHistory::url_host("about:config"));
+}
+
+#[test]
+fn it_filters_allowed_and_denied_domains() {
+    let config = BrowserHistoryConfig {
+        browser: BrowserKind::Firefox,
+        history_file: "".to_string(),
+        allowed_domains: "mycompany.com, docs.rs".to_string(),
+        denied_domains: "mail.mycompany.com".to_string(),
+    };
+    assert!(BrowserHistory::is_host_allowed(
+        &config,
+        "wiki.mycompany.com"
+    ));
+    assert!(BrowserHistory::is_host_allowed(&config, "docs.rs"));
+    assert!(!BrowserHistory::is_host_allowed(
+        &config,
+        "mail.mycompany.com"
+    ));
+    assert!(!BrowserHistory::is_host_allowed(
+        &config,
+        "notmycompany.com"
+    ));
+}
+
+#[test]
+fn it_collapses_consecutive_visits() {
+    let visit = |secs: i64, url: &str, duration_mins: Option<i64>| Visit {
+        time: Local.timestamp(1584957600 + secs, 0),
+        url: url.to_string(),
+        title: Some(url.to_string()),
+        duration: duration_mins.map(chrono::Duration::minutes),
+    };
+    let groups = BrowserHistory::collapse_visits(vec![
+        visit(0, "https://docs.rs/chrono", Some(20)),
+        visit(60, "https://docs.rs/regex", None),
+        visit(120, "about:blank", None),
+        // 45 minutes after the first visit started, but it lasted 20 minutes
+        visit(45 * 60, "https://docs.rs/chrono", Some(10)),
+        visit(46 * 60, "https://github.com/", None),
+        // more than 30 minutes after the previous visit
+        visit(90 * 60, "https://github.com/", None),
+    ]);
+    assert_eq!(
+        vec![("docs.rs", 3), ("github.com", 1), ("github.com", 1)],
+        groups
+            .iter()
+            .map(|(h, v)| (h.as_str(), v.len()))
+            .collect::<Vec<_>>()
+    );
+    let event = BrowserHistory::build_event(&groups[0].0, &groups[0].1);
+    assert_eq!("docs.rs: 2 page(s)", event.event_contents_header);
+    assert_eq!(Some("55m".to_string()), event.event_extra_details);
+}
+
+#[test]
+fn it_converts_chromium_times() {
+    let time = Local.timestamp(1584957600, 0);
+    assert_eq!(
+        time,
+        BrowserHistory::chromium_time(BrowserHistory::to_chromium_time(&time))
+    );
+}
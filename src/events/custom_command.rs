@@ -0,0 +1,402 @@
+// plug any script in: we run a command line, replacing the placeholders
+// {date} (2020-03-23), {start} and {end} (the day boundaries, RFC 3339),
+// and read events from its output, either one JSON object per line or CSV
+// with a header row. The fields (see the README) are:
+// - time: "09:30", "09:30:15" or an RFC 3339 date-time (required)
+// - title (required), body, details: text
+// - icon: the name of one of our icons, "clock" for instance
+use super::events::{run_command, ConfigType, Event, EventBody, EventProvider, Result};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::process::Command;
+
+#[derive(
+    serde_derive::Deserialize, serde_derive::Serialize, Clone, Copy, Debug, PartialEq, Default,
+)]
+pub enum OutputFormat {
+    #[default]
+    JsonLines,
+    Csv,
+}
+
+impl OutputFormat {
+    fn desc(self) -> &'static str {
+        match self {
+            OutputFormat::JsonLines => "JSON lines",
+            OutputFormat::Csv => "CSV",
+        }
+    }
+
+    fn from_desc(desc: &str) -> OutputFormat {
+        if desc == OutputFormat::Csv.desc() {
+            OutputFormat::Csv
+        } else {
+            OutputFormat::JsonLines
+        }
+    }
+}
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct CustomCommandConfig {
+    pub command: String,
+    pub output_format: OutputFormat,
+}
+
+#[derive(serde_derive::Deserialize, Debug, Default, PartialEq)]
+struct CommandEvent {
+    time: String,
+    title: String,
+    #[serde(default)]
+    body: String,
+    details: Option<String>,
+    icon: Option<String>,
+}
+
+pub struct CustomCommand;
+const COMMAND_KEY: &str = "Command";
+const OUTPUT_FORMAT_KEY: &str = "Output format";
+
+impl CustomCommand {
+    fn expand_command(command: &str, day: Date<Local>) -> String {
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        command
+            .replace("{date}", &day.format("%Y-%m-%d").to_string())
+            .replace("{start}", &day_start.to_rfc3339())
+            .replace("{end}", &next_day_start.to_rfc3339())
+    }
+
+    fn parse_json_lines(output: &str) -> Result<Vec<CommandEvent>> {
+        output
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+            .map(|(idx, l)| {
+                serde_json::from_str(l)
+                    .map_err(|e| format!("Invalid event on line {}: {}", idx + 1, e).into())
+            })
+            .collect()
+    }
+
+    // a field can be quoted, with "" for a quote. Quoted fields may contain newlines.
+    fn parse_csv_records(output: &str) -> Vec<Vec<String>> {
+        let mut records = vec![];
+        let mut record = vec![];
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut chars = output.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, in_quotes) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                ('"', true) => in_quotes = false,
+                ('"', false) if field.is_empty() => in_quotes = true,
+                (',', false) => record.push(std::mem::take(&mut field)),
+                ('\r', false) => {}
+                ('\n', false) => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                _ => field.push(c),
+            }
+        }
+        if !field.is_empty() || !record.is_empty() {
+            record.push(field);
+            records.push(record);
+        }
+        records
+            .into_iter()
+            .filter(|r| !(r.len() == 1 && r[0].trim().is_empty()))
+            .collect()
+    }
+
+    fn parse_csv(output: &str) -> Result<Vec<CommandEvent>> {
+        let mut records = Self::parse_csv_records(output).into_iter();
+        let header: Vec<String> = match records.next() {
+            Some(h) => h.iter().map(|c| c.trim().to_lowercase()).collect(),
+            None => return Ok(vec![]),
+        };
+        let column = |name: &str| header.iter().position(|c| c == name);
+        let (time_idx, title_idx) = match (column("time"), column("title")) {
+            (Some(t), Some(ti)) => (t, ti),
+            _ => return Err("The CSV header must have time & title columns".into()),
+        };
+        let (body_idx, details_idx, icon_idx) = (column("body"), column("details"), column("icon"));
+        let field = |record: &[String], idx: Option<usize>| {
+            idx.and_then(|i| record.get(i))
+                .filter(|f| !f.is_empty())
+                .cloned()
+        };
+        Ok(records
+            .map(|r| CommandEvent {
+                time: field(&r, Some(time_idx)).unwrap_or_default(),
+                title: field(&r, Some(title_idx)).unwrap_or_default(),
+                body: field(&r, body_idx).unwrap_or_default(),
+                details: field(&r, details_idx),
+                icon: field(&r, icon_idx),
+            })
+            .collect())
+    }
+
+    fn parse_time(time: &str, day: Date<Local>) -> Option<NaiveTime> {
+        let time = time.trim();
+        NaiveTime::parse_from_str(time, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+            .ok()
+            .or_else(|| {
+                DateTime::parse_from_rfc3339(time)
+                    .ok()
+                    .map(|d| d.with_timezone(&Local))
+                    .filter(|d| d.date() == day)
+                    .map(|d| d.time())
+            })
+    }
+
+    fn icon_from_name(name: Option<&str>) -> Icon {
+        match name.unwrap_or("") {
+            "tasks" => Icon::TASKS,
+            "clock" => Icon::CLOCK,
+            "edit" => Icon::EDIT,
+            "book" => Icon::BOOK,
+            "newspaper" => Icon::NEWSPAPER,
+            "file-alt" => Icon::FILE_ALT,
+            "trash-alt" => Icon::TRASH_ALT,
+            "users" => Icon::USERS,
+            "comment-dots" => Icon::COMMENT_DOTS,
+            "code-branch" => Icon::CODE_BRANCH,
+            "history" => Icon::HISTORY,
+            "project-diagram" => Icon::PROJECT_DIAGRAM,
+            "tag" => Icon::TAG,
+            "upload" => Icon::UPLOAD,
+            "envelope" => Icon::ENVELOPE,
+            "thumbs-up" => Icon::THUMBS_UP,
+            "check-square" => Icon::CHECK_SQUARE,
+            "calendar-alt" => Icon::CALENDAR_ALT,
+            "terminal" => Icon::TERMINAL,
+            "globe" => Icon::GLOBE,
+            "power-off" => Icon::POWER_OFF,
+            "lock" => Icon::LOCK,
+            "unlock" => Icon::UNLOCK,
+            "folder-open" => Icon::FOLDER_OPEN,
+            "exclamation-triangle" => Icon::EXCLAMATION_TRIANGLE,
+            _ => Icon::COG,
+        }
+    }
+
+    fn build_events(command_events: Vec<CommandEvent>, day: Date<Local>) -> Result<Vec<Event>> {
+        command_events
+            .into_iter()
+            .filter_map(|e| {
+                if e.title.is_empty() {
+                    return Some(Err(format!("Event without title at {}", e.time).into()));
+                }
+                let time = match Self::parse_time(&e.time, day) {
+                    Some(t) => t,
+                    // valid date-time, but not on this day
+                    None if DateTime::parse_from_rfc3339(e.time.trim()).is_ok() => return None,
+                    None => {
+                        return Some(Err(format!(
+                            "Invalid time for event {}: {}",
+                            e.title, e.time
+                        )
+                        .into()))
+                    }
+                };
+                Some(Ok(Event::new(
+                    "Custom",
+                    Self::icon_from_name(e.icon.as_deref()),
+                    time,
+                    e.title.clone(),
+                    e.title,
+                    EventBody::PlainText(e.body),
+                    e.details,
+                )))
+            })
+            .collect()
+    }
+}
+
+impl EventProvider for CustomCommand {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (COMMAND_KEY, ConfigType::Text("my-script --day {date}")),
+            (OUTPUT_FORMAT_KEY, ConfigType::Combo),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Custom command"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::COG
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.custom_command.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        if field_name == OUTPUT_FORMAT_KEY {
+            Ok(vec![
+                OutputFormat::JsonLines.desc().to_string(),
+                OutputFormat::Csv.desc().to_string(),
+            ])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                COMMAND_KEY,
+                config.custom_command[config_name].command.to_string(),
+            ),
+            (
+                OUTPUT_FORMAT_KEY,
+                config.custom_command[config_name]
+                    .output_format
+                    .desc()
+                    .to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.custom_command.insert(
+            config_name,
+            CustomCommandConfig {
+                command: config_values.remove(COMMAND_KEY).unwrap(),
+                output_format: OutputFormat::from_desc(
+                    &config_values.remove(OUTPUT_FORMAT_KEY).unwrap_or_default(),
+                ),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.custom_command.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let command_config = &config.custom_command[config_name];
+        // through the shell, so that pipes & co work
+        let output = run_command(
+            Command::new("sh")
+                .arg("-c")
+                .arg(Self::expand_command(&command_config.command, day)),
+        )?;
+        let command_events = match command_config.output_format {
+            OutputFormat::JsonLines => Self::parse_json_lines(&output)?,
+            OutputFormat::Csv => Self::parse_csv(&output)?,
+        };
+        Self::build_events(command_events, day)
+    }
+}
+
+#[test]
+fn it_expands_command_placeholders() {
+    let day = Local.ymd(2020, 3, 23);
+    let expanded = CustomCommand::expand_command("report {date} --from {start} --to {end}", day);
+    assert_eq!(
+        format!(
+            "report 2020-03-23 --from {} --to {}",
+            day.and_hms(0, 0, 0).to_rfc3339(),
+            day.succ().and_hms(0, 0, 0).to_rfc3339()
+        ),
+        expanded
+    );
+}
+
+#[test]
+fn it_parses_json_lines_events() {
+    let day = Local.ymd(2020, 3, 23);
+    let events = CustomCommand::build_events(
+        CustomCommand::parse_json_lines(&format!(
+            r#"{{"time": "09:30", "title": "Standup", "icon": "users"}}
+
+{{"time": "{}", "title": "Deploy", "body": "to production", "details": "v0.6"}}
+{{"time": "{}", "title": "Yesterday"}}
+"#,
+            day.and_hms(16, 45, 0).to_rfc3339(),
+            day.pred().and_hms(16, 45, 0).to_rfc3339()
+        ))
+        .unwrap(),
+        day,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            (NaiveTime::from_hms(9, 30, 0), "Standup", Icon::USERS, None),
+            (
+                NaiveTime::from_hms(16, 45, 0),
+                "Deploy",
+                Icon::COG,
+                Some("v0.6")
+            ),
+        ],
+        events
+            .iter()
+            .map(|e| (
+                e.event_time,
+                e.event_info.as_str(),
+                e.event_type_icon.clone(),
+                e.event_extra_details.as_deref()
+            ))
+            .collect::<Vec<_>>()
+    );
+    assert!(CustomCommand::parse_json_lines("{\"title\": \"no time\"}").is_err());
+}
+
+#[test]
+fn it_parses_csv_events() {
+    let events = CustomCommand::parse_csv(
+        "Title,Time,Body,Icon\r\n\
+         \"Review, round 2\",14:00,\"said \"\"ok\"\"\nthen merged\",code-branch\r\n\
+         Lunch,12:15,,\n",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            CommandEvent {
+                time: "14:00".to_string(),
+                title: "Review, round 2".to_string(),
+                body: "said \"ok\"\nthen merged".to_string(),
+                details: None,
+                icon: Some("code-branch".to_string()),
+            },
+            CommandEvent {
+                time: "12:15".to_string(),
+                title: "Lunch".to_string(),
+                ..Default::default()
+            },
+        ],
+        events
+    );
+    assert!(CustomCommand::parse_csv("name,when\nx,12:00").is_err());
+}
@@ -0,0 +1,509 @@
+// when the computer was started, when the user logged in, locked the screen...
+// We read the systemd journal (the user must be in the systemd-journal or adm
+// group to see the system messages), or wtmp through `last`, which only knows
+// about logins, logouts, boots & shutdowns.
+use super::events::{run_command, ConfigType, Event, EventBody, EventProvider, Result};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+
+#[derive(
+    serde_derive::Deserialize, serde_derive::Serialize, Clone, Copy, Debug, PartialEq, Default,
+)]
+pub enum SessionSource {
+    #[default]
+    Journal,
+    Wtmp,
+}
+
+impl SessionSource {
+    fn desc(self) -> &'static str {
+        match self {
+            SessionSource::Journal => "systemd journal",
+            SessionSource::Wtmp => "wtmp (last)",
+        }
+    }
+
+    fn from_desc(desc: &str) -> SessionSource {
+        if desc == SessionSource::Wtmp.desc() {
+            SessionSource::Wtmp
+        } else {
+            SessionSource::Journal
+        }
+    }
+}
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct DesktopSessionConfig {
+    pub source: SessionSource,
+    // only the sessions of this user, empty for all users
+    pub username: String,
+}
+
+pub struct DesktopSession;
+const SOURCE_KEY: &str = "Source";
+const USERNAME_KEY: &str = "Username (empty for all users)";
+
+// logind sessions are also opened for ssh, cron, and the systemd user
+// manager. These have no seat, we recognize them by their leader process.
+const SEATLESS_SESSION_LEADERS: &[&str] = &[
+    "sshd", "cron", "CRON", "crond", "atd", "systemd", "su", "sudo",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SessionEventKind {
+    Boot,
+    Login,
+    Logout,
+    Lock,
+    Unlock,
+    Suspend,
+    Resume,
+    Shutdown,
+}
+
+impl SessionEventKind {
+    fn desc(self) -> &'static str {
+        match self {
+            SessionEventKind::Boot => "Computer started",
+            SessionEventKind::Login => "Session started",
+            SessionEventKind::Logout => "Session ended",
+            SessionEventKind::Lock => "Screen locked",
+            SessionEventKind::Unlock => "Screen unlocked",
+            SessionEventKind::Suspend => "Suspended",
+            SessionEventKind::Resume => "Resumed",
+            SessionEventKind::Shutdown => "Shut down",
+        }
+    }
+
+    fn icon(self) -> Icon {
+        match self {
+            SessionEventKind::Boot | SessionEventKind::Shutdown => Icon::POWER_OFF,
+            SessionEventKind::Login => Icon::SIGN_IN_ALT,
+            SessionEventKind::Logout => Icon::SIGN_OUT_ALT,
+            SessionEventKind::Lock => Icon::LOCK,
+            SessionEventKind::Unlock => Icon::UNLOCK,
+            SessionEventKind::Suspend => Icon::MOON,
+            SessionEventKind::Resume => Icon::SUN,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct SessionEvent {
+    kind: SessionEventKind,
+    time: DateTime<Local>,
+    message: String,
+}
+
+#[derive(serde_derive::Deserialize, Debug)]
+struct JournalEntry {
+    #[serde(rename = "__REALTIME_TIMESTAMP")]
+    realtime_timestamp: String,
+    #[serde(rename = "SYSLOG_IDENTIFIER")]
+    syslog_identifier: Option<String>,
+    // journalctl gives an array of bytes for messages which are not valid utf8
+    #[serde(rename = "MESSAGE")]
+    message: Option<serde_json::Value>,
+    #[serde(rename = "_PID")]
+    pid: Option<String>,
+    // the logind session messages have the user name & the session leader pid
+    #[serde(rename = "USER_ID")]
+    user_id: Option<String>,
+    #[serde(rename = "LEADER")]
+    leader: Option<String>,
+}
+
+impl DesktopSession {
+    // there's no standard message for the screen lock, these are
+    // the ones of the most common desktops & screensavers
+    fn classify_journal_message(identifier: &str, message: &str) -> Option<SessionEventKind> {
+        let lower_message = message.to_lowercase();
+        match (identifier, message) {
+            ("kernel", m) if m.starts_with("Linux version") => Some(SessionEventKind::Boot),
+            ("systemd-logind", m) if m.starts_with("New session") && m.contains("of user") => {
+                Some(SessionEventKind::Login)
+            }
+            ("systemd-logind", m) if m.starts_with("Removed session") => {
+                Some(SessionEventKind::Logout)
+            }
+            ("systemd-logind", m)
+                if m.starts_with("System is powering down")
+                    || m.starts_with("System is rebooting") =>
+            {
+                Some(SessionEventKind::Shutdown)
+            }
+            ("systemd-sleep", m) if m.starts_with("Entering sleep state") => {
+                Some(SessionEventKind::Suspend)
+            }
+            ("systemd-sleep", m)
+                if m.starts_with("System returned from sleep state")
+                    || m.starts_with("System resumed") =>
+            {
+                Some(SessionEventKind::Resume)
+            }
+            (_, m) if m.contains("gkr-pam: unlocked login keyring") => {
+                Some(SessionEventKind::Unlock)
+            }
+            _ if lower_message.contains("screen unlocked")
+                || lower_message.contains("unlocking the screen") =>
+            {
+                Some(SessionEventKind::Unlock)
+            }
+            _ if lower_message.contains("screen locked")
+                || lower_message.contains("locking the screen")
+                || lower_message.contains("lock screen activated") =>
+            {
+                Some(SessionEventKind::Lock)
+            }
+            _ => None,
+        }
+    }
+
+    fn journal_message(value: &serde_json::Value) -> String {
+        match value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Array(bytes) => String::from_utf8_lossy(
+                &bytes
+                    .iter()
+                    .filter_map(|b| b.as_u64().map(|b| b as u8))
+                    .collect::<Vec<_>>(),
+            )
+            .to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn parse_journal(json_lines: &str, username: &str) -> Vec<SessionEvent> {
+        let entries: Vec<_> = json_lines
+            .lines()
+            .filter_map(|line| serde_json::from_str::<JournalEntry>(line).ok())
+            .collect();
+        let seatless_leaders: HashSet<&str> = entries
+            .iter()
+            .filter(|e| {
+                SEATLESS_SESSION_LEADERS.contains(&e.syslog_identifier.as_deref().unwrap_or(""))
+            })
+            .filter_map(|e| e.pid.as_deref())
+            .collect();
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let message = Self::journal_message(entry.message.as_ref()?);
+                let kind = Self::classify_journal_message(
+                    entry.syslog_identifier.as_deref().unwrap_or(""),
+                    &message,
+                )?;
+                let other_user = !username.is_empty() && entry.user_id.as_deref() != Some(username);
+                let seatless =
+                    matches!(entry.leader.as_deref(), Some(l) if seatless_leaders.contains(l));
+                if matches!(kind, SessionEventKind::Login | SessionEventKind::Logout)
+                    && (other_user || seatless)
+                {
+                    return None;
+                }
+                let micros: i64 = entry.realtime_timestamp.parse().ok()?;
+                Some(SessionEvent {
+                    kind,
+                    time: Local.timestamp_nanos(micros * 1000),
+                    message,
+                })
+            })
+            .collect()
+    }
+
+    fn parse_last_time(time_str: &str) -> Option<DateTime<Local>> {
+        DateTime::parse_from_rfc3339(time_str)
+            .or_else(|_| DateTime::parse_from_str(time_str, "%Y-%m-%dT%H:%M:%S%z"))
+            .ok()
+            .map(|d| d.with_timezone(&Local))
+    }
+
+    // emmanuel tty2     tty2             2020-03-23T08:01:02+01:00 - 2020-03-23T18:00:00+01:00  (09:58)
+    // reboot   system boot  5.4.0-42-generic 2020-03-23T08:00:00+01:00   still running
+    // shutdown system down  5.4.0-42-generic 2020-03-23T18:05:00+01:00 - 2020-03-24T08:00:00+01:00 (13:54)
+    fn parse_last_output(output: &str) -> Vec<SessionEvent> {
+        let line_regex = Regex::new(
+            r"^(?P<user>\S+)\s.*?(?P<start>\d{4}-\d\d-\d\dT\d\d:\d\d:\d\d[+-]\d\d:?\d\d)(?:\s+-\s+(?P<end>\d{4}-\d\d-\d\dT\d\d:\d\d:\d\d[+-]\d\d:?\d\d))?",
+        )
+        .unwrap();
+        let mut events = vec![];
+        for line in output.lines() {
+            let caps = match line_regex.captures(line) {
+                Some(c) => c,
+                None => continue,
+            };
+            let start = match Self::parse_last_time(&caps["start"]) {
+                Some(s) => s,
+                None => continue,
+            };
+            let end = caps
+                .name("end")
+                .and_then(|e| Self::parse_last_time(e.as_str()));
+            let event = |kind, time| SessionEvent {
+                kind,
+                time,
+                message: line.trim().to_string(),
+            };
+            match &caps["user"] {
+                "reboot" => events.push(event(SessionEventKind::Boot, start)),
+                "shutdown" => events.push(event(SessionEventKind::Shutdown, start)),
+                // runlevel changes
+                "runlevel" => {}
+                _ => {
+                    events.push(event(SessionEventKind::Login, start));
+                    if let Some(end) = end {
+                        events.push(event(SessionEventKind::Logout, end));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    // the desktops often log the same thing several times (one unlock
+    // message per keyring for instance)
+    fn dedup_events(mut events: Vec<SessionEvent>) -> Vec<SessionEvent> {
+        events.sort_by_key(|e| e.time);
+        let mut result: Vec<SessionEvent> = vec![];
+        for event in events {
+            if let Some(prev) = result.last() {
+                if prev.kind == event.kind && event.time - prev.time < chrono::Duration::minutes(1)
+                {
+                    continue;
+                }
+            }
+            result.push(event);
+        }
+        result
+    }
+}
+
+impl EventProvider for DesktopSession {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (SOURCE_KEY, ConfigType::Combo),
+            (USERNAME_KEY, ConfigType::Text("")),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Desktop session"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::POWER_OFF
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.desktop_session.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        if field_name == SOURCE_KEY {
+            Ok(vec![
+                SessionSource::Journal.desc().to_string(),
+                SessionSource::Wtmp.desc().to_string(),
+            ])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                SOURCE_KEY,
+                config.desktop_session[config_name]
+                    .source
+                    .desc()
+                    .to_string(),
+            ),
+            (
+                USERNAME_KEY,
+                config.desktop_session[config_name].username.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.desktop_session.insert(
+            config_name,
+            DesktopSessionConfig {
+                source: SessionSource::from_desc(
+                    &config_values.remove(SOURCE_KEY).unwrap_or_default(),
+                ),
+                username: config_values.remove(USERNAME_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.desktop_session.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let session_config = &config.desktop_session[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let since = day_start.format("%Y-%m-%d %H:%M:%S").to_string();
+        let until = next_day_start.format("%Y-%m-%d %H:%M:%S").to_string();
+        let events = match session_config.source {
+            SessionSource::Journal => Self::parse_journal(
+                &run_command(
+                    Command::new("journalctl")
+                        .args(["--output=json", "--no-pager", "--quiet"])
+                        .args(["--output-fields=MESSAGE,SYSLOG_IDENTIFIER,_PID,USER_ID,LEADER"])
+                        .args(["--since", &since, "--until", &until]),
+                )?,
+                &session_config.username,
+            ),
+            SessionSource::Wtmp => {
+                let mut command = Command::new("last");
+                command
+                    .args(["--time-format", "iso", "-x", "-w"])
+                    .args(["--since", &since, "--until", &until]);
+                if !session_config.username.is_empty() {
+                    // the boots & shutdowns are recorded for the pseudo-users reboot & shutdown
+                    command.args([&session_config.username, "reboot", "shutdown"]);
+                }
+                Self::parse_last_output(&run_command(&mut command)?)
+            }
+        };
+        Ok(Self::dedup_events(events)
+            .into_iter()
+            .filter(|e| e.time >= day_start && e.time < next_day_start)
+            .map(|e| {
+                Event::new(
+                    "Session",
+                    e.kind.icon(),
+                    e.time.time(),
+                    e.kind.desc().to_string(),
+                    e.kind.desc().to_string(),
+                    EventBody::PlainText(e.message),
+                    None,
+                )
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn it_parses_journal_session_events() {
+    let events = DesktopSession::parse_journal(
+        r#"{"__REALTIME_TIMESTAMP":"1584950400000000","SYSLOG_IDENTIFIER":"kernel","MESSAGE":"Linux version 5.4.0"}
+{"__REALTIME_TIMESTAMP":"1584950460000000","SYSLOG_IDENTIFIER":"systemd-logind","MESSAGE":"New session 2 of user me."}
+{"__REALTIME_TIMESTAMP":"1584950470000000","SYSLOG_IDENTIFIER":"NetworkManager","MESSAGE":"dhcp4: state changed"}
+{"__REALTIME_TIMESTAMP":"1584954000000000","SYSLOG_IDENTIFIER":"systemd-sleep","MESSAGE":"Entering sleep state 'suspend'..."}
+{"__REALTIME_TIMESTAMP":"1584957600000000","SYSLOG_IDENTIFIER":"systemd-sleep","MESSAGE":"System returned from sleep state."}
+{"__REALTIME_TIMESTAMP":"1584957610000000","SYSLOG_IDENTIFIER":"gdm-password]","MESSAGE":[103,107,114,45,112,97,109,58,32,117,110,108,111,99,107,101,100,32,108,111,103,105,110,32,107,101,121,114,105,110,103]}
+{"__REALTIME_TIMESTAMP":"1584990000000000","SYSLOG_IDENTIFIER":"systemd-logind","MESSAGE":"System is powering down."}"#,
+        "",
+    );
+    assert_eq!(
+        vec![
+            SessionEventKind::Boot,
+            SessionEventKind::Login,
+            SessionEventKind::Suspend,
+            SessionEventKind::Resume,
+            SessionEventKind::Unlock,
+            SessionEventKind::Shutdown
+        ],
+        events.iter().map(|e| e.kind).collect::<Vec<_>>()
+    );
+    assert_eq!(Local.timestamp(1584957600, 0), events[3].time);
+    assert_eq!("gkr-pam: unlocked login keyring", events[4].message);
+}
+
+#[test]
+fn it_parses_last_output() {
+    let events = DesktopSession::parse_last_output(
+        "me       tty2         tty2             2020-03-23T08:01:02+01:00 - 2020-03-23T18:00:00+01:00  (09:58)
+reboot   system boot  5.4.0-42-generic 2020-03-23T08:00:00+01:00   still running
+shutdown system down  5.4.0-42-generic 2020-03-22T18:05:00+01:00 - 2020-03-23T08:00:00+01:00 (13:54)
+
+wtmp begins Sun Mar  1 10:00:00 2020",
+    );
+    assert_eq!(
+        vec![
+            (SessionEventKind::Login, "2020-03-23T07:01:02+00:00"),
+            (SessionEventKind::Logout, "2020-03-23T17:00:00+00:00"),
+            (SessionEventKind::Boot, "2020-03-23T07:00:00+00:00"),
+            (SessionEventKind::Shutdown, "2020-03-22T17:05:00+00:00"),
+        ],
+        events
+            .iter()
+            .map(|e| (e.kind, e.time.with_timezone(&Utc).to_rfc3339()))
+            .collect::<Vec<_>>()
+            .iter()
+            .map(|(k, t)| (*k, t.as_str()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn it_dedups_repeated_session_events() {
+    let event = |kind, secs: i64| SessionEvent {
+        kind,
+        time: Local.timestamp(1584957600 + secs, 0),
+        message: String::new(),
+    };
+    assert_eq!(
+        vec![
+            event(SessionEventKind::Unlock, 0),
+            event(SessionEventKind::Lock, 20)
+        ],
+        DesktopSession::dedup_events(vec![
+            event(SessionEventKind::Lock, 20),
+            event(SessionEventKind::Unlock, 0),
+            event(SessionEventKind::Unlock, 10),
+        ])
+    );
+}
+
+#[test]
+fn it_keeps_only_the_seat_sessions_of_the_user() {
+    let events = DesktopSession::parse_journal(
+        r#"{"__REALTIME_TIMESTAMP":"1584950460000000","SYSLOG_IDENTIFIER":"systemd-logind","MESSAGE":"New session 2 of user me.","USER_ID":"me","LEADER":"1200"}
+{"__REALTIME_TIMESTAMP":"1584950500000000","SYSLOG_IDENTIFIER":"sshd","_PID":"1300","MESSAGE":"pam_unix(sshd:session): session opened for user me(uid=1000) by (uid=0)"}
+{"__REALTIME_TIMESTAMP":"1584950500000000","SYSLOG_IDENTIFIER":"systemd-logind","MESSAGE":"New session 3 of user me.","USER_ID":"me","LEADER":"1300"}
+{"__REALTIME_TIMESTAMP":"1584950600000000","SYSLOG_IDENTIFIER":"systemd-logind","MESSAGE":"New session 4 of user backup.","USER_ID":"backup","LEADER":"1400"}
+{"__REALTIME_TIMESTAMP":"1584950700000000","SYSLOG_IDENTIFIER":"systemd-logind","MESSAGE":"Removed session 3.","USER_ID":"me","LEADER":"1300"}
+{"__REALTIME_TIMESTAMP":"1584950800000000","SYSLOG_IDENTIFIER":"systemd-logind","MESSAGE":"Removed session 4.","USER_ID":"backup","LEADER":"1400"}
+{"__REALTIME_TIMESTAMP":"1584990000000000","SYSLOG_IDENTIFIER":"systemd-logind","MESSAGE":"Removed session 2.","USER_ID":"me","LEADER":"1200"}"#,
+        "me",
+    );
+    assert_eq!(
+        vec![
+            (SessionEventKind::Login, "New session 2 of user me."),
+            (SessionEventKind::Logout, "Removed session 2.")
+        ],
+        events
+            .iter()
+            .map(|e| (e.kind, e.message.as_str()))
+            .collect::<Vec<_>>()
+    );
+}
@@ -1,9 +1,22 @@
+use super::browser_history::BrowserHistory;
+use super::custom_command::CustomCommand;
+use super::desktop_session::DesktopSession;
 use super::email::Email;
+use super::file_modifications::FileModifications;
 use super::git::Git;
+use super::git_diff::{self, FileDiff};
+use super::gitea::Gitea;
+use super::github::Github;
 use super::gitlab::Gitlab;
 use super::ical::Ical;
+use super::jira::Jira;
+use super::journal::Journal;
+use super::mercurial::Mercurial;
 use super::redmine::Redmine;
+use super::shell_history::ShellHistory;
 use super::stackexchange::StackExchange;
+use super::svn::Svn;
+use super::timewarrior::Timewarrior;
 use crate::config::Config;
 use crate::icons::*;
 use chrono::prelude::*;
@@ -11,6 +24,7 @@ use rayon::prelude::*;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::process::Command;
 use std::time::Instant;
 
 #[derive(PartialEq, Copy, Clone)]
@@ -20,10 +34,33 @@ pub enum ConfigType {
     File,
     Folder,
     Combo,
+    Checkbox,
 }
 
 pub type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
 
+// for the providers which get their data from external programs
+pub fn run_command(command: &mut Command) -> Result<String> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// for the extra details of the events with a duration: "45m", "1h 05m"
+pub fn format_duration(duration: chrono::Duration) -> String {
+    match (duration.num_hours(), duration.num_minutes() % 60) {
+        (0, m) => format!("{}m", m),
+        (h, m) => format!("{}h {:02}m", h, m),
+    }
+}
+
 pub trait EventProvider: Sync {
     // TODO this could get derived automatically through a procedural macro
     fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)>;
@@ -71,6 +108,18 @@ pub fn get_event_providers() -> Vec<Box<dyn EventProvider>> {
         Box::new(Ical),
         Box::new(Redmine),
         Box::new(Gitlab),
+        Box::new(Github),
+        Box::new(Gitea),
+        Box::new(Jira),
+        Box::new(ShellHistory),
+        Box::new(BrowserHistory),
+        Box::new(DesktopSession),
+        Box::new(FileModifications),
+        Box::new(Mercurial),
+        Box::new(Svn),
+        Box::new(Timewarrior),
+        Box::new(Journal),
+        Box::new(CustomCommand),
         Box::new(StackExchange),
     ]
 }
@@ -184,6 +233,56 @@ impl EventBody {
     }
 }
 
+/// details which are expensive to get, so we fetch them
+/// only when the user selects the event
+#[derive(Clone, Debug, PartialEq)]
+pub enum LazyEventDetails {
+    GitCommitDiff {
+        repo_folder: String,
+        commit_sha: String,
+    },
+    GitlabTarget {
+        config_name: String,
+        project_id: usize,
+        // MergeRequest or Issue
+        target_type: String,
+        iid: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventDetails {
+    GitCommitDiff(Vec<FileDiff>),
+    Markup(String),
+}
+
+impl LazyEventDetails {
+    pub fn fetch(&self, config: &Config) -> Result<EventDetails> {
+        match self {
+            LazyEventDetails::GitCommitDiff {
+                repo_folder,
+                commit_sha,
+            } => Ok(EventDetails::GitCommitDiff(
+                git_diff::get_commit_file_diffs(repo_folder, commit_sha, config.prefer_dark_theme)?,
+            )),
+            LazyEventDetails::GitlabTarget {
+                config_name,
+                project_id,
+                target_type,
+                iid,
+            } => Ok(EventDetails::Markup(Gitlab::fetch_target_details(
+                config
+                    .gitlab
+                    .get(config_name)
+                    .ok_or_else(|| format!("Unknown gitlab config: {}", config_name))?,
+                *project_id,
+                target_type,
+                *iid,
+            )?)),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Event {
     pub event_type_desc: &'static str,
@@ -193,6 +292,7 @@ pub struct Event {
     pub event_contents_header: String,
     pub event_contents_body: EventBody,
     pub event_extra_details: Option<String>,
+    pub event_lazy_details: Option<LazyEventDetails>,
 }
 
 impl Event {
@@ -213,6 +313,12 @@ impl Event {
             event_contents_header,
             event_contents_body,
             event_extra_details,
+            event_lazy_details: None,
         }
     }
+
+    pub fn with_lazy_details(mut self, lazy_details: LazyEventDetails) -> Event {
+        self.event_lazy_details = Some(lazy_details);
+        self
+    }
 }
@@ -0,0 +1,349 @@
+// for work which doesn't go through git (documents, spreadsheets...):
+// we scan the folder for files modified on the day, based on their mtime,
+// so we only know about the last modification of each file.
+use super::events::{ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct FileModificationsConfig {
+    pub folder: PathBuf,
+    pub ignore_globs: String,
+}
+
+pub struct FileModifications;
+const FOLDER_KEY: &str = "Folder";
+const IGNORE_GLOBS_KEY: &str = "Ignore globs (comma-separated)";
+
+// gitignore-like: a trailing / only matches folders, patterns without
+// any other / match the file name at any depth, the others match the
+// path relative to the scanned folder
+struct IgnoreGlob {
+    regex: Regex,
+    folders_only: bool,
+    match_full_path: bool,
+}
+
+impl IgnoreGlob {
+    fn parse(glob: &str) -> Option<IgnoreGlob> {
+        let folders_only = glob.ends_with('/');
+        let glob = glob.trim_end_matches('/');
+        let match_full_path = glob.contains('/');
+        let glob = glob.trim_start_matches('/');
+        if glob.is_empty() {
+            return None;
+        }
+        let mut regex_str = String::from("^");
+        let mut chars = glob.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    regex_str.push_str(".*");
+                }
+                '*' => regex_str.push_str("[^/]*"),
+                '?' => regex_str.push_str("[^/]"),
+                _ => regex_str.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex_str.push('$');
+        Regex::new(&regex_str).ok().map(|regex| IgnoreGlob {
+            regex,
+            folders_only,
+            match_full_path,
+        })
+    }
+
+    fn parse_list(globs: &str) -> Vec<IgnoreGlob> {
+        globs
+            .split(',')
+            .filter_map(|g| IgnoreGlob::parse(g.trim()))
+            .collect()
+    }
+
+    fn is_match(&self, relative_path: &str, is_folder: bool) -> bool {
+        if self.folders_only && !is_folder {
+            return false;
+        }
+        if self.match_full_path {
+            self.regex.is_match(relative_path)
+        } else {
+            self.regex
+                .is_match(relative_path.rsplit('/').next().unwrap_or(relative_path))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ModifiedFile {
+    // relative to the scanned folder, '' for the folder itself
+    folder: String,
+    name: String,
+    time: DateTime<Local>,
+}
+
+impl FileModifications {
+    fn scan_folder(
+        root: &Path,
+        relative_folder: &str,
+        ignore_globs: &[IgnoreGlob],
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+        result: &mut Vec<ModifiedFile>,
+    ) -> Result<()> {
+        for entry in std::fs::read_dir(root.join(relative_folder))? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            // don't follow symlinks, we could loop or leave the folder
+            if file_type.is_symlink() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative_path = if relative_folder.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", relative_folder, name)
+            };
+            if ignore_globs
+                .iter()
+                .any(|g| g.is_match(&relative_path, file_type.is_dir()))
+            {
+                continue;
+            }
+            if file_type.is_dir() {
+                // unreadable subfolders are skipped rather than failing the whole scan
+                let _ = Self::scan_folder(
+                    root,
+                    &relative_path,
+                    ignore_globs,
+                    day_start,
+                    next_day_start,
+                    result,
+                );
+            } else if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+                let time = DateTime::<Local>::from(modified);
+                if time >= day_start && time < next_day_start {
+                    result.push(ModifiedFile {
+                        folder: relative_folder.to_string(),
+                        name,
+                        time,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn group_by_folder(files: Vec<ModifiedFile>) -> BTreeMap<String, Vec<ModifiedFile>> {
+        let mut groups = BTreeMap::new();
+        for file in files {
+            groups
+                .entry(file.folder.clone())
+                .or_insert_with(Vec::new)
+                .push(file);
+        }
+        for files in groups.values_mut() {
+            files.sort_by_key(|f| f.time);
+        }
+        groups
+    }
+
+    fn build_folder_event(root: &Path, folder: &str, files: &[ModifiedFile]) -> Event {
+        let first = files.first().unwrap();
+        let last = files.last().unwrap();
+        let display_folder = if folder.is_empty() {
+            root.to_string_lossy().to_string()
+        } else {
+            folder.to_string()
+        };
+        let files_markup = files
+            .iter()
+            .map(|f| {
+                format!(
+                    "{}  {}",
+                    f.time.format("%H:%M"),
+                    glib::markup_escape_text(&f.name)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Event::new(
+            "Files",
+            Icon::FOLDER_OPEN,
+            first.time.time(),
+            display_folder.clone(),
+            format!("{} file(s) modified in {}", files.len(), display_folder),
+            EventBody::Markup(
+                format!("<span font-family=\"monospace\">{}</span>", files_markup),
+                WordWrapMode::NoWordWrap,
+            ),
+            Some(format!("Until {}", last.time.format("%H:%M"))),
+        )
+    }
+}
+
+impl EventProvider for FileModifications {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (FOLDER_KEY, ConfigType::Folder),
+            (
+                IGNORE_GLOBS_KEY,
+                ConfigType::Text(".git/, target/, node_modules/, *~, .#*, *.swp"),
+            ),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "File modifications"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::FOLDER_OPEN
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.file_modifications.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        _field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                FOLDER_KEY,
+                config.file_modifications[config_name]
+                    .folder
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            (
+                IGNORE_GLOBS_KEY,
+                config.file_modifications[config_name]
+                    .ignore_globs
+                    .to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.file_modifications.insert(
+            config_name,
+            FileModificationsConfig {
+                folder: PathBuf::from(config_values.remove(FOLDER_KEY).unwrap()),
+                ignore_globs: config_values.remove(IGNORE_GLOBS_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.file_modifications.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let files_config = &config.file_modifications[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let mut files = vec![];
+        Self::scan_folder(
+            &files_config.folder,
+            "",
+            &IgnoreGlob::parse_list(&files_config.ignore_globs),
+            day_start,
+            next_day_start,
+            &mut files,
+        )?;
+        Ok(Self::group_by_folder(files)
+            .iter()
+            .map(|(folder, files)| Self::build_folder_event(&files_config.folder, folder, files))
+            .collect())
+    }
+}
+
+#[test]
+fn it_matches_ignore_globs() {
+    let globs = IgnoreGlob::parse_list("target/, .git/, *.swp, docs/build/**, /out");
+    let is_ignored =
+        |path: &str, is_folder: bool| globs.iter().any(|g| g.is_match(path, is_folder));
+    assert!(is_ignored("target", true));
+    assert!(is_ignored("sub/project/target", true));
+    assert!(!is_ignored("target", false));
+    assert!(is_ignored("sub/.git", true));
+    assert!(is_ignored("notes/.report.odt.swp", false));
+    assert!(!is_ignored("notes/report.odt", false));
+    assert!(is_ignored("docs/build/html/index.html", false));
+    assert!(!is_ignored("sub/docs/build/index.html", false));
+    assert!(is_ignored("out", true));
+    assert!(!is_ignored("sub/out", true));
+}
+
+#[test]
+fn it_groups_modified_files_by_folder() {
+    let root = std::env::temp_dir().join(format!("cigale-files-{}", std::process::id()));
+    for folder in &["docs/drafts", "target/debug", ".git"] {
+        std::fs::create_dir_all(root.join(folder)).unwrap();
+    }
+    for file in &[
+        "todo.txt",
+        "docs/report.odt",
+        "docs/budget.ods",
+        "docs/drafts/plan.md",
+        "target/debug/cigale",
+        ".git/index",
+    ] {
+        std::fs::write(root.join(file), "x").unwrap();
+    }
+    let day_start = Local::today().and_hms(0, 0, 0);
+    let mut files = vec![];
+    FileModifications::scan_folder(
+        &root,
+        "",
+        &IgnoreGlob::parse_list("target/, .git/"),
+        day_start,
+        day_start + chrono::Duration::days(1),
+        &mut files,
+    )
+    .unwrap();
+    let groups = FileModifications::group_by_folder(files);
+    std::fs::remove_dir_all(&root).unwrap();
+    assert_eq!(
+        vec![
+            ("", vec!["todo.txt"]),
+            ("docs", vec!["budget.ods", "report.odt"]),
+            ("docs/drafts", vec!["plan.md"]),
+        ],
+        groups
+            .iter()
+            .map(|(folder, files)| {
+                let mut names: Vec<_> = files.iter().map(|f| f.name.as_str()).collect();
+                names.sort_unstable();
+                (folder.as_str(), names)
+            })
+            .collect::<Vec<_>>()
+    );
+}
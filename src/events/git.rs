@@ -1,10 +1,15 @@
-use super::events::{ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode};
+use super::events::{
+    ConfigType, Event, EventBody, EventProvider, LazyEventDetails, Result, WordWrapMode,
+};
+use super::git_index::GitIndex;
 use crate::config::Config;
 use crate::icons::*;
 use chrono::prelude::*;
-use git2::{Commit, Repository};
+use git2::{Commit, Oid, Repository};
+use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
 // git2 revwalk
 // https://github.com/rust-lang/git2-rs/blob/master/examples/log.rs
@@ -13,6 +18,64 @@ use std::collections::{HashMap, HashSet};
 pub struct GitConfig {
     pub repo_folder: String, // Path
     pub commit_author: String,
+    #[serde(default)] // include_reflog was added later, after 0.5.4
+    pub include_reflog: bool,
+}
+
+// git operations which we can find in the reflog, but which
+// don't show up when walking the commits of the branches.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+enum ReflogOperation {
+    Amend,
+    Rebase,
+    Checkout,
+    Reset,
+    CherryPick,
+    Merge,
+    Pull,
+    Revert,
+    Branch,
+}
+
+impl ReflogOperation {
+    fn desc(self) -> &'static str {
+        match self {
+            ReflogOperation::Amend => "Amend",
+            ReflogOperation::Rebase => "Rebase",
+            ReflogOperation::Checkout => "Checkout",
+            ReflogOperation::Reset => "Reset",
+            ReflogOperation::CherryPick => "Cherry-pick",
+            ReflogOperation::Merge => "Merge",
+            ReflogOperation::Pull => "Pull",
+            ReflogOperation::Revert => "Revert",
+            ReflogOperation::Branch => "Branch",
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct RemoteUrl {
+    host: String,
+    web_base_url: String, // https://host, with the port for http remotes
+    path: String,         // without the .git suffix
+}
+
+// the commits listed in a push event
+const MAX_PUSHED_COMMITS: usize = 50;
+
+lazy_static! {
+    static ref REFLOG_MSG_REGEX: Regex =
+        Regex::new(r"^(?P<cmd>[^:(]+?)(?: \((?P<step>[^)]+)\))?: (?P<detail>.*)$").unwrap();
+}
+
+#[derive(PartialEq, Debug)]
+struct ReflogActivity {
+    operation: ReflogOperation,
+    time: DateTime<Local>,
+    // the ref target after the activity
+    new_oid: Oid,
+    summary: String,
+    details: Vec<String>,
 }
 
 impl Git {
@@ -20,10 +83,8 @@ impl Git {
         Utc.timestamp(time.seconds(), 0).with_timezone(&Local)
     }
 
+    // for merge commits, we diff against the first parent
     fn get_commit_diff<'a>(repo: &'a Repository, c: &Commit) -> Option<git2::Diff<'a>> {
-        if c.parent_count() > 1 {
-            return None;
-        }
         let commit_tree = c.tree().ok()?;
         let parent = c.parent(0).ok()?;
         let parent_tree = parent.tree().ok()?;
@@ -57,7 +118,7 @@ impl Git {
     }
 
     // common prefix to all the files
-    fn get_files_root(files: &[std::path::PathBuf]) -> String {
+    pub fn get_files_root(files: &[std::path::PathBuf]) -> String {
         let paths_for_each_file: Vec<Vec<&str>> = files
             .iter()
             .filter_map(|f| f.iter().map(|c| c.to_str()).collect())
@@ -81,9 +142,8 @@ impl Git {
         common_prefix.join("/")
     }
 
-    // collaborate with the gitlab plugin... if this repo matches a configured
-    // gitlab event source, then we can build a URL to open the commit in the
-    // browser in the gitlab GUI.
+    // offer to open the commits in the browser: we look at the
+    // origin remote of the repo to find out which forge hosts it.
     fn get_commit_display_url(repo: &Repository, config: &Config) -> Result<Option<String>> {
         let origin_url = match repo
             .find_remote("origin")
@@ -93,59 +153,592 @@ impl Git {
             Some(v) => v,
             None => return Ok(None),
         };
+        Ok(Self::remote_url_to_commit_display_url(&origin_url, config))
+    }
 
-        let url = Self::get_commit_display_url_gitlab(&origin_url, config)?.or_else(|| {
-            Self::get_commit_display_url_github(&origin_url)
-                .ok()
-                .flatten()
-        });
-        Ok(url)
-    }
-
-    fn get_commit_display_url_gitlab(origin_url: &str, config: &Config) -> Result<Option<String>> {
-        // we have the repo origin url, something like git@gitlab.lit-transit.com:afc/afc.git
-        // and we also have the gitlab URL, something like https://gitlab.lit-transit.com/
-        // find out whether the origin URL contains the gitlab URL minus the protocol
-        let url_protocol_regex = Regex::new(r"^[a-z]+://").unwrap();
-        let matching_gitlab_cfg = match config.gitlab.iter().find(|(_, v)| {
-            origin_url.contains(
-                &url_protocol_regex
-                    .replace_all(&v.gitlab_url, "")
-                    .to_string(),
-            )
-        }) {
-            Some((_, v)) => v,
-            None => return Ok(None),
-        };
+    // the remote url can look like:
+    // git@github.com:emmanueltouzery/cigale.git
+    // ssh://git@git.mycompany.com:2222/team/project
+    // https://git.mycompany.com:8443/team/project.git
+    fn parse_remote_url(remote_url: &str) -> Option<RemoteUrl> {
+        let url_with_scheme_regex = Regex::new(
+            r"^(?P<scheme>[a-z][a-z0-9+.-]*)://(?:[^@/]+@)?(?P<host>[^:/]+)(?::(?P<port>\d+))?/(?P<path>.+?)(?:\.git)?/?$",
+        )
+        .unwrap();
+        let scp_like_url_regex =
+            Regex::new(r"^(?:[^@/]+@)?(?P<host>[^:/]+):(?P<path>.+?)(?:\.git)?/?$").unwrap();
+        if let Some(caps) = url_with_scheme_regex.captures(remote_url) {
+            let scheme = &caps["scheme"];
+            let is_http = scheme == "http" || scheme == "https";
+            let web_host = match caps.name("port") {
+                // the port of a ssh remote is not the port of the web server
+                Some(port) if is_http => format!("{}:{}", &caps["host"], port.as_str()),
+                _ => caps["host"].to_string(),
+            };
+            Some(RemoteUrl {
+                host: caps["host"].to_string(),
+                web_base_url: format!("{}://{}", if is_http { scheme } else { "https" }, web_host),
+                path: caps["path"].to_string(),
+            })
+        } else {
+            scp_like_url_regex
+                .captures(remote_url)
+                .map(|caps| RemoteUrl {
+                    host: caps["host"].to_string(),
+                    web_base_url: format!("https://{}", &caps["host"]),
+                    path: caps["path"].to_string(),
+                })
+        }
+    }
 
-        // git@gitlab.lit-transit.com:afc/afc.git => afc/afc [keep between ':' and '.git']
-        let gitlab_projectname_regex = Regex::new(r":(.*?)\.git").unwrap();
-        let gitlab_project_name = match gitlab_projectname_regex.captures_iter(origin_url).next() {
-            Some(v) => v[1].to_string(),
-            None => return Ok(None),
+    // returns the url to display a commit in the browser,
+    // minus the commit sha which must be appended to it.
+    // We try the resolvers in turn, the first one to recognize the remote wins.
+    fn remote_url_to_commit_display_url(remote_url: &str, config: &Config) -> Option<String> {
+        let remote = Self::parse_remote_url(remote_url)?;
+        let resolvers: &[fn(&RemoteUrl, &Config) -> Option<String>] = &[
+            Self::get_commit_display_url_custom,
+            Self::get_commit_display_url_gitlab,
+            Self::get_commit_display_url_github,
+            Self::get_commit_display_url_bitbucket,
+            Self::get_commit_display_url_azure_devops,
+            Self::get_commit_display_url_sourcehut,
+            Self::get_commit_display_url_gitea,
+        ];
+        resolvers
+            .iter()
+            .find_map(|resolver| resolver(&remote, config))
+    }
+
+    // url templates from the config file, for forges we can't recognize
+    fn get_commit_display_url_custom(remote: &RemoteUrl, config: &Config) -> Option<String> {
+        config
+            .git_commit_url_templates
+            .get(&remote.host)
+            .map(|template| {
+                template
+                    .replace("{base_url}", &remote.web_base_url)
+                    .replace("{path}", &remote.path)
+            })
+    }
+
+    // we have the repo origin host, something like gitlab.lit-transit.com
+    // and we also have the forge URL, something like https://gitlab.lit-transit.com/
+    // find out whether the forge URL minus the protocol is on the same host.
+    // If so, return the repo path, minus the subfolder of the forge instance
+    // if it's not installed at the root of the server.
+    fn get_forge_repo_path(remote: &RemoteUrl, forge_url: &str) -> Option<String> {
+        let url_no_protocol = forge_url.splitn(2, "://").last().unwrap_or("");
+        let mut parts = url_no_protocol.trim_end_matches('/').splitn(2, '/');
+        let host = parts.next().unwrap_or("");
+        let subfolder = parts.next().unwrap_or("");
+        // the forge url may contain a port
+        if host.split(':').next() != Some(remote.host.as_str()) {
+            return None;
+        }
+        Some(
+            remote
+                .path
+                .strip_prefix(&format!("{}/", subfolder))
+                .unwrap_or(&remote.path)
+                .to_string(),
+        )
+    }
+
+    // collaborate with the gitlab plugin... if this repo matches a configured
+    // gitlab event source, then we can build a URL to open the commit in the
+    // browser in the gitlab GUI.
+    fn get_commit_display_url_gitlab(remote: &RemoteUrl, config: &Config) -> Option<String> {
+        let (gitlab_cfg, gitlab_project_name) = match config
+            .gitlab
+            .values()
+            .find_map(|v| Self::get_forge_repo_path(remote, &v.gitlab_url).map(|p| (v, p)))
+        {
+            Some(v) => v,
+            None if remote.host == "gitlab.com" => {
+                return Some(format!("https://gitlab.com/{}/-/commit/", remote.path))
+            }
+            None => return None,
         };
 
         // combine the URL from the gitlab config plus the project name
         // that we extracted from the repo upstream URL to get the URL
         // to display a commit.
         // (the commit sha will have to be appended to this URL)
-        Ok(Some(format!(
+        Some(format!(
             "{}/{}/commit/",
-            matching_gitlab_cfg.gitlab_url, gitlab_project_name
-        )))
+            gitlab_cfg.gitlab_url, gitlab_project_name
+        ))
+    }
+
+    // github.com, and github enterprise instances (github.mycompany.com,
+    // or any host configured in the github event source)
+    fn get_commit_display_url_github(remote: &RemoteUrl, config: &Config) -> Option<String> {
+        let is_configured_host = config.github.values().any(|g| {
+            g.github_url
+                .split("://")
+                .nth(1)
+                .and_then(|u| u.split(&['/', ':'][..]).next())
+                == Some(remote.host.as_str())
+        });
+        if remote.host == "github.com" || remote.host.starts_with("github.") || is_configured_host {
+            Some(format!("{}/{}/commit/", remote.web_base_url, remote.path))
+        } else {
+            None
+        }
+    }
+
+    fn get_commit_display_url_bitbucket(remote: &RemoteUrl, _config: &Config) -> Option<String> {
+        if remote.host == "bitbucket.org" {
+            return Some(format!("https://bitbucket.org/{}/commits/", remote.path));
+        }
+        if !remote.host.starts_with("bitbucket.") {
+            return None;
+        }
+        // self-hosted bitbucket server:
+        // ssh://git@bitbucket.mycompany.com:7999/proj/repo.git
+        // https://bitbucket.mycompany.com/scm/proj/repo.git
+        let path = remote.path.strip_prefix("scm/").unwrap_or(&remote.path);
+        let mut parts = path.splitn(2, '/');
+        match (parts.next(), parts.next()) {
+            (Some(project), Some(repo)) => Some(format!(
+                "{}/projects/{}/repos/{}/commits/",
+                remote.web_base_url,
+                project.to_uppercase(),
+                repo
+            )),
+            _ => None,
+        }
+    }
+
+    fn get_commit_display_url_azure_devops(remote: &RemoteUrl, _config: &Config) -> Option<String> {
+        match remote.host.as_str() {
+            // git@ssh.dev.azure.com:v3/org/project/repo
+            // org@vs-ssh.visualstudio.com:v3/org/project/repo
+            "ssh.dev.azure.com" | "vs-ssh.visualstudio.com" => {
+                let parts: Vec<&str> = remote.path.split('/').collect();
+                match parts.as_slice() {
+                    ["v3", org, project, repo] => Some(format!(
+                        "https://dev.azure.com/{}/{}/_git/{}/commit/",
+                        org, project, repo
+                    )),
+                    _ => None,
+                }
+            }
+            // https://org@dev.azure.com/org/project/_git/repo
+            // https://org.visualstudio.com/project/_git/repo
+            h if (h == "dev.azure.com" || h.ends_with(".visualstudio.com"))
+                && remote.path.contains("/_git/") =>
+            {
+                Some(format!("{}/{}/commit/", remote.web_base_url, remote.path))
+            }
+            _ => None,
+        }
     }
 
-    // autodetect github repos & offer to open the commits in the browser
-    fn get_commit_display_url_github(origin_url: &str) -> Result<Option<String>> {
-        let github_projectname_regex =
-            Regex::new(r"^git@github\.com:(?P<reponame1>.*)\.git$|^https://github\.com/(?P<reponame2>.*)\.git$").unwrap();
-        let github_project_name = github_projectname_regex
-            .captures_iter(origin_url)
-            .next()
-            .and_then(|v| v.name("reponame1").or_else(|| v.name("reponame2")))
-            .map(|m| m.as_str());
+    fn get_commit_display_url_sourcehut(remote: &RemoteUrl, _config: &Config) -> Option<String> {
+        if remote.host == "git.sr.ht" {
+            Some(format!("https://git.sr.ht/{}/commit/", remote.path))
+        } else {
+            None
+        }
+    }
 
-        Ok(github_project_name.map(|n| format!("https://github.com/{}/commit/", n)))
+    // gitea and forgejo instances are often self-hosted. We recognize the
+    // servers configured in the gitea event source, otherwise we can only guess
+    // from the server name. Other servers can be set up in the config file.
+    fn get_commit_display_url_gitea(remote: &RemoteUrl, config: &Config) -> Option<String> {
+        let configured = config.gitea.values().find_map(|v| {
+            Self::get_forge_repo_path(remote, &v.gitea_url).map(|repo_path| {
+                format!(
+                    "{}/{}/commit/",
+                    v.gitea_url.trim_end_matches('/'),
+                    repo_path
+                )
+            })
+        });
+        if configured.is_some() {
+            return configured;
+        }
+        if remote.host == "codeberg.org"
+            || remote.host.contains("gitea")
+            || remote.host.contains("forgejo")
+        {
+            Some(format!("{}/{}/commit/", remote.web_base_url, remote.path))
+        } else {
+            None
+        }
+    }
+
+    // reflog messages look like:
+    // "checkout: moving from master to feature"
+    // "rebase -i (pick): commit summary"
+    // "merge feature: Fast-forward"
+    fn parse_reflog_message(msg: &str) -> Option<(ReflogOperation, Option<&str>, String)> {
+        let caps = REFLOG_MSG_REGEX.captures(msg)?;
+        let cmd = caps.name("cmd")?.as_str();
+        let step = caps.name("step").map(|m| m.as_str());
+        let detail = caps.name("detail")?.as_str();
+        let operation = match cmd.split(' ').next()? {
+            // plain commits are already reported by the revwalk
+            "commit" if step == Some("amend") => ReflogOperation::Amend,
+            "rebase" => ReflogOperation::Rebase,
+            // git pull --rebase
+            "pull" if step.is_some() => ReflogOperation::Rebase,
+            "pull" => ReflogOperation::Pull,
+            "checkout" => ReflogOperation::Checkout,
+            "reset" => ReflogOperation::Reset,
+            "cherry-pick" => ReflogOperation::CherryPick,
+            "merge" => ReflogOperation::Merge,
+            "revert" => ReflogOperation::Revert,
+            "branch" => ReflogOperation::Branch,
+            _ => return None,
+        };
+        let summary = match cmd.strip_prefix("merge ") {
+            Some(merged_branch) => format!("{} ({})", merged_branch, detail),
+            None => detail.to_string(),
+        };
+        Some((operation, step, summary))
+    }
+
+    // the entries must be sorted from the oldest to the newest.
+    // a rebase leaves many entries in the reflog (start, one per
+    // commit, finish): we group them in a single activity.
+    fn group_reflog_entries(entries: &[(DateTime<Local>, Oid, String)]) -> Vec<ReflogActivity> {
+        let mut result: Vec<ReflogActivity> = vec![];
+        let mut open_rebase_idx = None;
+        for (time, new_oid, msg) in entries {
+            let (operation, step, summary) = match Self::parse_reflog_message(msg) {
+                Some(p) => p,
+                None => continue,
+            };
+            match (open_rebase_idx, operation, step) {
+                (_, ReflogOperation::Rebase, Some("start")) => {
+                    open_rebase_idx = Some(result.len());
+                    result.push(ReflogActivity {
+                        operation,
+                        time: *time,
+                        new_oid: *new_oid,
+                        summary,
+                        details: vec![],
+                    });
+                }
+                (Some(idx), _, _) => {
+                    let rebase: &mut ReflogActivity = &mut result[idx];
+                    rebase.new_oid = *new_oid;
+                    if operation == ReflogOperation::Rebase
+                        && (step == Some("finish") || step == Some("abort"))
+                    {
+                        open_rebase_idx = None;
+                    }
+                    rebase.details.push(match step {
+                        Some(s) => format!("{}: {}", s, summary),
+                        None => format!("{}: {}", operation.desc(), summary),
+                    });
+                }
+                (None, _, _) => result.push(ReflogActivity {
+                    operation,
+                    time: *time,
+                    new_oid: *new_oid,
+                    summary,
+                    details: vec![],
+                }),
+            }
+        }
+        result
+    }
+
+    fn get_reflog_events(
+        repo: &Repository,
+        day_start: &DateTime<Local>,
+        next_day_start: &DateTime<Local>,
+    ) -> Result<Vec<Event>> {
+        let mut ref_names = vec!["HEAD".to_string()];
+        for branch in repo
+            .branches(Some(git2::BranchType::Local))?
+            .filter_map(|b| b.ok())
+        {
+            if let Some(ref_name) = branch.0.get().name() {
+                ref_names.push(ref_name.to_string());
+            }
+        }
+        let mut activities_by_ref = vec![];
+        for ref_name in ref_names {
+            let reflog = repo.reflog(&ref_name)?;
+            // the reflog goes from the newest to the oldest entry
+            let mut entries: Vec<(DateTime<Local>, Oid, String)> = reflog
+                .iter()
+                .map(|e| {
+                    (
+                        Git::git2_time_to_datetime(e.committer().when()),
+                        e.id_new(),
+                        e.message().unwrap_or("").to_string(),
+                    )
+                })
+                .filter(|(time, _, _)| time >= day_start && time < next_day_start)
+                .collect();
+            entries.reverse();
+            let activities = Self::group_reflog_entries(&entries);
+            activities_by_ref.push((ref_name, activities));
+        }
+        Ok(Self::dedup_reflog_activities(activities_by_ref)
+            .iter()
+            .map(|(ref_name, activity)| Self::build_reflog_event(ref_name, activity))
+            .collect())
+    }
+
+    // HEAD and the checked out branch log the same operations, but the branch
+    // only has the last entry of a rebase: compare the resulting commits, and
+    // keep the grouped activity of HEAD, which comes first. We only dedup the
+    // branches against HEAD: the same operation can legitimately happen twice
+    // in a day, for instance checking out the same branch in the morning and
+    // in the afternoon.
+    fn dedup_reflog_activities(
+        activities_by_ref: Vec<(String, Vec<ReflogActivity>)>,
+    ) -> Vec<(String, ReflogActivity)> {
+        let mut head_activities = HashSet::new();
+        let mut result = vec![];
+        for (ref_name, activities) in activities_by_ref {
+            let is_head = ref_name == "HEAD";
+            for activity in activities {
+                if is_head {
+                    head_activities.insert((activity.new_oid, activity.operation));
+                } else if head_activities.contains(&(activity.new_oid, activity.operation)) {
+                    continue;
+                }
+                result.push((ref_name.clone(), activity));
+            }
+        }
+        result
+    }
+
+    fn build_reflog_event(ref_name: &str, activity: &ReflogActivity) -> Event {
+        let header = format!("{}: {}", activity.operation.desc(), activity.summary);
+        Event::new(
+            "Git",
+            Icon::HISTORY,
+            activity.time.time(),
+            header.clone(),
+            header,
+            EventBody::Markup(
+                format!(
+                    "<span font-family=\"monospace\">{}\n\n{}</span>",
+                    glib::markup_escape_text(ref_name),
+                    glib::markup_escape_text(&activity.details.join("\n"))
+                ),
+                WordWrapMode::NoWordWrap,
+            ),
+            Some(activity.operation.desc().to_string()),
+        )
+    }
+
+    // Merge branch 'feature' into master
+    // Merge remote-tracking branch 'origin/feature'
+    // Merge pull request #12 from user/feature
+    fn get_merged_branch_name(commit_msg: &str) -> Option<&str> {
+        let merge_msg_regex = Regex::new(
+            r"^Merge (?:(?:remote-tracking )?branch '(?P<branch>[^']+)'|pull request #\d+ from (?P<pr_branch>\S+))",
+        )
+        .unwrap();
+        let caps = merge_msg_regex.captures(commit_msg)?;
+        caps.name("branch")
+            .or_else(|| caps.name("pr_branch"))
+            .map(|m| m.as_str())
+    }
+
+    // annotated tags only: lightweight tags don't know who created them, or when
+    fn get_tag_events(
+        repo: &Repository,
+        commit_author: &str,
+        day_start: &DateTime<Local>,
+        next_day_start: &DateTime<Local>,
+        commit_display_url: &Option<String>,
+    ) -> Result<Vec<Event>> {
+        let mut tag_ids = vec![];
+        repo.tag_foreach(|oid, _name| {
+            tag_ids.push(oid);
+            true
+        })?;
+        let mut result = vec![];
+        for tag in tag_ids
+            .into_iter()
+            .filter_map(|oid| repo.find_tag(oid).ok())
+        {
+            let tagger = match tag.tagger() {
+                Some(t) => t,
+                None => continue,
+            };
+            let tag_date = Git::git2_time_to_datetime(tagger.when());
+            if tagger.name() != Some(commit_author)
+                || tag_date < *day_start
+                || tag_date >= *next_day_start
+            {
+                continue;
+            }
+            let tag_name = tag.name().unwrap_or("").to_string();
+            let open_in_browser = match commit_display_url {
+                Some(cdu) => format!(
+                    "<a href=\"{}/{}\">Open in browser</a>\n\n",
+                    cdu,
+                    tag.target_id()
+                ),
+                None => "".to_string(),
+            };
+            let target_summary = repo
+                .find_commit(tag.target_id())
+                .ok()
+                .and_then(|c| c.summary().map(|s| s.to_string()))
+                .unwrap_or_default();
+            result.push(Event::new(
+                "Git",
+                Icon::TAG,
+                tag_date.time(),
+                format!("Tag {}", tag_name),
+                format!("Tag {}", tag_name),
+                EventBody::Markup(
+                    format!(
+                        "{}<span font-family=\"monospace\">{}\n\n{}\n\nTagged commit: {}</span>",
+                        open_in_browser,
+                        glib::markup_escape_text(&tag_name),
+                        glib::markup_escape_text(tag.message().unwrap_or("")),
+                        glib::markup_escape_text(&target_summary),
+                    ),
+                    WordWrapMode::NoWordWrap,
+                ),
+                Some("Tag".to_string()),
+            ));
+        }
+        Ok(result)
+    }
+
+    // git records pushes in the reflog of the remote-tracking branches
+    fn get_push_events(
+        repo: &Repository,
+        commit_author: &str,
+        day_start: &DateTime<Local>,
+        next_day_start: &DateTime<Local>,
+    ) -> Result<Vec<Event>> {
+        let mut result = vec![];
+        let ref_names: Vec<String> = repo
+            .references_glob("refs/remotes/*")?
+            .filter_map(|r| r.ok())
+            .filter_map(|r| r.name().map(|n| n.to_string()))
+            .collect();
+        for ref_name in &ref_names {
+            let reflog = match repo.reflog(ref_name) {
+                Ok(r) => r,
+                Err(_) => continue,
+            };
+            for entry in reflog.iter() {
+                let push_date = Git::git2_time_to_datetime(entry.committer().when());
+                // in a shared clone, other people push too
+                if entry.message() != Some("update by push")
+                    || entry.committer().name() != Some(commit_author)
+                    || push_date < *day_start
+                    || push_date >= *next_day_start
+                {
+                    continue;
+                }
+                let remote_branch = ref_name.trim_start_matches("refs/remotes/");
+                let (mut pushed_commits, truncated) = Self::get_pushed_commit_summaries(
+                    repo,
+                    &ref_names,
+                    ref_name,
+                    entry.id_old(),
+                    entry.id_new(),
+                )
+                .unwrap_or_else(|e| {
+                    log::warn!("Can't list the commits pushed to {}: {}", ref_name, e);
+                    (vec![], false)
+                });
+                let commits_desc = if truncated {
+                    pushed_commits.push("...".to_string());
+                    format!("More than {} commits pushed", MAX_PUSHED_COMMITS)
+                } else {
+                    format!("{} commit(s) pushed", pushed_commits.len())
+                };
+                result.push(Event::new(
+                    "Git",
+                    Icon::UPLOAD,
+                    push_date.time(),
+                    format!("Push to {}", remote_branch),
+                    format!("Push to {}", remote_branch),
+                    EventBody::Markup(
+                        format!(
+                            "<span font-family=\"monospace\">{}\n\n{}</span>",
+                            glib::markup_escape_text(remote_branch),
+                            glib::markup_escape_text(&pushed_commits.join("\n"))
+                        ),
+                        WordWrapMode::NoWordWrap,
+                    ),
+                    Some(commits_desc),
+                ));
+            }
+        }
+        Ok(result)
+    }
+
+    // refs/remotes/origin/feature/login => refs/remotes/origin/
+    // branch names can contain slashes, so we ask the remotes config,
+    // falling back to the first component for remotes which were removed.
+    fn remote_ref_prefix(repo: &Repository, ref_name: &str) -> String {
+        let remote = repo
+            .branch_remote_name(ref_name)
+            .ok()
+            .and_then(|r| r.as_str().map(|s| s.to_string()))
+            .or_else(|| {
+                ref_name
+                    .trim_start_matches("refs/remotes/")
+                    .split('/')
+                    .next()
+                    .map(|s| s.to_string())
+            })
+            .unwrap_or_default();
+        format!("refs/remotes/{}/", remote)
+    }
+
+    // returns the summaries, and whether we truncated the list
+    fn get_pushed_commit_summaries(
+        repo: &Repository,
+        remote_ref_names: &[String],
+        ref_name: &str,
+        old_tip: git2::Oid,
+        new_tip: git2::Oid,
+    ) -> Result<(Vec<String>, bool)> {
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(new_tip)?;
+        if old_tip.is_zero() {
+            // first push of a new branch: the commits which are not on the
+            // other branches of the same remote. The tips of the other branches
+            // are the current ones, not the ones at push time, but that's the
+            // best we can do.
+            let remote_prefix = Self::remote_ref_prefix(repo, ref_name);
+            for other_ref in remote_ref_names
+                .iter()
+                .filter(|r| r.as_str() != ref_name && r.starts_with(&remote_prefix))
+            {
+                if let Ok(oid) = repo.refname_to_id(other_ref) {
+                    revwalk.hide(oid).ok();
+                }
+            }
+        } else {
+            // if we force-pushed, the old tip may not be in our repo anymore
+            revwalk.hide(old_tip).ok();
+        }
+        let mut summaries = revwalk
+            .take(MAX_PUSHED_COMMITS + 1)
+            .map(|oid| {
+                let commit = repo.find_commit(oid?)?;
+                Ok(format!(
+                    "{} {}",
+                    &commit.id().to_string()[..8],
+                    commit.summary().unwrap_or("")
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let truncated = summaries.len() > MAX_PUSHED_COMMITS;
+        summaries.truncate(MAX_PUSHED_COMMITS);
+        Ok((summaries, truncated))
     }
 
     fn build_event(
@@ -165,25 +758,42 @@ impl Git {
             Some(cdu) => format!("<a href=\"{}/{}\">Open in browser</a>", cdu, c.id()),
             None => "".to_string(),
         };
+        let is_merge = c.parent_count() > 1;
+        let merged_branch = if is_merge {
+            Git::get_merged_branch_name(base_msg)
+        } else {
+            None
+        };
+        let branch_desc = match merged_branch {
+            Some(m) => format!("{}, merged {}", branch, glib::markup_escape_text(m)),
+            None => branch.to_string(),
+        };
         let (contents, extra_details) = match diff {
             None => (
-                format!("{}\n{}\n{}", open_in_browser, branch, message_contents),
+                format!("{}\n{}\n{}", open_in_browser, branch_desc, message_contents),
                 None,
             ),
             Some(d) => (
                 format!(
                     "{}\n\n{}\n<span font-family=\"monospace\">{}\n\n{}</span>",
                     open_in_browser,
-                    branch,
+                    branch_desc,
                     message_contents,
                     &Git::get_commit_full_diffstr(&d).unwrap_or_else(|| "".to_string())
                 ),
-                Git::get_commit_extra_info(&d),
+                match merged_branch {
+                    Some(m) => Some(format!("Merged {}", m)),
+                    None => Git::get_commit_extra_info(&d),
+                },
             ),
         };
         Event::new(
             "Git",
-            Icon::CODE_BRANCH,
+            if is_merge {
+                Icon::PROJECT_DIAGRAM
+            } else {
+                Icon::CODE_BRANCH
+            },
             commit_date.time(),
             c.summary().unwrap_or("").to_string(),
             contents_header,
@@ -191,17 +801,118 @@ impl Git {
             extra_details,
         )
     }
+
+    fn get_repo_events(
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+        index_folder: &Path,
+    ) -> Result<Vec<Event>> {
+        let git_config = &config.git[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let repo = Repository::open(&git_config.repo_folder)?;
+        let commit_display_url = Self::get_commit_display_url(&repo, config)?;
+        log::info!("gitlab commit display url: {:?}", commit_display_url);
+        let mut result = if Self::has_branch_tip_since(&repo, &day_start)? {
+            Self::get_commit_events(
+                &repo,
+                git_config,
+                &day_start,
+                &next_day_start,
+                &commit_display_url,
+                index_folder,
+            )?
+        } else {
+            // early abort: no need to load the index if no branch
+            // got a commit since that day
+            vec![]
+        };
+        result.append(&mut Self::get_tag_events(
+            &repo,
+            &git_config.commit_author,
+            &day_start,
+            &next_day_start,
+            &commit_display_url,
+        )?);
+        result.append(&mut Self::get_push_events(
+            &repo,
+            &git_config.commit_author,
+            &day_start,
+            &next_day_start,
+        )?);
+        if git_config.include_reflog {
+            result.append(&mut Self::get_reflog_events(
+                &repo,
+                &day_start,
+                &next_day_start,
+            )?);
+        }
+        result.sort_by_key(|e| e.event_time); // need to sort for the dedup to work
+        result.dedup_by(|e1, e2| {
+            // deduplicate identical commits seen in different branches
+            // (the body will be different since we put the branch name there)
+            e1.event_time == e2.event_time
+                && e1.event_contents_header == e2.event_contents_header
+                && e1.event_info == e2.event_info
+        });
+        Ok(result)
+    }
+
+    fn has_branch_tip_since(repo: &Repository, day_start: &DateTime<Local>) -> Result<bool> {
+        for tip in GitIndex::current_tips(repo)?.values() {
+            let tip_commit = repo.find_commit(*tip)?;
+            if Git::git2_time_to_datetime(tip_commit.time()) >= *day_start {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_commit_events(
+        repo: &Repository,
+        git_config: &GitConfig,
+        day_start: &DateTime<Local>,
+        next_day_start: &DateTime<Local>,
+        commit_display_url: &Option<String>,
+        index_folder: &Path,
+    ) -> Result<Vec<Event>> {
+        let index = GitIndex::load(index_folder, &git_config.repo_folder, repo)?;
+        index
+            .commits_between(
+                day_start.timestamp(),
+                next_day_start.timestamp(),
+                &git_config.commit_author,
+            )?
+            .into_iter()
+            .map(|(oid, indexed_commit)| {
+                let c = repo.find_commit(oid)?;
+                Ok(Self::build_event(
+                    &c,
+                    repo,
+                    indexed_commit.display_branch(),
+                    commit_display_url,
+                )
+                .with_lazy_details(LazyEventDetails::GitCommitDiff {
+                    repo_folder: git_config.repo_folder.clone(),
+                    commit_sha: oid.to_string(),
+                }))
+            })
+            .collect()
+    }
 }
 
 pub struct Git;
 const REPO_FOLDER_KEY: &str = "Repository folder";
 const COMMIT_AUTHOR_KEY: &str = "Commit Author";
+const INCLUDE_REFLOG_KEY: &str = "Include reflog activity";
 
 impl EventProvider for Git {
     fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
         vec![
             (REPO_FOLDER_KEY, ConfigType::Folder),
             (COMMIT_AUTHOR_KEY, ConfigType::Combo),
+            (INCLUDE_REFLOG_KEY, ConfigType::Checkbox),
         ]
     }
 
@@ -223,9 +934,8 @@ impl EventProvider for Git {
         field_name: &'static str,
     ) -> Result<Vec<String>> {
         // for the 'commit author' combo box, we offer the list
-        // of authors for the repo. This is quite slow though,
-        // hopefully there is a faster way?
-        // https://stackoverflow.com/questions/60464449/get-the-list-of-authors-in-a-git-repository-efficiently-with-libgit2
+        // of authors for the repo. Walking the history is slow
+        // for big repos, so we read them from our commit index.
         let git_path = cur_values
             .get(REPO_FOLDER_KEY)
             .map(|s| s.as_str())
@@ -234,30 +944,7 @@ impl EventProvider for Git {
             return Ok(Vec::new());
         }
         let repo = Repository::open(&git_path)?;
-        let mut revwalk = repo.revwalk()?;
-        revwalk.push_head()?;
-        let mut authors: Vec<String> = revwalk
-            .map(|r| {
-                let oid = r?;
-                repo.find_commit(oid)
-            })
-            .filter_map(|c| match c {
-                Ok(commit) => Some(commit),
-                Err(e) => {
-                    println!("Error walking the revisions {}, skipping", e);
-                    None
-                }
-            })
-            .fold(HashSet::new(), |mut sofar, cur| {
-                if let Some(name) = cur.author().name() {
-                    sofar.insert(name.to_string());
-                }
-                sofar
-            })
-            .into_iter()
-            .collect();
-        authors.sort();
-        Ok(authors)
+        Ok(GitIndex::load(&Config::config_folder()?, git_path, &repo)?.authors())
     }
 
     fn get_config_values(
@@ -274,6 +961,10 @@ impl EventProvider for Git {
                 COMMIT_AUTHOR_KEY,
                 config.git[config_name].commit_author.to_string(),
             ),
+            (
+                INCLUDE_REFLOG_KEY,
+                config.git[config_name].include_reflog.to_string(),
+            ),
         ]
         .into_iter()
         .collect()
@@ -290,6 +981,7 @@ impl EventProvider for Git {
             GitConfig {
                 repo_folder: config_values.remove(REPO_FOLDER_KEY).unwrap(),
                 commit_author: config_values.remove(COMMIT_AUTHOR_KEY).unwrap(),
+                include_reflog: config_values.remove(INCLUDE_REFLOG_KEY).as_deref() == Some("true"),
             },
         );
     }
@@ -304,81 +996,7 @@ impl EventProvider for Git {
         config_name: &str,
         day: Date<Local>,
     ) -> Result<Vec<Event>> {
-        let git_config = &config.git[config_name];
-        let day_start = day.and_hms(0, 0, 0);
-        let next_day_start = day_start + chrono::Duration::days(1);
-        let repo = Repository::open(&git_config.repo_folder)?;
-        let mut all_commits = HashMap::new();
-        let commit_display_url = Self::get_commit_display_url(&repo, config)?;
-        log::info!("gitlab commit display url: {:?}", commit_display_url);
-        for branch in repo
-            .branches(Some(git2::BranchType::Local))?
-            .filter_map(|b| b.ok())
-        {
-            if let Some(branch_oid) = branch.0.get().target() {
-                let branch_name = branch.0.name().ok().flatten().map(|s| s.to_string());
-                let branch_head = repo.find_commit(branch_oid)?;
-                let branch_head_date = Git::git2_time_to_datetime(branch_head.time());
-                if branch_head_date < day_start {
-                    // early abort: quite a lot faster than starting a useless revwalk
-                    continue;
-                }
-                let mut revwalk = repo.revwalk()?;
-                revwalk.set_sorting(/*git2::Sort::REVERSE |*/ git2::Sort::TIME)?;
-                revwalk.push(branch_oid)?;
-                let mut commits: Vec<Commit> = revwalk
-                    .map(|r| {
-                        let oid = r?;
-                        repo.find_commit(oid)
-                    })
-                    .filter_map(|c| match c {
-                        Ok(commit) => Some(commit),
-                        Err(e) => {
-                            println!("Error walking the revisions {}, skipping", e);
-                            None
-                        }
-                    })
-                    .take_while(|c| {
-                        let commit_date = Git::git2_time_to_datetime(c.time());
-                        commit_date >= day_start
-                    })
-                    .filter(|c| {
-                        let commit_date = Git::git2_time_to_datetime(c.time());
-                        // TODO move to option.contains when it stabilizes https://github.com/rust-lang/rust/issues/62358
-                        commit_date < next_day_start
-                            && c.author().name() == Some(&git_config.commit_author)
-                    })
-                    .collect();
-                commits.reverse();
-                all_commits.insert(branch_name.unwrap_or_else(|| "".to_string()), commits);
-            }
-        }
-        let master_commit_ids: &HashSet<git2::Oid> = &all_commits
-            .get("master")
-            .unwrap_or(&vec![])
-            .iter()
-            .map(|c| c.id())
-            .collect();
-        let mut result = all_commits
-            .iter()
-            .flat_map(|(branch, commits)| {
-                let rrepo = &repo;
-                let cdu = &commit_display_url;
-                commits
-                    .iter()
-                    .filter(move |c| branch == "master" || !master_commit_ids.contains(&c.id()))
-                    .map(move |c| Self::build_event(c, rrepo, branch, cdu))
-            })
-            .collect::<Vec<Event>>();
-        result.sort_by_key(|e| e.event_time); // need to sort for the dedup to work
-        result.dedup_by(|e1, e2| {
-            // deduplicate identical commits seen in different branches
-            // (the body will be different since we put the branch name there)
-            e1.event_time == e2.event_time
-                && e1.event_contents_header == e2.event_contents_header
-                && e1.event_info == e2.event_info
-        });
-        Ok(result)
+        Self::get_repo_events(config, config_name, day, &Config::config_folder()?)
     }
 }
 
@@ -386,7 +1004,10 @@ impl EventProvider for Git {
 fn it_parses_ssh_remote_github_url() {
     assert_eq!(
         Some("https://github.com/emmanueltouzery/cigale/commit/".to_string()),
-        Git::get_commit_display_url_github("git@github.com:emmanueltouzery/cigale.git").unwrap()
+        Git::remote_url_to_commit_display_url(
+            "git@github.com:emmanueltouzery/cigale.git",
+            &Config::default_config()
+        )
     );
 }
 
@@ -394,8 +1015,10 @@ fn it_parses_ssh_remote_github_url() {
 fn it_parses_http_remote_github_url() {
     assert_eq!(
         Some("https://github.com/emmanueltouzery/cigale/commit/".to_string()),
-        Git::get_commit_display_url_github("https://github.com/emmanueltouzery/cigale.git")
-            .unwrap()
+        Git::remote_url_to_commit_display_url(
+            "https://github.com/emmanueltouzery/cigale.git",
+            &Config::default_config()
+        )
     );
 }
 
@@ -403,8 +1026,147 @@ fn it_parses_http_remote_github_url() {
 fn it_rejects_a_non_remote_github_url() {
     assert_eq!(
         None,
-        Git::get_commit_display_url_github("https://mycompany.com/emmanueltouzery/cigale.git")
-            .unwrap()
+        Git::remote_url_to_commit_display_url(
+            "https://mycompany.com/emmanueltouzery/cigale.git",
+            &Config::default_config()
+        )
+    );
+}
+
+#[test]
+fn it_parses_remote_urls_with_ports_and_without_git_suffix() {
+    assert_eq!(
+        Some(RemoteUrl {
+            host: "git.mycompany.com".to_string(),
+            web_base_url: "https://git.mycompany.com".to_string(),
+            path: "team/project".to_string(),
+        }),
+        Git::parse_remote_url("ssh://git@git.mycompany.com:2222/team/project")
+    );
+    assert_eq!(
+        Some(RemoteUrl {
+            host: "git.mycompany.com".to_string(),
+            web_base_url: "http://git.mycompany.com:8080".to_string(),
+            path: "team/project".to_string(),
+        }),
+        Git::parse_remote_url("http://git.mycompany.com:8080/team/project.git/")
+    );
+}
+
+#[test]
+fn it_resolves_commit_urls_for_other_forges() {
+    let mut config = Config::default_config();
+    let url = |remote: &str, config: &Config| Git::remote_url_to_commit_display_url(remote, config);
+    assert_eq!(
+        Some("https://bitbucket.org/team/project/commits/".to_string()),
+        url("git@bitbucket.org:team/project.git", &config)
+    );
+    assert_eq!(
+        Some("https://bitbucket.mycompany.com/projects/PROJ/repos/project/commits/".to_string()),
+        url(
+            "ssh://git@bitbucket.mycompany.com:7999/proj/project.git",
+            &config
+        )
+    );
+    assert_eq!(
+        Some("https://dev.azure.com/org/proj/_git/project/commit/".to_string()),
+        url("git@ssh.dev.azure.com:v3/org/proj/project", &config)
+    );
+    assert_eq!(
+        Some("https://dev.azure.com/org/proj/_git/project/commit/".to_string()),
+        url("https://org@dev.azure.com/org/proj/_git/project", &config)
+    );
+    assert_eq!(
+        Some("https://git.sr.ht/~user/project/commit/".to_string()),
+        url("git@git.sr.ht:~user/project", &config)
+    );
+    assert_eq!(
+        Some("https://gitea.mycompany.com:3000/team/project/commit/".to_string()),
+        url("https://gitea.mycompany.com:3000/team/project.git", &config)
+    );
+    assert_eq!(
+        Some("https://github.mycompany.com/team/project/commit/".to_string()),
+        url("git@github.mycompany.com:team/project.git", &config)
+    );
+    assert_eq!(
+        Some("https://gitlab.com/team/project/-/commit/".to_string()),
+        url("https://gitlab.com/team/project.git", &config)
+    );
+    assert_eq!(None, url("git@git.mycompany.com:team/project.git", &config));
+    config.git_commit_url_templates.insert(
+        "git.mycompany.com".to_string(),
+        "{base_url}/cgit/{path}/commit/?id=".to_string(),
+    );
+    assert_eq!(
+        Some("https://git.mycompany.com/cgit/team/project/commit/?id=".to_string()),
+        url("git@git.mycompany.com:team/project.git", &config)
+    );
+}
+
+#[test]
+fn it_resolves_commit_urls_for_configured_gitlab_instances() {
+    let mut config = Config::default_config();
+    config.gitlab.insert(
+        "work".to_string(),
+        crate::events::gitlab::GitlabConfig {
+            gitlab_url: "https://mycompany.com/gitlab".to_string(),
+            personal_access_token: "".to_string(),
+        },
+    );
+    assert_eq!(
+        Some("https://mycompany.com/gitlab/team/project/commit/".to_string()),
+        Git::remote_url_to_commit_display_url("git@mycompany.com:team/project.git", &config)
+    );
+    assert_eq!(
+        Some("https://mycompany.com/gitlab/team/project/commit/".to_string()),
+        Git::remote_url_to_commit_display_url(
+            "https://mycompany.com/gitlab/team/project.git",
+            &config
+        )
+    );
+}
+
+#[test]
+fn it_resolves_commit_urls_for_configured_gitea_instances() {
+    let mut config = Config::default_config();
+    config.gitea.insert(
+        "work".to_string(),
+        crate::events::gitea::GiteaConfig {
+            gitea_url: "https://code.mycompany.com/git/".to_string(),
+            username: "me".to_string(),
+            access_token: "".to_string(),
+        },
+    );
+    assert_eq!(
+        Some("https://code.mycompany.com/git/team/project/commit/".to_string()),
+        Git::remote_url_to_commit_display_url("git@code.mycompany.com:team/project.git", &config)
+    );
+    assert_eq!(
+        Some("https://code.mycompany.com/git/team/project/commit/".to_string()),
+        Git::remote_url_to_commit_display_url(
+            "https://code.mycompany.com/git/team/project.git",
+            &config
+        )
+    );
+}
+
+#[test]
+fn it_extracts_merged_branch_names() {
+    assert_eq!(
+        Some("feature/login"),
+        Git::get_merged_branch_name("Merge branch 'feature/login' into master\n\nConflicts:")
+    );
+    assert_eq!(
+        Some("origin/master"),
+        Git::get_merged_branch_name("Merge remote-tracking branch 'origin/master'")
+    );
+    assert_eq!(
+        Some("emmanueltouzery/fix"),
+        Git::get_merged_branch_name("Merge pull request #12 from emmanueltouzery/fix")
+    );
+    assert_eq!(
+        None,
+        Git::get_merged_branch_name("Fix the merge of configs")
     );
 }
 
@@ -446,10 +1208,193 @@ fn it_can_get_events_for_the_cigale_repo() {
             WordWrapMode::NoWordWrap,
         ),
         Some("".to_string()),
-    );
-    let actual = Git
-        .get_events(&config, "test", Local.ymd(2020, 2, 25))
-        .unwrap();
+    )
+    .with_lazy_details(LazyEventDetails::GitCommitDiff {
+        repo_folder: ".".to_string(),
+        commit_sha: "1225b0a0efceb2f9b8862fd1cd03bf5dc6cb54d4".to_string(),
+    });
+    let index_folder = super::test_repo::TempFolder::new("git_events_index");
+    let actual =
+        Git::get_repo_events(&config, "test", Local.ymd(2020, 2, 25), &index_folder.path).unwrap();
     assert_eq!(2, actual.len());
     assert_eq!(expected_fst, *actual.first().unwrap());
 }
+
+#[test]
+fn it_parses_reflog_messages() {
+    assert_eq!(
+        Some((
+            ReflogOperation::Checkout,
+            None,
+            "moving from master to feature".to_string()
+        )),
+        Git::parse_reflog_message("checkout: moving from master to feature")
+    );
+    assert_eq!(
+        Some((
+            ReflogOperation::Rebase,
+            Some("pick"),
+            "include the icons in the binary".to_string()
+        )),
+        Git::parse_reflog_message("rebase -i (pick): include the icons in the binary")
+    );
+    assert_eq!(
+        Some((
+            ReflogOperation::Merge,
+            None,
+            "origin/master (Fast-forward)".to_string()
+        )),
+        Git::parse_reflog_message("merge origin/master: Fast-forward")
+    );
+    assert_eq!(
+        Some((
+            ReflogOperation::Amend,
+            Some("amend"),
+            "fix typo".to_string()
+        )),
+        Git::parse_reflog_message("commit (amend): fix typo")
+    );
+    assert_eq!(None, Git::parse_reflog_message("commit: fix typo"));
+}
+
+#[test]
+fn it_groups_rebase_reflog_entries() {
+    let at = |h, m| Local.ymd(2020, 2, 25).and_hms(h, m, 0);
+    let oid = |n: u32| Oid::from_str(&format!("{:040}", n)).unwrap();
+    let entries = vec![
+        (
+            at(9, 0),
+            oid(1),
+            "checkout: moving from master to feature".to_string(),
+        ),
+        (
+            at(9, 5),
+            oid(2),
+            "rebase -i (start): checkout master".to_string(),
+        ),
+        (
+            at(9, 5),
+            oid(3),
+            "rebase -i (pick): first commit".to_string(),
+        ),
+        (
+            at(9, 6),
+            oid(4),
+            "commit (amend): second commit".to_string(),
+        ),
+        (
+            at(9, 6),
+            oid(4),
+            "rebase -i (finish): returning to refs/heads/feature".to_string(),
+        ),
+        (at(9, 10), oid(5), "commit: third commit".to_string()),
+        (at(9, 15), oid(4), "reset: moving to HEAD~1".to_string()),
+    ];
+    assert_eq!(
+        vec![
+            ReflogActivity {
+                operation: ReflogOperation::Checkout,
+                time: at(9, 0),
+                new_oid: oid(1),
+                summary: "moving from master to feature".to_string(),
+                details: vec![],
+            },
+            ReflogActivity {
+                operation: ReflogOperation::Rebase,
+                time: at(9, 5),
+                new_oid: oid(4),
+                summary: "checkout master".to_string(),
+                details: vec![
+                    "pick: first commit".to_string(),
+                    "amend: second commit".to_string(),
+                    "finish: returning to refs/heads/feature".to_string(),
+                ],
+            },
+            ReflogActivity {
+                operation: ReflogOperation::Reset,
+                time: at(9, 15),
+                new_oid: oid(4),
+                summary: "moving to HEAD~1".to_string(),
+                details: vec![],
+            },
+        ],
+        Git::group_reflog_entries(&entries)
+    );
+}
+
+#[test]
+fn it_dedups_the_branch_reflog_activities_against_head() {
+    let at = |h, m| Local.ymd(2020, 2, 25).and_hms(h, m, 0);
+    let oid = |n: u32| Oid::from_str(&format!("{:040}", n)).unwrap();
+    let activity = |operation, time, new_oid| ReflogActivity {
+        operation,
+        time,
+        new_oid,
+        summary: "".to_string(),
+        details: vec![],
+    };
+    let head_activities = vec![
+        activity(ReflogOperation::Checkout, at(9, 0), oid(1)),
+        activity(ReflogOperation::Checkout, at(15, 0), oid(1)),
+        activity(ReflogOperation::Reset, at(16, 0), oid(2)),
+    ];
+    let branch_activities = vec![
+        activity(ReflogOperation::Reset, at(16, 0), oid(2)),
+        activity(ReflogOperation::Reset, at(17, 0), oid(3)),
+    ];
+    let deduped = Git::dedup_reflog_activities(vec![
+        ("HEAD".to_string(), head_activities),
+        ("refs/heads/feature".to_string(), branch_activities),
+    ]);
+    assert_eq!(
+        vec![
+            ("HEAD", at(9, 0)),
+            ("HEAD", at(15, 0)),
+            ("HEAD", at(16, 0)),
+            ("refs/heads/feature", at(17, 0)),
+        ],
+        deduped
+            .iter()
+            .map(|(ref_name, a)| (ref_name.as_str(), a.time))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn it_lists_only_the_new_commits_of_a_pushed_new_branch() {
+    let test_repo = super::test_repo::TestRepo::new("git_push");
+    let repo = &test_repo.repo;
+    let first = test_repo.commit(&[], "first");
+    let second = test_repo.commit(&[&first], "second");
+    let login = test_repo.commit(&[&second], "login");
+    let feature = test_repo.commit(&[&second], "feature");
+    repo.reference("refs/remotes/origin/master", second.id(), true, "push")
+        .unwrap();
+    repo.reference("refs/remotes/origin/login", login.id(), true, "push")
+        .unwrap();
+    repo.reference("refs/remotes/origin/feature/x", feature.id(), true, "push")
+        .unwrap();
+    let ref_names = vec![
+        "refs/remotes/origin/master".to_string(),
+        "refs/remotes/origin/login".to_string(),
+        "refs/remotes/origin/feature/x".to_string(),
+    ];
+    let summaries_of = |ref_name: &str, tip: Oid| {
+        Git::get_pushed_commit_summaries(repo, &ref_names, ref_name, Oid::zero(), tip).unwrap()
+    };
+    assert_eq!(
+        (
+            vec![format!("{} login", &login.id().to_string()[..8])],
+            false
+        ),
+        summaries_of("refs/remotes/origin/login", login.id())
+    );
+    // the branch name contains a slash, we must still hide all the branches of origin
+    assert_eq!(
+        (
+            vec![format!("{} feature", &feature.id().to_string()[..8])],
+            false
+        ),
+        summaries_of("refs/remotes/origin/feature/x", feature.id())
+    );
+}
@@ -0,0 +1,216 @@
+// the git events only contain the diffstat of the commit. When the user
+// selects a git event, we load the full diff of the commit, highlighted
+// for display in the event details pane. We do it lazily because diffs
+// can be big, and we'd be computing them for every commit of the day.
+use super::events::Result;
+use git2::{Oid, Repository};
+use lazy_static::lazy_static;
+use std::path::Path;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Style, ThemeSet};
+use syntect::parsing::SyntaxSet;
+
+// generated files or vendored dependencies can make for huge diffs,
+// which would freeze the UI when building the labels
+const MAX_FILE_DIFF_LINES: usize = 1000;
+const MAX_TOTAL_DIFF_LINES: usize = 5000;
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffHunk {
+    pub header: String,
+    pub markup: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileDiff {
+    pub path: String,
+    pub is_binary: bool,
+    pub hunks: Vec<DiffHunk>,
+    // we stopped at the line limit
+    pub truncated: bool,
+}
+
+pub fn get_commit_file_diffs(
+    repo_folder: &str,
+    commit_sha: &str,
+    dark_theme: bool,
+) -> Result<Vec<FileDiff>> {
+    let repo = Repository::open(repo_folder)?;
+    let commit = repo.find_commit(Oid::from_str(commit_sha)?)?;
+    // for merge commits, we diff against the first parent
+    let parent_tree = match commit.parents().next() {
+        Some(p) => Some(p.tree()?),
+        None => None,
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+    let syntax_set: &SyntaxSet = &SYNTAX_SET;
+    let theme = &THEME_SET.themes[if dark_theme {
+        "base16-ocean.dark"
+    } else {
+        "InspiredGitHub"
+    }];
+    let (added_bg, removed_bg) = if dark_theme {
+        ("#1e3a24", "#4a1f24")
+    } else {
+        ("#e6ffed", "#ffeef0")
+    };
+
+    let mut result = vec![];
+    let mut total_lines = 0;
+    for delta_idx in 0..diff.deltas().len() {
+        let delta = diff.get_delta(delta_idx).unwrap();
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if total_lines >= MAX_TOTAL_DIFF_LINES {
+            result.push(FileDiff {
+                path,
+                is_binary: false,
+                hunks: vec![],
+                truncated: true,
+            });
+            continue;
+        }
+        let patch = match git2::Patch::from_diff(&diff, delta_idx)? {
+            Some(p) if !delta.flags().is_binary() => p,
+            _ => {
+                result.push(FileDiff {
+                    path,
+                    is_binary: true,
+                    hunks: vec![],
+                    truncated: false,
+                });
+                continue;
+            }
+        };
+        let syntax = Path::new(&path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| syntax_set.find_syntax_by_extension(e))
+            .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+        let mut hunks = vec![];
+        let mut file_lines = 0;
+        let mut truncated = false;
+        for hunk_idx in 0..patch.num_hunks() {
+            let (hunk, line_count) = patch.hunk(hunk_idx)?;
+            // the highlighting state is shared between the added & removed
+            // lines, it's not perfect, but good enough in practice.
+            let mut highlighter = HighlightLines::new(syntax, theme);
+            let mut markup = String::new();
+            for line_idx in 0..line_count {
+                if file_lines >= MAX_FILE_DIFF_LINES || total_lines >= MAX_TOTAL_DIFF_LINES {
+                    truncated = true;
+                    break;
+                }
+                file_lines += 1;
+                total_lines += 1;
+                let line = patch.line_in_hunk(hunk_idx, line_idx)?;
+                let origin = line.origin();
+                let bg = match origin {
+                    '+' => Some(added_bg),
+                    '-' => Some(removed_bg),
+                    _ => None,
+                };
+                let content = String::from_utf8_lossy(line.content());
+                let content = if content.ends_with('\n') {
+                    content.to_string()
+                } else {
+                    // "\ No newline at end of file" and such
+                    format!("{}\n", content)
+                };
+                let line_markup = highlight_line(&mut highlighter, syntax_set, &content);
+                let prefix = match origin {
+                    '+' | '-' | ' ' => origin.to_string(),
+                    _ => "".to_string(),
+                };
+                match bg {
+                    Some(bg) => markup.push_str(&format!(
+                        "<span background=\"{}\">{}{}</span>",
+                        bg, prefix, line_markup
+                    )),
+                    None => markup.push_str(&format!("{}{}", prefix, line_markup)),
+                }
+            }
+            hunks.push(DiffHunk {
+                header: String::from_utf8_lossy(hunk.header()).trim().to_string(),
+                markup: markup.trim_end().to_string(),
+            });
+            if truncated {
+                break;
+            }
+        }
+        result.push(FileDiff {
+            path,
+            is_binary: false,
+            hunks,
+            truncated,
+        });
+    }
+    Ok(result)
+}
+
+fn highlight_line(highlighter: &mut HighlightLines, syntax_set: &SyntaxSet, line: &str) -> String {
+    match highlighter.highlight_line(line, syntax_set) {
+        Ok(ranges) => ranges
+            .iter()
+            .map(|(style, text)| style_to_markup(style, text))
+            .collect(),
+        Err(_) => glib::markup_escape_text(line).to_string(),
+    }
+}
+
+fn style_to_markup(style: &Style, text: &str) -> String {
+    format!(
+        "<span foreground=\"#{:02x}{:02x}{:02x}\">{}</span>",
+        style.foreground.r,
+        style.foreground.g,
+        style.foreground.b,
+        glib::markup_escape_text(text)
+    )
+}
+
+#[test]
+fn it_gets_the_highlighted_diff_of_a_commit() {
+    let test_repo = super::test_repo::TestRepo::new("git_diff");
+    let repo_folder = test_repo.path_str();
+    let first = test_repo.commit_file("main.rs", "fn main() {\n}\n", &[]);
+    let second =
+        test_repo.commit_file("main.rs", "fn main() {\n    let a = 1 < 2;\n}\n", &[&first]);
+
+    let diffs = get_commit_file_diffs(repo_folder, &second.id().to_string(), false).unwrap();
+    assert_eq!(1, diffs.len());
+    assert_eq!("main.rs", diffs[0].path);
+    assert!(!diffs[0].is_binary);
+    assert_eq!(1, diffs[0].hunks.len());
+    assert_eq!("@@ -1,2 +1,3 @@", diffs[0].hunks[0].header);
+    let markup = &diffs[0].hunks[0].markup;
+    assert!(markup.contains("<span background=\"#e6ffed\">+"));
+    assert!(markup.contains("&lt;"));
+    assert!(!markup.contains("#ffeef0"));
+    assert!(!diffs[0].truncated);
+
+    let big_file = (0..MAX_FILE_DIFF_LINES + 10)
+        .map(|i| format!("// line {}\n", i))
+        .collect::<String>();
+    let third = test_repo.commit_file("main.rs", &big_file, &[&second]);
+    let diffs = get_commit_file_diffs(repo_folder, &third.id().to_string(), false).unwrap();
+    assert!(diffs[0].truncated);
+    assert_eq!(
+        MAX_FILE_DIFF_LINES,
+        diffs[0]
+            .hunks
+            .iter()
+            // all the lines are added or removed
+            .map(|h| h.markup.matches("<span background").count())
+            .sum::<usize>()
+    );
+}
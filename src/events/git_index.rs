@@ -0,0 +1,369 @@
+// walking the whole history of a repository is slow on big repositories,
+// and we used to do it for every branch, for every day we display, and
+// to list the authors of the repository.
+// So we persist an index of the commits of each repository in the
+// config folder, along with the branch tips at the time we indexed,
+// and we only walk the commits which were added since then when
+// the branches move.
+// We also keep the loaded indexes in memory, and only go back to the
+// repository when its branches moved.
+// Note that libgit2 will take advantage of the commit-graph file of
+// the repository, if there is one (`git commit-graph write`), when
+// we do need to walk the history.
+use super::events::Result;
+use crate::config::Config;
+use git2::{Oid, Repository};
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+static TMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    // index path => index, as of the last time we loaded it
+    static ref LOADED_INDEXES: Mutex<HashMap<PathBuf, Arc<GitIndex>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct IndexedCommit {
+    pub author: String,
+    pub committer: String,
+    pub time: i64, // committer time, in seconds since the epoch
+    // a commit is attributed to the branches through which we discovered
+    // it, which is not necessarily all the branches which contain it.
+    pub branches: Vec<String>,
+}
+
+impl IndexedCommit {
+    pub fn display_branch(&self) -> &str {
+        self.branches
+            .iter()
+            .find(|b| *b == "master")
+            .or_else(|| self.branches.first())
+            .map(|b| b.as_str())
+            .unwrap_or("")
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct GitIndex {
+    // branch name => sha of the branch tip when we last updated the index
+    ref_tips: HashMap<String, String>,
+    // commit sha => commit info
+    commits: HashMap<String, IndexedCommit>,
+}
+
+impl GitIndex {
+    fn index_path(index_folder: &Path, repo_folder: &str) -> PathBuf {
+        index_folder.join(format!(
+            "Git_{}.index",
+            Config::sanitize_for_filename(repo_folder)
+        ))
+    }
+
+    /// get the index for this repository, bringing it up to date with the
+    /// current branches of the repository if needed. The indexes are
+    /// persisted in `index_folder`, normally the config folder.
+    pub fn load(
+        index_folder: &Path,
+        repo_folder: &str,
+        repo: &Repository,
+    ) -> Result<Arc<GitIndex>> {
+        let index_path = Self::index_path(index_folder, repo_folder);
+        let current_tips = Self::current_tips(repo)?;
+        let loaded = LOADED_INDEXES.lock().unwrap().remove(&index_path);
+        let mut index = match loaded {
+            Some(index) if index.is_up_to_date(&current_tips) => {
+                LOADED_INDEXES
+                    .lock()
+                    .unwrap()
+                    .insert(index_path, index.clone());
+                return Ok(index);
+            }
+            // other event sources may still be using it
+            Some(index) => Arc::try_unwrap(index).unwrap_or_else(|i| (*i).clone()),
+            None => Self::read(&index_path),
+        };
+        if index.update(repo)? {
+            // multiple event sources may use the same repository, and
+            // we fetch event sources in parallel => write then rename,
+            // with a temporary file specific to this writer
+            let tmp_path = index_path.with_file_name(format!(
+                "{}.{}-{}.tmp",
+                index_path
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                std::process::id(),
+                TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            serde_json::to_writer(BufWriter::new(File::create(&tmp_path)?), &index)?;
+            std::fs::rename(&tmp_path, &index_path)?;
+        }
+        let index = Arc::new(index);
+        LOADED_INDEXES
+            .lock()
+            .unwrap()
+            .insert(index_path, index.clone());
+        Ok(index)
+    }
+
+    fn read(index_path: &Path) -> GitIndex {
+        if !index_path.is_file() {
+            return GitIndex::default();
+        }
+        File::open(index_path)
+            .map_err(|e| e.to_string())
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                log::warn!("Invalid git index {:?}, rebuilding: {}", index_path, e);
+                GitIndex::default()
+            })
+    }
+
+    pub fn authors(&self) -> Vec<String> {
+        let mut authors: Vec<String> = self
+            .commits
+            .values()
+            .map(|c| c.author.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        authors.sort();
+        authors
+    }
+
+    /// commits with a committer time between start (inclusive) and end (exclusive)
+    pub fn commits_between(
+        &self,
+        start: i64,
+        end: i64,
+        author: &str,
+    ) -> Result<Vec<(Oid, &IndexedCommit)>> {
+        self.commits
+            .iter()
+            .filter(|(_, c)| c.time >= start && c.time < end && c.author == author)
+            .map(|(sha, c)| Ok((Oid::from_str(sha)?, c)))
+            .collect()
+    }
+
+    pub fn current_tips(repo: &Repository) -> Result<HashMap<String, Oid>> {
+        let mut tips = HashMap::new();
+        for branch in repo
+            .branches(Some(git2::BranchType::Local))?
+            .filter_map(|b| b.ok())
+        {
+            if let (Some(oid), Ok(Some(name))) = (branch.0.get().target(), branch.0.name()) {
+                tips.insert(name.to_string(), oid);
+            }
+        }
+        Ok(tips)
+    }
+
+    fn indexed_tips(&self) -> HashMap<String, Oid> {
+        self.ref_tips
+            .iter()
+            .filter_map(|(name, sha)| Oid::from_str(sha).ok().map(|oid| (name.clone(), oid)))
+            .collect()
+    }
+
+    fn is_up_to_date(&self, current_tips: &HashMap<String, Oid>) -> bool {
+        self.indexed_tips() == *current_tips
+    }
+
+    /// returns true if the index changed
+    fn update(&mut self, repo: &Repository) -> Result<bool> {
+        let current_tips = Self::current_tips(repo)?;
+        if self.is_up_to_date(&current_tips) {
+            return Ok(false);
+        }
+        let old_tips = self.indexed_tips();
+        // tips which are fully indexed, we can stop walking there
+        let mut indexed_tips: Vec<Oid> = current_tips
+            .iter()
+            .filter(|(name, tip)| old_tips.get(*name) == Some(*tip))
+            .map(|(_, tip)| *tip)
+            .collect();
+        let mut removed_branches = false;
+        for name in old_tips.keys() {
+            if !current_tips.contains_key(name) {
+                self.remove_branch(name);
+                removed_branches = true;
+            }
+        }
+
+        // index master first, so that it gets attributed the shared history
+        let mut names: Vec<&String> = current_tips.keys().collect();
+        names.sort_by_key(|n| (n.as_str() != "master", n.as_str()));
+        for name in names {
+            let new_tip = current_tips[name];
+            match old_tips.get(name) {
+                Some(old_tip) if *old_tip == new_tip => continue,
+                Some(old_tip) if repo.graph_descendant_of(new_tip, *old_tip).unwrap_or(false) => {
+                    // the branch moved forward
+                    self.walk(repo, name, new_tip, &[*old_tip])?;
+                }
+                Some(_) => {
+                    // the branch was rewritten (rebase, reset..)
+                    self.remove_branch(name);
+                    removed_branches = true;
+                    self.walk(repo, name, new_tip, &indexed_tips)?;
+                }
+                None => {
+                    self.walk(repo, name, new_tip, &indexed_tips)?;
+                }
+            }
+            indexed_tips.push(new_tip);
+        }
+        if removed_branches {
+            self.reattribute_orphans(repo, &current_tips);
+        }
+        self.ref_tips = current_tips
+            .iter()
+            .map(|(name, tip)| (name.clone(), tip.to_string()))
+            .collect();
+        Ok(true)
+    }
+
+    fn walk(&mut self, repo: &Repository, branch: &str, tip: Oid, hide: &[Oid]) -> Result<()> {
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(tip)?;
+        for oid in hide {
+            revwalk.hide(*oid)?;
+        }
+        for oid in revwalk {
+            let oid = oid?;
+            match self.commits.entry(oid.to_string()) {
+                Entry::Occupied(mut e) => {
+                    let branches = &mut e.get_mut().branches;
+                    if !branches.iter().any(|b| b == branch) {
+                        branches.push(branch.to_string());
+                    }
+                }
+                Entry::Vacant(e) => {
+                    let commit = repo.find_commit(oid)?;
+                    e.insert(IndexedCommit {
+                        author: commit.author().name().unwrap_or("").to_string(),
+                        committer: commit.committer().name().unwrap_or("").to_string(),
+                        time: commit.time().seconds(),
+                        branches: vec![branch.to_string()],
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_branch(&mut self, branch: &str) {
+        for commit in self.commits.values_mut() {
+            commit.branches.retain(|b| b != branch);
+        }
+    }
+
+    // commits which were attributed only to branches which were deleted or
+    // rewritten may still be reachable from other branches. Attribute them
+    // to one of these, or drop them if they're not reachable anymore.
+    fn reattribute_orphans(&mut self, repo: &Repository, current_tips: &HashMap<String, Oid>) {
+        self.commits.retain(|sha, commit| {
+            if !commit.branches.is_empty() {
+                return true;
+            }
+            let oid = match Oid::from_str(sha) {
+                Ok(oid) => oid,
+                Err(_) => return false,
+            };
+            match current_tips.iter().find(|(_, tip)| {
+                **tip == oid || repo.graph_descendant_of(**tip, oid).unwrap_or(false)
+            }) {
+                Some((name, _)) => {
+                    commit.branches.push(name.clone());
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+}
+
+#[test]
+fn it_updates_the_index_incrementally() {
+    let test_repo = super::test_repo::TestRepo::new("git_index");
+    let repo = &test_repo.repo;
+    let first = test_repo.commit(&[], "first");
+    // HEAD points to master => can't use repo.branch() to move master
+    repo.reference("refs/heads/master", first.id(), true, "first")
+        .unwrap();
+    let feature_commit = test_repo.commit(&[&first], "feature");
+    repo.branch("feature", &feature_commit, true).unwrap();
+
+    let mut index = GitIndex::default();
+    assert!(index.update(repo).unwrap());
+    assert!(!index.update(repo).unwrap());
+    assert_eq!(vec!["Jane".to_string()], index.authors());
+    assert_eq!(
+        "master",
+        index.commits[&first.id().to_string()].display_branch()
+    );
+    assert_eq!(
+        vec!["feature".to_string()],
+        index.commits[&feature_commit.id().to_string()].branches
+    );
+
+    // fast-forward master to the feature branch, then delete the feature branch
+    repo.reference("refs/heads/master", feature_commit.id(), true, "ff")
+        .unwrap();
+    repo.find_branch("feature", git2::BranchType::Local)
+        .unwrap()
+        .delete()
+        .unwrap();
+    assert!(index.update(repo).unwrap());
+    assert_eq!(
+        vec!["master".to_string()],
+        index.commits[&feature_commit.id().to_string()].branches
+    );
+    assert_eq!(
+        2,
+        index
+            .commits_between(1_600_000_000, 1_600_000_001, "Jane")
+            .unwrap()
+            .len()
+    );
+}
+
+#[test]
+fn it_reuses_the_loaded_index_until_a_branch_moves() {
+    let test_repo = super::test_repo::TestRepo::new("git_index_load");
+    let index_folder = super::test_repo::TempFolder::new("git_index_load_folder");
+    let repo = &test_repo.repo;
+    let first = test_repo.commit(&[], "first");
+    repo.reference("refs/heads/master", first.id(), true, "first")
+        .unwrap();
+
+    let index = GitIndex::load(&index_folder.path, test_repo.path_str(), repo).unwrap();
+    let index_path = GitIndex::index_path(&index_folder.path, test_repo.path_str());
+    assert!(index_path.is_file());
+    std::fs::remove_file(&index_path).unwrap();
+    let same_index = GitIndex::load(&index_folder.path, test_repo.path_str(), repo).unwrap();
+    assert!(Arc::ptr_eq(&index, &same_index));
+    // we didn't rewrite the file since no branch moved
+    assert!(!index_path.is_file());
+
+    let second = test_repo.commit(&[&first], "second");
+    repo.reference("refs/heads/master", second.id(), true, "second")
+        .unwrap();
+    let updated_index = GitIndex::load(&index_folder.path, test_repo.path_str(), repo).unwrap();
+    assert!(index_path.is_file());
+    assert_eq!(
+        2,
+        updated_index
+            .commits_between(1_600_000_000, 1_600_000_001, "Jane")
+            .unwrap()
+            .len()
+    );
+}
@@ -0,0 +1,538 @@
+// gitea and its fork forgejo share the same API
+use super::events::{ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode};
+use super::rest_api::{self, PagedRecords, RestApi};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use itertools::Itertools;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+type RepoId = usize;
+
+const PAGE_SIZE: usize = 50;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GiteaConfig {
+    pub gitea_url: String,
+    pub username: String,
+    pub access_token: String,
+}
+
+impl RestApi for GiteaConfig {
+    fn source_name(&self) -> &'static str {
+        "Gitea"
+    }
+
+    fn prepare_request(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        request.header("Authorization", format!("token {}", self.access_token))
+    }
+}
+
+pub struct Gitea;
+const GITEA_URL_KEY: &str = "Gitea URL";
+const USERNAME_KEY: &str = "Username";
+const ACCESS_TOKEN_KEY: &str = "Access Token";
+
+#[derive(Deserialize, Clone, Debug)]
+struct GiteaActivity {
+    op_type: String,
+    repo_id: RepoId,
+    #[serde(default)]
+    ref_name: String,
+    // depending on the op_type, "<index>|<title>", or json for pushes
+    #[serde(default)]
+    content: String,
+    comment: Option<GiteaComment>,
+    // recent gitea versions give us the repo with the activity
+    repo: Option<GiteaRepo>,
+    created: DateTime<Local>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GiteaComment {
+    body: String,
+    html_url: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct GiteaPushCommits {
+    #[serde(default)]
+    commits: Vec<GiteaPushCommit>,
+    #[serde(default)]
+    len: usize,
+    #[serde(rename = "CompareURL", default)]
+    compare_url: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct GiteaPushCommit {
+    sha1: String,
+    message: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+struct GiteaRepo {
+    id: RepoId,
+    full_name: String,
+    html_url: String,
+}
+
+impl Gitea {
+    fn build_push_event(
+        gitea_config: &GiteaConfig,
+        activity: &GiteaActivity,
+        repo: &GiteaRepo,
+    ) -> Event {
+        let branch = activity.ref_name.trim_start_matches("refs/heads/");
+        let push = serde_json::from_str::<GiteaPushCommits>(&activity.content).ok();
+        let commit_count = push
+            .as_ref()
+            .map(|p| p.len.max(p.commits.len()))
+            .unwrap_or(0);
+        let url = match &push {
+            // the compare url is relative to the server
+            Some(p) if !p.compare_url.is_empty() => format!(
+                "{}/{}",
+                gitea_config.gitea_url.trim_end_matches('/'),
+                p.compare_url.trim_start_matches('/')
+            ),
+            _ => format!("{}/src/branch/{}", repo.html_url, branch),
+        };
+        let commit_summaries = push
+            .as_ref()
+            .map(|p| {
+                p.commits
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{} {}",
+                            &c.sha1[..c.sha1.len().min(8)],
+                            c.message.lines().next().unwrap_or("")
+                        )
+                    })
+                    .join("\n")
+            })
+            .unwrap_or_default();
+        let title = format!("Pushed {} commit(s) to {}", commit_count, branch);
+        Event::new(
+            "Gitea",
+            Icon::UPLOAD,
+            activity.created.time(),
+            repo.full_name.clone(),
+            title.clone(),
+            EventBody::Markup(
+                format!(
+                    "<a href=\"{}\">Open in browser</a>\n\n{}\n{}",
+                    glib::markup_escape_text(&url),
+                    glib::markup_escape_text(&title),
+                    glib::markup_escape_text(&commit_summaries)
+                ),
+                WordWrapMode::WordWrap,
+            ),
+            Some(format!("{} commit(s) pushed", commit_count)),
+        )
+    }
+
+    // (target description, url part, verb, icon)
+    fn op_type_desc(op_type: &str) -> Option<(&'static str, &'static str, &'static str, Icon)> {
+        Some(match op_type {
+            "create_issue" => ("Issue", "issues", "Opened", Icon::TASKS),
+            "reopen_issue" => ("Issue", "issues", "Reopened", Icon::TASKS),
+            "close_issue" => ("Issue", "issues", "Closed", Icon::CHECK_SQUARE),
+            "comment_issue" => ("Issue", "issues", "Comment", Icon::COMMENT_DOTS),
+            "create_pull_request" => ("Pull Request", "pulls", "Opened", Icon::CODE_BRANCH),
+            "reopen_pull_request" => ("Pull Request", "pulls", "Reopened", Icon::CODE_BRANCH),
+            "close_pull_request" => ("Pull Request", "pulls", "Closed", Icon::CHECK_SQUARE),
+            "merge_pull_request" | "auto_merge_pull_request" => {
+                ("Pull Request", "pulls", "Merged", Icon::CHECK_SQUARE)
+            }
+            "comment_pull" => ("Pull Request", "pulls", "Comment", Icon::COMMENT_DOTS),
+            "approve_pull_request" => ("Pull Request", "pulls", "Approved", Icon::THUMBS_UP),
+            "reject_pull_request" => (
+                "Pull Request",
+                "pulls",
+                "Changes requested",
+                Icon::COMMENT_DOTS,
+            ),
+            _ => return None,
+        })
+    }
+
+    // issues, pull requests, comments & reviews: the content is "<index>|<title or comment>"
+    fn build_issue_event(
+        activity: &GiteaActivity,
+        repo: &GiteaRepo,
+        (target_desc, url_part, verb, icon): (&'static str, &str, &str, Icon),
+    ) -> Event {
+        let mut parts = activity.content.splitn(2, '|');
+        let index = parts.next().unwrap_or("");
+        let text = parts.next().unwrap_or("");
+        let (url, body) = match &activity.comment {
+            Some(comment) => (comment.html_url.clone(), comment.body.as_str()),
+            None => (format!("{}/{}/{}", repo.html_url, url_part, index), text),
+        };
+        let title = format!("{} #{} {}", target_desc, index, verb);
+        // for comments & reviews, the text is the comment, not the title
+        let text_is_title = matches!(verb, "Opened" | "Reopened" | "Closed" | "Merged");
+        let header = if text_is_title && !text.is_empty() {
+            format!("{}: {}", title, text)
+        } else {
+            format!("{}: {}", title, repo.full_name)
+        };
+        Event::new(
+            "Gitea",
+            icon,
+            activity.created.time(),
+            repo.full_name.clone(),
+            header,
+            EventBody::Markup(
+                format!(
+                    "<a href=\"{}\">Open in browser</a>\n\n{}",
+                    glib::markup_escape_text(&url),
+                    glib::markup_escape_text(body)
+                ),
+                WordWrapMode::WordWrap,
+            ),
+            Some(format!("{} {}", target_desc, verb.to_lowercase())),
+        )
+    }
+
+    // tags, branch deletions, releases, new repos...
+    fn build_other_event(activity: &GiteaActivity, repo: &GiteaRepo) -> Event {
+        let ref_name = activity
+            .ref_name
+            .trim_start_matches("refs/heads/")
+            .trim_start_matches("refs/tags/");
+        let (title, icon, url) = match activity.op_type.as_str() {
+            "push_tag" => (
+                format!("Pushed tag {}", ref_name),
+                Icon::TAG,
+                format!("{}/src/tag/{}", repo.html_url, ref_name),
+            ),
+            "publish_release" => (
+                format!("Released {}", ref_name),
+                Icon::TAG,
+                format!("{}/releases/tag/{}", repo.html_url, ref_name),
+            ),
+            "delete_tag" | "delete_branch" => (
+                format!("Deleted {}", ref_name),
+                Icon::TRASH_ALT,
+                repo.html_url.clone(),
+            ),
+            op => (
+                op.replace('_', " "),
+                Icon::COMMENT_DOTS,
+                repo.html_url.clone(),
+            ),
+        };
+        Event::new(
+            "Gitea",
+            icon,
+            activity.created.time(),
+            repo.full_name.clone(),
+            format!("{}: {}", title, repo.full_name),
+            EventBody::Markup(
+                format!(
+                    "<a href=\"{}\">Open in browser</a>\n\n{}",
+                    glib::markup_escape_text(&url),
+                    glib::markup_escape_text(&title)
+                ),
+                WordWrapMode::WordWrap,
+            ),
+            Some(title),
+        )
+    }
+
+    fn build_event(
+        gitea_config: &GiteaConfig,
+        activity: &GiteaActivity,
+        repo: &GiteaRepo,
+    ) -> Event {
+        match activity.op_type.as_str() {
+            "commit_repo" | "mirror_sync_push" => {
+                Self::build_push_event(gitea_config, activity, repo)
+            }
+            op => match Self::op_type_desc(op) {
+                Some(desc) => Self::build_issue_event(activity, repo, desc),
+                None => Self::build_other_event(activity, repo),
+            },
+        }
+    }
+
+    fn get_repos_info(
+        config_name: &str,
+        gitea_config: &GiteaConfig,
+        repo_ids: &HashSet<RepoId>,
+    ) -> Result<HashMap<RepoId, GiteaRepo>> {
+        let cache = Config::get_cached_contents(
+            &Gitea,
+            config_name,
+            &Local.ymd(1970, 1, 1).and_hms(0, 0, 0),
+        )?;
+        match cache
+            .and_then(|cached_json| serde_json::from_str::<Vec<GiteaRepo>>(&cached_json).ok())
+            .and_then(|repos| Self::get_repos_from_json(repos, repo_ids))
+        {
+            Some(hash) => Ok(hash),
+            None => {
+                // either no cache or the cache doesn't know some of the
+                // repos (it's outdated) => refetch & store to cache
+                let mut repos =
+                    Self::call_gitea_rest::<GiteaRepo>("/api/v1/user/repos", &[], gitea_config)?
+                        .complete()?;
+                // we may have activity on repos we're not a member of,
+                // for instance comments on the issues of other projects
+                let known_ids: HashSet<_> = repos.iter().map(|r| r.id).collect();
+                for repo_id in repo_ids.difference(&known_ids) {
+                    match Self::get_repo(gitea_config, *repo_id) {
+                        Ok(repo) => repos.push(repo),
+                        Err(e) => log::warn!("Gitea: can't get the repo {}: {}", repo_id, e),
+                    }
+                }
+                Config::write_to_cache(&Gitea, config_name, &serde_json::to_string(&repos)?)?;
+                Ok(repos
+                    .into_iter()
+                    .filter(|r| repo_ids.contains(&r.id))
+                    .map(|r| (r.id, r))
+                    .collect())
+            }
+        }
+    }
+
+    fn get_repos_from_json(
+        repos: Vec<GiteaRepo>,
+        repo_ids: &HashSet<RepoId>,
+    ) -> Option<HashMap<RepoId, GiteaRepo>> {
+        let filtered_repos: HashMap<_, _> = repos
+            .into_iter()
+            .filter(|r| repo_ids.contains(&r.id))
+            .map(|r| (r.id, r))
+            .collect();
+        if filtered_repos.len() == repo_ids.len() {
+            Some(filtered_repos)
+        } else {
+            None
+        }
+    }
+
+    fn get_repo(gitea_config: &GiteaConfig, repo_id: RepoId) -> Result<GiteaRepo> {
+        let url = format!(
+            "{}/api/v1/repositories/{}",
+            gitea_config.gitea_url.trim_end_matches('/'),
+            repo_id
+        );
+        let json =
+            rest_api::send_with_backoff(&rest_api::http_client()?, &url, gitea_config)?.text()?;
+        Ok(serde_json::from_str(&json)
+            .map_err(|e| format!("Failed parsing json {} {:?} -- {}", url, e, json))?)
+    }
+
+    /// the first page must succeed, but we return partial data if a later page fails
+    fn call_gitea_rest<T>(
+        get_url: &str,
+        get_params: &[(&'static str, &str)],
+        gitea_config: &GiteaConfig,
+    ) -> Result<PagedRecords<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let get_params = get_params
+            .iter()
+            .map(|(k, v)| format!("{}={}&", k, v))
+            .join("");
+        let url = format!(
+            "{}{}?{}limit={}",
+            gitea_config.gitea_url.trim_end_matches('/'),
+            get_url,
+            get_params,
+            PAGE_SIZE
+        );
+        rest_api::get_paged(gitea_config, &url, |_| false)
+    }
+}
+
+impl EventProvider for Gitea {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (GITEA_URL_KEY, ConfigType::Text("")),
+            (USERNAME_KEY, ConfigType::Text("")),
+            (ACCESS_TOKEN_KEY, ConfigType::Password),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Gitea"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::CODE_BRANCH
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.gitea.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        _field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                GITEA_URL_KEY,
+                config.gitea[config_name].gitea_url.to_string(),
+            ),
+            (USERNAME_KEY, config.gitea[config_name].username.to_string()),
+            (
+                ACCESS_TOKEN_KEY,
+                config.gitea[config_name].access_token.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.gitea.insert(
+            config_name,
+            GiteaConfig {
+                gitea_url: config_values.remove(GITEA_URL_KEY).unwrap(),
+                username: config_values.remove(USERNAME_KEY).unwrap(),
+                access_token: config_values.remove(ACCESS_TOKEN_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.gitea.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let gitea_config = &config.gitea[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        // the date filter is applied in the timezone of the server,
+        // hopefully the same as ours. We filter again below anyway.
+        let gitea_records = Self::call_gitea_rest::<GiteaActivity>(
+            &format!("/api/v1/users/{}/activities/feeds", gitea_config.username),
+            &[
+                ("only-performed-by", "true"),
+                ("date", &day.format("%F").to_string()),
+            ],
+            gitea_config,
+        )?;
+        let activities: Vec<_> = gitea_records
+            .records
+            .into_iter()
+            .filter(|a| a.created >= day_start && a.created < next_day_start)
+            .collect();
+
+        let missing_repo_ids: HashSet<_> = activities
+            .iter()
+            .filter(|a| a.repo.is_none())
+            .map(|a| a.repo_id)
+            .collect();
+        let repo_infos = if missing_repo_ids.is_empty() {
+            HashMap::new()
+        } else {
+            Self::get_repos_info(config_name, gitea_config, &missing_repo_ids)?
+        };
+
+        let mut events: Vec<_> = activities
+            .iter()
+            // skip the activities on repos we can't see anymore
+            .filter_map(|a| {
+                a.repo
+                    .as_ref()
+                    .or_else(|| repo_infos.get(&a.repo_id))
+                    .map(|repo| Self::build_event(gitea_config, a, repo))
+            })
+            .collect();
+        if let Some(err) = gitea_records.error {
+            events.push(rest_api::missing_events_event("Gitea", &err));
+        }
+        Ok(events)
+    }
+}
+
+#[test]
+fn it_builds_events_for_gitea_activities() {
+    let gitea_config = GiteaConfig {
+        gitea_url: "https://gitea.mycompany.com/".to_string(),
+        username: "me".to_string(),
+        access_token: "".to_string(),
+    };
+    let repo = GiteaRepo {
+        id: 1,
+        full_name: "me/proj".to_string(),
+        html_url: "https://gitea.mycompany.com/me/proj".to_string(),
+    };
+    let activities: Vec<GiteaActivity> = serde_json::from_str(
+        r#"[{"op_type":"commit_repo","repo_id":1,"ref_name":"refs/heads/main",
+             "content":"{\"Commits\":[{\"Sha1\":\"0123456789abcdef\",\"Message\":\"Fix the build\\n\"}],\"Len\":1,\"CompareURL\":\"me/proj/compare/aaa...bbb\"}",
+             "created":"2020-03-23T10:00:00Z"},
+            {"op_type":"merge_pull_request","repo_id":1,"ref_name":"","content":"12|Add export",
+             "created":"2020-03-23T11:00:00Z"},
+            {"op_type":"comment_issue","repo_id":1,"ref_name":"","content":"3|thanks",
+             "comment":{"body":"thanks <3","html_url":"https://gitea.mycompany.com/me/proj/issues/3#issuecomment-5"},
+             "created":"2020-03-23T12:00:00Z"},
+            {"op_type":"push_tag","repo_id":1,"ref_name":"refs/tags/1.0","content":"",
+             "created":"2020-03-23T13:00:00Z"}]"#,
+    )
+    .unwrap();
+    let events: Vec<_> = activities
+        .iter()
+        .map(|a| Gitea::build_event(&gitea_config, a, &repo))
+        .collect();
+    assert_eq!(
+        "Pushed 1 commit(s) to main",
+        events[0].event_contents_header
+    );
+    assert!(events[0]
+        .event_contents_body
+        .as_str()
+        .contains("https://gitea.mycompany.com/me/proj/compare/aaa...bbb"));
+    assert!(events[0]
+        .event_contents_body
+        .as_str()
+        .contains("01234567 Fix the build"));
+    assert_eq!(
+        "Pull Request #12 Merged: Add export",
+        events[1].event_contents_header
+    );
+    assert!(events[1]
+        .event_contents_body
+        .as_str()
+        .contains("https://gitea.mycompany.com/me/proj/pulls/12"));
+    assert_eq!(Icon::COMMENT_DOTS, events[2].event_type_icon);
+    assert!(events[2]
+        .event_contents_body
+        .as_str()
+        .contains("thanks &lt;3"));
+    assert_eq!(Icon::TAG, events[3].event_type_icon);
+    assert_eq!("Pushed tag 1.0: me/proj", events[3].event_contents_header);
+}
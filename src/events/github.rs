@@ -0,0 +1,774 @@
+use super::events::{ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode};
+use super::rest_api::{self, PagedRecords, RestApi};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use itertools::Itertools;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+const PAGE_SIZE: usize = 100;
+// the events API returns at most 300 events, from the last 90 days
+const MAX_FEED_EVENTS: usize = 300;
+const MAX_FEED_DAYS: i64 = 90;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GithubConfig {
+    // https://github.com, or the URL of a github enterprise instance
+    pub github_url: String,
+    pub personal_access_token: String,
+}
+
+impl GithubConfig {
+    fn api_url(&self) -> String {
+        let url = self.github_url.trim_end_matches('/');
+        if url == "https://github.com" || url == "http://github.com" {
+            "https://api.github.com".to_string()
+        } else {
+            // github enterprise
+            format!("{}/api/v3", url)
+        }
+    }
+}
+
+impl RestApi for GithubConfig {
+    fn source_name(&self) -> &'static str {
+        "Github"
+    }
+
+    fn prepare_request(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        request
+            .header(
+                "Authorization",
+                format!("token {}", self.personal_access_token),
+            )
+            .header("Accept", "application/vnd.github+json")
+    }
+}
+
+pub struct Github;
+const GITHUB_URL_KEY: &str = "Github URL";
+const PERSONAL_TOKEN_KEY: &str = "Personal Access Token";
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+struct GithubUser {
+    login: String,
+}
+
+// the login depends on the token, and we don't want to store the token in the cache
+#[derive(Deserialize, Serialize, Clone, Debug)]
+struct GithubLoginCache {
+    token_hash: u64,
+    login: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GithubRepo {
+    name: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GithubEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    repo: GithubRepo,
+    created_at: DateTime<Local>,
+    payload: GithubPayload,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GithubPayload {
+    action: Option<String>,
+    pull_request: Option<GithubIssue>,
+    issue: Option<GithubIssue>,
+    review: Option<GithubReview>,
+    comment: Option<GithubComment>,
+    release: Option<GithubRelease>,
+}
+
+// issues & pull requests. The search API returns pull requests as issues,
+// with a "pull_request" field.
+#[derive(Deserialize, Clone, Debug)]
+struct GithubIssue {
+    number: usize,
+    title: String,
+    html_url: String,
+    #[serde(default)]
+    merged: bool,
+    pull_request: Option<serde_json::Value>,
+    // the API url of the issue
+    url: Option<String>,
+    // only in search results
+    repository_url: Option<String>,
+    created_at: Option<DateTime<Local>>,
+    closed_at: Option<DateTime<Local>>,
+    // only when fetching a single issue
+    closed_by: Option<GithubUser>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GithubReview {
+    state: String,
+    body: Option<String>,
+    html_url: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GithubComment {
+    body: String,
+    html_url: String,
+    path: Option<String>,
+    line: Option<usize>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GithubRelease {
+    tag_name: String,
+    name: Option<String>,
+    html_url: String,
+    body: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GithubSearchResult {
+    items: Vec<GithubIssue>,
+}
+
+impl Github {
+    fn open_in_browser_link(url: &str) -> String {
+        format!(
+            "<a href=\"{}\">Open in browser</a>",
+            glib::markup_escape_text(url)
+        )
+    }
+
+    fn build_event(
+        icon: Icon,
+        time: NaiveTime,
+        info: &str,
+        header: String,
+        body: String,
+        extra: String,
+    ) -> Event {
+        Event::new(
+            "Github",
+            icon,
+            time,
+            info.to_string(),
+            header,
+            EventBody::Markup(body, WordWrapMode::WordWrap),
+            Some(extra),
+        )
+    }
+
+    fn gather_pull_request_events(github_events: &[GithubEvent]) -> Vec<Event> {
+        github_events
+            .iter()
+            .filter(|evt| evt.event_type == "PullRequestEvent")
+            .filter_map(|evt| {
+                let pr = evt.payload.pull_request.as_ref()?;
+                let (verb, icon) = match evt.payload.action.as_deref()? {
+                    "opened" => ("Opened", Icon::CODE_BRANCH),
+                    "reopened" => ("Reopened", Icon::CODE_BRANCH),
+                    "closed" if pr.merged => ("Merged", Icon::CHECK_SQUARE),
+                    "closed" => ("Closed", Icon::CHECK_SQUARE),
+                    _ => return None,
+                };
+                let header = format!("Pull Request #{} {}: {}", pr.number, verb, pr.title);
+                Some(Self::build_event(
+                    icon,
+                    evt.created_at.time(),
+                    &pr.title,
+                    header.clone(),
+                    format!(
+                        "{}\n\n{}\n{}",
+                        Self::open_in_browser_link(&pr.html_url),
+                        glib::markup_escape_text(&evt.repo.name),
+                        glib::markup_escape_text(&header)
+                    ),
+                    format!("Pull request {}", verb.to_lowercase()),
+                ))
+            })
+            .collect()
+    }
+
+    fn gather_review_events(github_events: &[GithubEvent]) -> Vec<Event> {
+        github_events
+            .iter()
+            .filter(|evt| evt.event_type == "PullRequestReviewEvent")
+            .filter_map(|evt| {
+                let pr = evt.payload.pull_request.as_ref()?;
+                let review = evt.payload.review.as_ref()?;
+                let (verb, icon) = match review.state.to_lowercase().as_str() {
+                    "approved" => ("Approved", Icon::THUMBS_UP),
+                    "changes_requested" => ("Requested changes on", Icon::COMMENT_DOTS),
+                    _ => ("Reviewed", Icon::COMMENT_DOTS),
+                };
+                Some(Self::build_event(
+                    icon,
+                    evt.created_at.time(),
+                    &pr.title,
+                    format!("{} Pull Request #{}: {}", verb, pr.number, pr.title),
+                    format!(
+                        "{}\n\n{}",
+                        Self::open_in_browser_link(&review.html_url),
+                        glib::markup_escape_text(review.body.as_deref().unwrap_or(""))
+                    ),
+                    "Pull request review".to_string(),
+                ))
+            })
+            .collect()
+    }
+
+    // the review comments come one by one, group them by pull request
+    fn gather_review_comment_events(github_events: &[GithubEvent]) -> Vec<Event> {
+        let mut data_grouped: Vec<(&GithubIssue, Vec<&GithubEvent>)> = Vec::new();
+        for (_, group) in &github_events
+            .iter()
+            .filter(|evt| {
+                evt.event_type == "PullRequestReviewCommentEvent"
+                    && evt.payload.pull_request.is_some()
+                    && evt.payload.comment.is_some()
+            })
+            .group_by(|evt| evt.payload.pull_request.as_ref().unwrap().html_url.clone())
+        {
+            let evts: Vec<_> = group.collect();
+            data_grouped.push((evts[0].payload.pull_request.as_ref().unwrap(), evts));
+        }
+        data_grouped
+            .iter()
+            .map(|(pr, evts)| {
+                let contents = evts
+                    .iter()
+                    .map(|evt| {
+                        let comment = evt.payload.comment.as_ref().unwrap();
+                        match &comment.path {
+                            Some(path) => format!(
+                                "<b>{}</b>:{}\n    {}",
+                                glib::markup_escape_text(path),
+                                comment.line.unwrap_or(0),
+                                glib::markup_escape_text(&comment.body)
+                            ),
+                            None => glib::markup_escape_text(&comment.body).to_string(),
+                        }
+                    })
+                    .join("\n\n");
+                Self::build_event(
+                    Icon::COMMENT_DOTS,
+                    evts.iter().map(|e| e.created_at).min().unwrap().time(),
+                    &pr.title,
+                    format!("Pull Request #{}: {}", pr.number, pr.title),
+                    format!(
+                        "{}\n\n{}",
+                        Self::open_in_browser_link(
+                            &evts[0].payload.comment.as_ref().unwrap().html_url
+                        ),
+                        contents
+                    ),
+                    "Review comments".to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn issue_desc(issue: &GithubIssue) -> &'static str {
+        if issue.pull_request.is_some() {
+            "Pull Request"
+        } else {
+            "Issue"
+        }
+    }
+
+    fn gather_issue_comment_events(github_events: &[GithubEvent]) -> Vec<Event> {
+        github_events
+            .iter()
+            .filter(|evt| evt.event_type == "IssueCommentEvent")
+            .filter_map(|evt| {
+                let issue = evt.payload.issue.as_ref()?;
+                let comment = evt.payload.comment.as_ref()?;
+                let desc = Self::issue_desc(issue);
+                Some(Self::build_event(
+                    Icon::COMMENT_DOTS,
+                    evt.created_at.time(),
+                    &issue.title,
+                    format!("{} #{} Comment: {}", desc, issue.number, issue.title),
+                    format!(
+                        "{}\n\n{}",
+                        Self::open_in_browser_link(&comment.html_url),
+                        glib::markup_escape_text(&comment.body)
+                    ),
+                    format!("{} comment", desc),
+                ))
+            })
+            .collect()
+    }
+
+    fn gather_issue_events(github_events: &[GithubEvent]) -> Vec<Event> {
+        github_events
+            .iter()
+            .filter(|evt| evt.event_type == "IssuesEvent")
+            .filter_map(|evt| {
+                let issue = evt.payload.issue.as_ref()?;
+                let (verb, icon) = match evt.payload.action.as_deref()? {
+                    "opened" => ("Opened", Icon::TASKS),
+                    "reopened" => ("Reopened", Icon::TASKS),
+                    "closed" => ("Closed", Icon::CHECK_SQUARE),
+                    _ => return None,
+                };
+                let header = format!("Issue #{} {}: {}", issue.number, verb, issue.title);
+                Some(Self::build_event(
+                    icon,
+                    evt.created_at.time(),
+                    &issue.title,
+                    header.clone(),
+                    format!(
+                        "{}\n\n{}\n{}",
+                        Self::open_in_browser_link(&issue.html_url),
+                        glib::markup_escape_text(&evt.repo.name),
+                        glib::markup_escape_text(&header)
+                    ),
+                    format!("Issue {}", verb.to_lowercase()),
+                ))
+            })
+            .collect()
+    }
+
+    fn gather_release_events(github_events: &[GithubEvent]) -> Vec<Event> {
+        github_events
+            .iter()
+            .filter(|evt| {
+                evt.event_type == "ReleaseEvent"
+                    && evt.payload.action.as_deref() == Some("published")
+            })
+            .filter_map(|evt| {
+                let release = evt.payload.release.as_ref()?;
+                let name = release
+                    .name
+                    .as_deref()
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or(&release.tag_name);
+                Some(Self::build_event(
+                    Icon::TAG,
+                    evt.created_at.time(),
+                    name,
+                    format!("Released {} {}", evt.repo.name, name),
+                    format!(
+                        "{}\n\n{}",
+                        Self::open_in_browser_link(&release.html_url),
+                        glib::markup_escape_text(release.body.as_deref().unwrap_or(""))
+                    ),
+                    format!("Release {}", release.tag_name),
+                ))
+            })
+            .collect()
+    }
+
+    // the events API only covers the last 90 days (and 300 events).
+    // For older days we fall back to the search API, which only knows
+    // when issues & pull requests were created or closed.
+    // Other people may have closed the user's issues, we only report
+    // the ones the user closed.
+    fn build_search_events(
+        items: &[GithubIssue],
+        login: &str,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Vec<Event> {
+        let in_day =
+            |t: &Option<DateTime<Local>>| t.filter(|t| *t >= day_start && *t < next_day_start);
+        let mut events = vec![];
+        for item in items {
+            let desc = Self::issue_desc(item);
+            let repo_name = item
+                .repository_url
+                .as_deref()
+                .and_then(|u| u.split("/repos/").nth(1))
+                .unwrap_or("");
+            let mut push_event = |time: DateTime<Local>, verb: &str, icon: Icon| {
+                let header = format!("{} #{} {}: {}", desc, item.number, verb, item.title);
+                events.push(Self::build_event(
+                    icon,
+                    time.time(),
+                    &item.title,
+                    header.clone(),
+                    format!(
+                        "{}\n\n{}\n{}",
+                        Self::open_in_browser_link(&item.html_url),
+                        glib::markup_escape_text(repo_name),
+                        glib::markup_escape_text(&header)
+                    ),
+                    format!("{} {}", desc, verb.to_lowercase()),
+                ));
+            };
+            if let Some(created) = in_day(&item.created_at) {
+                push_event(
+                    created,
+                    "Opened",
+                    if item.pull_request.is_some() {
+                        Icon::CODE_BRANCH
+                    } else {
+                        Icon::TASKS
+                    },
+                );
+            }
+            let closed_by_user = item
+                .closed_by
+                .as_ref()
+                .filter(|u| u.login.eq_ignore_ascii_case(login))
+                .is_some();
+            if let Some(closed) = in_day(&item.closed_at).filter(|_| closed_by_user) {
+                push_event(closed, "Closed", Icon::CHECK_SQUARE);
+            }
+        }
+        events
+    }
+
+    fn token_hash(github_config: &GithubConfig) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        github_config.api_url().hash(&mut hasher);
+        github_config.personal_access_token.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn get_login(config_name: &str, github_config: &GithubConfig) -> Result<String> {
+        let token_hash = Self::token_hash(github_config);
+        let cache = Config::get_cached_contents(
+            &Github,
+            config_name,
+            &Local.ymd(1970, 1, 1).and_hms(0, 0, 0),
+        )?;
+        if let Some(cached) = cache
+            .and_then(|json| serde_json::from_str::<GithubLoginCache>(&json).ok())
+            .filter(|c| c.token_hash == token_hash)
+        {
+            return Ok(cached.login);
+        }
+        let json = rest_api::send_with_backoff(
+            &rest_api::http_client()?,
+            &format!("{}/user", github_config.api_url()),
+            github_config,
+        )?
+        .text()?;
+        let user = serde_json::from_str::<GithubUser>(&json)
+            .map_err(|e| format!("Failed parsing json /user {:?} -- {}", e, json))?;
+        Config::write_to_cache(
+            &Github,
+            config_name,
+            &serde_json::to_string(&GithubLoginCache {
+                token_hash,
+                login: user.login.clone(),
+            })?,
+        )?;
+        Ok(user.login)
+    }
+
+    /// the first page must succeed, but we return partial data if a later page fails.
+    /// We stop paging once `is_done` returns true for the records fetched so far.
+    fn call_github_rest<T>(
+        get_url: &str,
+        github_config: &GithubConfig,
+        is_done: impl Fn(&[T]) -> bool,
+    ) -> Result<PagedRecords<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let url = format!(
+            "{}{}?per_page={}",
+            github_config.api_url(),
+            get_url,
+            PAGE_SIZE
+        );
+        rest_api::get_paged(github_config, &url, is_done)
+    }
+
+    fn get_issue(
+        client: &reqwest::blocking::Client,
+        github_config: &GithubConfig,
+        url: &str,
+    ) -> Result<GithubIssue> {
+        let json = rest_api::send_with_backoff(client, url, github_config)?.text()?;
+        Ok(serde_json::from_str(&json)
+            .map_err(|e| format!("Failed parsing json {} {:?} -- {}", url, e, json))?)
+    }
+
+    fn search_issues(
+        github_config: &GithubConfig,
+        login: &str,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Result<Vec<GithubIssue>> {
+        let client = rest_api::http_client()?;
+        let day = day_start.date();
+        let day_str = day.format("%F");
+        // the search API dates are in UTC, search a bit wider, we filter later
+        let date_range = format!("{}..{}", day.pred().format("%F"), day.succ().format("%F"));
+        let mut items = vec![];
+        for query in &[
+            format!("author:{} created:{}", login, date_range),
+            format!("author:{} closed:{}", login, date_range),
+        ] {
+            let url = format!(
+                "{}/search/issues?per_page={}&q={}",
+                github_config.api_url(),
+                PAGE_SIZE,
+                query.replace(' ', "+")
+            );
+            let json = rest_api::send_with_backoff(&client, &url, github_config)?.text()?;
+            log::debug!("search {} for {}: {}", query, day_str, json);
+            let result = serde_json::from_str::<GithubSearchResult>(&json)
+                .map_err(|e| format!("Failed parsing json {} {:?} -- {}", url, e, json))?;
+            for item in result.items {
+                if !items
+                    .iter()
+                    .any(|i: &GithubIssue| i.html_url == item.html_url)
+                {
+                    items.push(item);
+                }
+            }
+        }
+        // the search results don't say who closed the issues
+        for item in items.iter_mut().filter(|i| {
+            i.closed_at
+                .filter(|t| *t >= day_start && *t < next_day_start)
+                .is_some()
+        }) {
+            if let Some(url) = item.url.clone() {
+                match Self::get_issue(&client, github_config, &url) {
+                    Ok(issue) => item.closed_by = issue.closed_by,
+                    Err(e) => log::warn!("Github: can't get the issue {}: {}", url, e),
+                }
+            }
+        }
+        Ok(items)
+    }
+}
+
+impl EventProvider for Github {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (GITHUB_URL_KEY, ConfigType::Text("https://github.com")),
+            (PERSONAL_TOKEN_KEY, ConfigType::Password),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Github"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::CODE_BRANCH
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.github.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        _field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                GITHUB_URL_KEY,
+                config.github[config_name].github_url.to_string(),
+            ),
+            (
+                PERSONAL_TOKEN_KEY,
+                config.github[config_name].personal_access_token.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.github.insert(
+            config_name,
+            GithubConfig {
+                github_url: config_values.remove(GITHUB_URL_KEY).unwrap(),
+                personal_access_token: config_values.remove(PERSONAL_TOKEN_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.github.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let github_config = &config.github[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let login = Self::get_login(config_name, github_config)?;
+        // the events are sorted by date, most recent first
+        let github_records = Self::call_github_rest::<GithubEvent>(
+            &format!("/users/{}/events", login),
+            github_config,
+            |evts| evts.last().filter(|e| e.created_at < day_start).is_some(),
+        )?;
+        let day_covered = github_records
+            .records
+            .last()
+            .filter(|e| e.created_at < day_start)
+            .is_some();
+        // the feed is truncated, it's not that we had no activity
+        let feed_truncated = github_records.records.len() >= MAX_FEED_EVENTS
+            || day_start < Local::now() - chrono::Duration::days(MAX_FEED_DAYS);
+        if !day_covered && feed_truncated && github_records.error.is_none() {
+            log::debug!("Github: the events API doesn't cover {}, searching", day);
+            let items = Self::search_issues(github_config, &login, day_start, next_day_start)?;
+            return Ok(Self::build_search_events(
+                &items,
+                &login,
+                day_start,
+                next_day_start,
+            ));
+        }
+        let github_events: Vec<_> = github_records
+            .records
+            .into_iter()
+            .filter(|e| e.created_at >= day_start && e.created_at < next_day_start)
+            .collect();
+
+        let mut events = Self::gather_pull_request_events(&github_events);
+        events.append(&mut Self::gather_review_events(&github_events));
+        events.append(&mut Self::gather_review_comment_events(&github_events));
+        events.append(&mut Self::gather_issue_comment_events(&github_events));
+        events.append(&mut Self::gather_issue_events(&github_events));
+        events.append(&mut Self::gather_release_events(&github_events));
+        if let Some(err) = github_records.error {
+            events.push(rest_api::missing_events_event("Github", &err));
+        }
+        Ok(events)
+    }
+}
+
+#[test]
+fn it_computes_the_api_url() {
+    let config = |url: &str| GithubConfig {
+        github_url: url.to_string(),
+        personal_access_token: "".to_string(),
+    };
+    assert_eq!(
+        "https://api.github.com",
+        config("https://github.com/").api_url()
+    );
+    assert_eq!(
+        "https://github.mycompany.com/api/v3",
+        config("https://github.mycompany.com").api_url()
+    );
+}
+
+#[test]
+fn it_builds_events_for_pull_requests_reviews_and_comments() {
+    let github_events: Vec<GithubEvent> = serde_json::from_str(
+        r#"[{"type":"PullRequestEvent","repo":{"name":"me/proj"},"created_at":"2020-03-23T10:00:00Z",
+             "payload":{"action":"closed","pull_request":{"number":3,"title":"Add export",
+                        "html_url":"https://github.com/me/proj/pull/3","merged":true}}},
+            {"type":"PullRequestReviewEvent","repo":{"name":"me/proj"},"created_at":"2020-03-23T11:00:00Z",
+             "payload":{"action":"created","review":{"state":"approved","body":null,
+                        "html_url":"https://github.com/me/proj/pull/4#review"},
+                        "pull_request":{"number":4,"title":"Fix <build>",
+                        "html_url":"https://github.com/me/proj/pull/4"}}},
+            {"type":"PullRequestReviewCommentEvent","repo":{"name":"me/proj"},"created_at":"2020-03-23T12:00:00Z",
+             "payload":{"action":"created","comment":{"body":"typo","path":"src/main.rs","line":3,
+                        "html_url":"https://github.com/me/proj/pull/4#c1"},
+                        "pull_request":{"number":4,"title":"Fix <build>",
+                        "html_url":"https://github.com/me/proj/pull/4"}}},
+            {"type":"PullRequestReviewCommentEvent","repo":{"name":"me/proj"},"created_at":"2020-03-23T12:01:00Z",
+             "payload":{"action":"created","comment":{"body":"and here","path":"src/lib.rs","line":5,
+                        "html_url":"https://github.com/me/proj/pull/4#c2"},
+                        "pull_request":{"number":4,"title":"Fix <build>",
+                        "html_url":"https://github.com/me/proj/pull/4"}}},
+            {"type":"IssueCommentEvent","repo":{"name":"me/proj"},"created_at":"2020-03-23T13:00:00Z",
+             "payload":{"action":"created","comment":{"body":"thanks!",
+                        "html_url":"https://github.com/me/proj/pull/3#c3"},
+                        "issue":{"number":3,"title":"Add export","pull_request":{},
+                        "html_url":"https://github.com/me/proj/pull/3"}}},
+            {"type":"ReleaseEvent","repo":{"name":"me/proj"},"created_at":"2020-03-23T14:00:00Z",
+             "payload":{"action":"published","release":{"tag_name":"1.0","name":"",
+                        "html_url":"https://github.com/me/proj/releases/1.0","body":"notes"}}}]"#,
+    )
+    .unwrap();
+    let prs = Github::gather_pull_request_events(&github_events);
+    assert_eq!(1, prs.len());
+    assert_eq!(
+        "Pull Request #3 Merged: Add export",
+        prs[0].event_contents_header
+    );
+    let reviews = Github::gather_review_events(&github_events);
+    assert_eq!(Icon::THUMBS_UP, reviews[0].event_type_icon);
+    assert_eq!(
+        "Approved Pull Request #4: Fix <build>",
+        reviews[0].event_contents_header
+    );
+    let comments = Github::gather_review_comment_events(&github_events);
+    assert_eq!(1, comments.len());
+    assert!(comments[0]
+        .event_contents_body
+        .as_str()
+        .contains("<b>src/lib.rs</b>:5\n    and here"));
+    let issue_comments = Github::gather_issue_comment_events(&github_events);
+    assert_eq!(
+        "Pull Request #3 Comment: Add export",
+        issue_comments[0].event_contents_header
+    );
+    let releases = Github::gather_release_events(&github_events);
+    assert_eq!("Released me/proj 1.0", releases[0].event_contents_header);
+}
+
+#[test]
+fn it_builds_events_for_the_issues_the_user_opened_or_closed() {
+    let items: Vec<GithubIssue> = serde_json::from_str(
+        r#"[{"number":1,"title":"Crash on startup","html_url":"https://github.com/me/proj/issues/1",
+             "repository_url":"https://api.github.com/repos/me/proj",
+             "created_at":"2020-03-23T11:00:00Z","closed_at":"2020-03-23T12:00:00Z",
+             "closed_by":{"login":"Me"}},
+            {"number":2,"title":"Add export","html_url":"https://github.com/me/proj/issues/2",
+             "repository_url":"https://api.github.com/repos/me/proj",
+             "created_at":"2020-03-20T11:00:00Z","closed_at":"2020-03-23T13:00:00Z",
+             "closed_by":{"login":"someone-else"}}]"#,
+    )
+    .unwrap();
+    let day_start = Local.ymd(2020, 3, 23).and_hms(0, 0, 0);
+    let events = Github::build_search_events(
+        &items,
+        "me",
+        day_start,
+        day_start + chrono::Duration::days(1),
+    );
+    assert_eq!(
+        vec![
+            "Issue #1 Opened: Crash on startup",
+            "Issue #1 Closed: Crash on startup"
+        ],
+        events
+            .iter()
+            .map(|e| e.event_contents_header.as_str())
+            .collect::<Vec<_>>()
+    );
+}
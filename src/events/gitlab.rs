@@ -1,8 +1,10 @@
-use super::events::{ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode};
+use super::events::{
+    ConfigType, Event, EventBody, EventProvider, LazyEventDetails, Result, WordWrapMode,
+};
+use super::rest_api::{self, PagedRecords, RestApi};
 use crate::config::Config;
 use crate::icons::*;
 use chrono::prelude::*;
-use core::time::Duration;
 use itertools::{join, Itertools};
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Cow;
@@ -10,12 +12,42 @@ use std::collections::{HashMap, HashSet};
 
 type ProjectId = usize;
 
+const PAGE_SIZE: usize = 100;
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct GitlabConfig {
     pub gitlab_url: String,
     pub personal_access_token: String,
 }
 
+impl RestApi for GitlabConfig {
+    fn source_name(&self) -> &'static str {
+        "Gitlab"
+    }
+
+    fn prepare_request(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        request.header("PRIVATE-TOKEN", &self.personal_access_token)
+    }
+
+    // prefer the Link header, which also works with keyset pagination
+    fn next_page_url(
+        &self,
+        headers: &reqwest::header::HeaderMap,
+        first_url: &str,
+    ) -> Option<String> {
+        rest_api::next_page_link(headers).or_else(|| {
+            headers
+                .get("X-Next-Page")
+                .and_then(|h| h.to_str().ok())
+                .filter(|p| !p.is_empty())
+                .map(|p| format!("{}&page={}", first_url, p))
+        })
+    }
+}
+
 pub struct Gitlab;
 const GITLAB_URL_KEY: &str = "Gitlab URL";
 const PERSONAL_TOKEN_KEY: &str = "Personal Access Token";
@@ -45,6 +77,19 @@ struct GitlabEvent {
     created_at: DateTime<Local>,
     note: Option<GitlabNote>,
     target_iid: Option<usize>,
+    push_data: Option<GitlabPushData>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+struct GitlabPushData {
+    commit_count: usize,
+    action: String,   // pushed, created, removed
+    ref_type: String, // branch, tag
+    commit_from: Option<String>,
+    commit_to: Option<String>,
+    #[serde(rename = "ref")]
+    ref_name: Option<String>,
+    commit_title: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -53,6 +98,36 @@ struct GitlabProject {
     web_url: String,
 }
 
+// a merge request or an issue, as we display it in the event details
+#[derive(Deserialize, Clone, Debug)]
+struct GitlabTarget {
+    title: String,
+    description: Option<String>,
+    state: String,
+    #[serde(default)]
+    labels: Vec<String>,
+    milestone: Option<GitlabMilestone>,
+    #[serde(default)]
+    assignees: Vec<GitlabUser>,
+    // only for merge requests
+    head_pipeline: Option<GitlabPipeline>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GitlabMilestone {
+    title: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GitlabUser {
+    name: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GitlabPipeline {
+    status: String,
+}
+
 impl Gitlab {
     fn noteable_type_desc(note_type: &str) -> String {
         if note_type == "MergeRequest" {
@@ -66,17 +141,40 @@ impl Gitlab {
         project_infos: &HashMap<ProjectId, String>,
         evt: &GitlabEvent,
     ) -> Cow<'static, str> {
-        if let Some(iid) = evt.note.as_ref().unwrap().noteable_iid {
-            Cow::from(format!(
+        match (
+            project_infos.get(&evt.project_id),
+            evt.note.as_ref().unwrap().noteable_iid,
+        ) {
+            (Some(project_url), Some(iid)) => Cow::from(format!(
                 "<a href=\"{}{}{}\">Open in browser</a>\n\n",
-                project_infos[&evt.project_id], "/merge_requests/", iid
-            ))
-        } else {
-            Cow::from("")
+                project_url, "/merge_requests/", iid
+            )),
+            _ => Cow::from(""),
+        }
+    }
+
+    // we can fetch more details only for merge requests & issues
+    fn with_target_details(
+        event: Event,
+        config_name: &str,
+        g_evt: &GitlabEvent,
+        target_type: Option<&str>,
+        iid: Option<usize>,
+    ) -> Event {
+        match (target_type, iid) {
+            (Some(t @ "MergeRequest"), Some(iid)) | (Some(t @ "Issue"), Some(iid)) => event
+                .with_lazy_details(LazyEventDetails::GitlabTarget {
+                    config_name: config_name.to_string(),
+                    project_id: g_evt.project_id,
+                    target_type: t.to_string(),
+                    iid,
+                }),
+            _ => event,
         }
     }
 
     fn build_mr_comment_event(
+        config_name: &str,
         target_title: &str,
         evts: &[&GitlabEvent],
         project_infos: &HashMap<ProjectId, String>,
@@ -111,20 +209,14 @@ impl Gitlab {
                 .unwrap()
                 .noteable_type,
         );
-        let header = if let Some(iid) = evts
-            .iter()
-            .next()
-            .unwrap()
-            .note
-            .as_ref()
-            .unwrap()
-            .noteable_iid
-        {
+        let first_evt = evts.first().unwrap();
+        let first_note = first_evt.note.as_ref().unwrap();
+        let header = if let Some(iid) = first_note.noteable_iid {
             format!("{} #{}: {}", note_type_desc, iid, target_title)
         } else {
             format!("{}: {}", note_type_desc, target_title)
         };
-        Event::new(
+        let event = Event::new(
             "Gitlab",
             Icon::COMMENT_DOTS,
             evts.iter()
@@ -136,26 +228,36 @@ impl Gitlab {
             header,
             EventBody::Markup(contents, WordWrapMode::WordWrap),
             Some(note_type_desc),
+        );
+        Self::with_target_details(
+            event,
+            config_name,
+            first_evt,
+            Some(&first_note.noteable_type),
+            first_note.noteable_iid,
         )
     }
 
+    fn is_merge_request_comment(evt: &GitlabEvent) -> bool {
+        evt.note.is_some()
+            && evt.target_title.is_some()
+            && (evt.target_type.as_deref() == Some("DiffNote")
+                || evt
+                    .note
+                    .as_ref()
+                    .filter(|n| &n.noteable_type == "MergeRequest")
+                    .is_some())
+    }
+
     fn gather_merge_request_comments(
+        config_name: &str,
         gitlab_events: &[GitlabEvent],
         project_infos: &HashMap<ProjectId, String>,
     ) -> Vec<Event> {
         let mut data_grouped: Vec<(&String, Vec<&GitlabEvent>)> = Vec::new();
         for (key, group) in &gitlab_events
             .iter()
-            .filter(|evt| {
-                evt.note.is_some()
-                    && evt.target_title.is_some()
-                    && (evt.target_type.as_deref() == Some("DiffNote")
-                        || evt
-                            .note
-                            .as_ref()
-                            .filter(|n| &n.noteable_type == "MergeRequest")
-                            .is_some())
-            })
+            .filter(|evt| Self::is_merge_request_comment(evt))
             .group_by(|evt| evt.target_title.as_ref().unwrap())
         {
             data_grouped.push((key, group.collect()));
@@ -163,23 +265,27 @@ impl Gitlab {
         data_grouped
             .iter()
             .map(|(target_title, evts)| {
-                Self::build_mr_comment_event(target_title, evts, project_infos)
+                Self::build_mr_comment_event(config_name, target_title, evts, project_infos)
             })
             .collect()
     }
 
+    fn is_accept_event(evt: &GitlabEvent) -> bool {
+        (evt.action_name == "accepted" && evt.target_type.as_deref() == Some("MergeRequest"))
+            || (evt.action_name == "closed" && evt.target_type.as_deref() == Some("Issue"))
+    }
+
     fn gather_accept_events(
+        config_name: &str,
         gitlab_events: &[GitlabEvent],
         project_infos: &HashMap<ProjectId, String>,
     ) -> Vec<Event> {
         gitlab_events
             .iter()
-            .filter(|evt| {
-                (evt.action_name == "accepted"
-                    && evt.target_type.as_deref() == Some("MergeRequest"))
-                    || (evt.action_name == "closed" && evt.target_type.as_deref() == Some("Issue"))
-            })
-            .map(|g_evt| {
+            .filter(|evt| Self::is_accept_event(evt))
+            // skip the events on projects we can't see anymore
+            .filter_map(|g_evt| {
+                let project_url = project_infos.get(&g_evt.project_id)?;
                 let (desc, url_part) = match g_evt.target_type.as_deref().unwrap() {
                     "MergeRequest" => ("Merge Request", "/merge_requests/"),
                     "Issue" => ("Issue", "/issues/"),
@@ -196,12 +302,12 @@ impl Gitlab {
                 );
                 let body = format!(
                     "<a href=\"{}{}{}\">Open in browser</a>\n\n{}",
-                    project_infos[&g_evt.project_id],
+                    project_url,
                     url_part,
                     g_evt.target_iid.unwrap(),
                     title
                 );
-                Event::new(
+                let event = Event::new(
                     "Gitlab",
                     Icon::CHECK_SQUARE,
                     g_evt.created_at.time(),
@@ -209,21 +315,32 @@ impl Gitlab {
                     title,
                     EventBody::Markup(body, WordWrapMode::WordWrap),
                     Some(format!("{} accepted", desc)),
-                )
+                );
+                Some(Self::with_target_details(
+                    event,
+                    config_name,
+                    g_evt,
+                    g_evt.target_type.as_deref(),
+                    g_evt.target_iid,
+                ))
             })
             .collect()
     }
 
+    fn is_issue_open_event(evt: &GitlabEvent) -> bool {
+        evt.action_name == "opened" && evt.target_type.as_deref() == Some("Issue")
+    }
+
     fn gather_issue_open_events(
+        config_name: &str,
         gitlab_events: &[GitlabEvent],
         project_infos: &HashMap<ProjectId, String>,
     ) -> Vec<Event> {
         gitlab_events
             .iter()
-            .filter(|evt| {
-                evt.action_name == "opened" && evt.target_type.as_deref() == Some("Issue")
-            })
-            .map(|g_evt| {
+            .filter(|evt| Self::is_issue_open_event(evt))
+            .filter_map(|g_evt| {
+                let project_url = project_infos.get(&g_evt.project_id)?;
                 let title = format!(
                     "Issue #{} Opened: {}",
                     g_evt.target_iid.unwrap(),
@@ -231,12 +348,12 @@ impl Gitlab {
                 );
                 let body = format!(
                     "<a href=\"{}{}{}\">Open in browser</a>\n\n{}",
-                    project_infos[&g_evt.project_id],
+                    project_url,
                     "/issues/",
                     g_evt.target_iid.unwrap(),
                     title
                 );
-                Event::new(
+                let event = Event::new(
                     "Gitlab",
                     Icon::COMMENT_DOTS,
                     g_evt.created_at.time(),
@@ -244,28 +361,40 @@ impl Gitlab {
                     title,
                     EventBody::Markup(body, WordWrapMode::WordWrap),
                     Some("Issue opened".to_string()),
-                )
+                );
+                Some(Self::with_target_details(
+                    event,
+                    config_name,
+                    g_evt,
+                    Some("Issue"),
+                    g_evt.target_iid,
+                ))
             })
             .collect()
     }
 
+    // the target type can be Note, DiscussionNote or DiffNote,
+    // the note tells us what was commented
+    fn is_issue_comment_event(evt: &GitlabEvent) -> bool {
+        evt.action_name == "commented on"
+            && !Self::is_merge_request_comment(evt)
+            && evt
+                .note
+                .as_ref()
+                .filter(|n| &n.noteable_type != "MergeRequest")
+                .is_some()
+    }
+
     fn gather_issue_comment_events(
+        config_name: &str,
         gitlab_events: &[GitlabEvent],
         project_infos: &HashMap<ProjectId, String>,
     ) -> Vec<Event> {
         gitlab_events
             .iter()
-            .filter(|evt| {
-                evt.action_name == "commented on"
-                    && evt.target_type.as_deref() == Some("Note")
-                    && evt.note.is_some()
-                    && evt
-                        .note
-                        .as_ref()
-                        .filter(|n| &n.noteable_type != "MergeRequest")
-                        .is_some()
-            })
-            .map(|g_evt| {
+            .filter(|evt| Self::is_issue_comment_event(evt))
+            .filter_map(|g_evt| {
+                let project_url = project_infos.get(&g_evt.project_id)?;
                 let title = if let Some(iid) = g_evt.note.as_ref().unwrap().noteable_iid {
                     format!(
                         "Issue #{} Comment: {}",
@@ -278,12 +407,12 @@ impl Gitlab {
                 let body = if let Some(iid) = g_evt.note.as_ref().unwrap().noteable_iid {
                     format!(
                         "<a href=\"{}{}{}\">Open in browser</a>\n\n{}",
-                        project_infos[&g_evt.project_id], "/issues/", iid, title
+                        project_url, "/issues/", iid, title
                     )
                 } else {
                     title.clone()
                 };
-                Event::new(
+                let event = Event::new(
                     "Gitlab",
                     Icon::COMMENT_DOTS,
                     g_evt.created_at.time(),
@@ -291,11 +420,186 @@ impl Gitlab {
                     title,
                     EventBody::Markup(body, WordWrapMode::WordWrap),
                     Some("Issue comment".to_string()),
-                )
+                );
+                let note = g_evt.note.as_ref().unwrap();
+                Some(Self::with_target_details(
+                    event,
+                    config_name,
+                    g_evt,
+                    Some(&note.noteable_type),
+                    note.noteable_iid,
+                ))
             })
             .collect()
     }
 
+    // "pushed to", "pushed new" and "deleted" (branches & tags)
+    fn build_push_event(
+        g_evt: &GitlabEvent,
+        push_data: &GitlabPushData,
+        project_url: &str,
+    ) -> Event {
+        let ref_name = push_data.ref_name.as_deref().unwrap_or("");
+        let (title, icon, extra, url) = match push_data.action.as_str() {
+            "removed" => (
+                format!("Deleted {} {}", push_data.ref_type, ref_name),
+                Icon::TRASH_ALT,
+                format!("{} deleted", push_data.ref_type),
+                project_url.to_string(),
+            ),
+            "created" => (
+                format!("Created {} {}", push_data.ref_type, ref_name),
+                Icon::CODE_BRANCH,
+                format!("{} created", push_data.ref_type),
+                format!("{}/tree/{}", project_url, ref_name),
+            ),
+            _ => (
+                format!(
+                    "Pushed {} commit(s) to {}",
+                    push_data.commit_count, ref_name
+                ),
+                Icon::UPLOAD,
+                format!("{} commit(s) pushed", push_data.commit_count),
+                match (&push_data.commit_from, &push_data.commit_to) {
+                    (Some(from), Some(to)) => {
+                        format!("{}/compare/{}...{}", project_url, from, to)
+                    }
+                    _ => format!("{}/commits/{}", project_url, ref_name),
+                },
+            ),
+        };
+        let commit_title = push_data.commit_title.as_deref().unwrap_or("");
+        let body = format!(
+            "<a href=\"{}\">Open in browser</a>\n\n{}\n{}",
+            url,
+            glib::markup_escape_text(&title),
+            glib::markup_escape_text(commit_title)
+        );
+        Event::new(
+            "Gitlab",
+            icon,
+            g_evt.created_at.time(),
+            if commit_title.is_empty() {
+                title.clone()
+            } else {
+                commit_title.to_string()
+            },
+            title,
+            EventBody::Markup(body, WordWrapMode::WordWrap),
+            Some(extra),
+        )
+    }
+
+    fn gather_push_events(
+        gitlab_events: &[GitlabEvent],
+        project_infos: &HashMap<ProjectId, String>,
+    ) -> Vec<Event> {
+        gitlab_events
+            .iter()
+            .filter_map(|evt| {
+                let project_url = project_infos.get(&evt.project_id)?;
+                evt.push_data
+                    .as_ref()
+                    .map(|push_data| Self::build_push_event(evt, push_data, project_url))
+            })
+            .collect()
+    }
+
+    fn target_type_desc(target_type: &str) -> (&'static str, Icon, &'static str) {
+        match target_type {
+            "MergeRequest" => ("Merge Request", Icon::CODE_BRANCH, "/merge_requests/"),
+            "Issue" => ("Issue", Icon::TASKS, "/issues/"),
+            "Milestone" => ("Milestone", Icon::CALENDAR_ALT, "/milestones/"),
+            "Snippet" | "ProjectSnippet" => ("Snippet", Icon::FILE_ALT, "/snippets/"),
+            "WikiPage::Meta" => ("Wiki page", Icon::BOOK, "/wikis/"),
+            "DesignManagement::Design" => ("Design", Icon::FILE_ALT, "/issues/"),
+            _ => ("", Icon::COMMENT_DOTS, ""),
+        }
+    }
+
+    // everything which the other gatherers don't handle: opened & approved
+    // merge requests, reopened issues, joined projects, wiki edits, milestones...
+    fn gather_other_events(
+        config_name: &str,
+        gitlab_events: &[GitlabEvent],
+        project_infos: &HashMap<ProjectId, String>,
+    ) -> Vec<Event> {
+        gitlab_events
+            .iter()
+            .filter(|evt| {
+                evt.push_data.is_none()
+                    && !Self::is_merge_request_comment(evt)
+                    && !Self::is_accept_event(evt)
+                    && !Self::is_issue_open_event(evt)
+                    && !Self::is_issue_comment_event(evt)
+            })
+            .filter_map(|g_evt| {
+                let project_url = project_infos.get(&g_evt.project_id)?;
+                let (target_desc, target_icon, url_part) =
+                    Self::target_type_desc(g_evt.target_type.as_deref().unwrap_or(""));
+                let icon = match g_evt.action_name.as_str() {
+                    "approved" => Icon::THUMBS_UP,
+                    "joined" | "left" => Icon::USERS,
+                    "deleted" | "destroyed" => Icon::TRASH_ALT,
+                    _ => target_icon,
+                };
+                let target_title = g_evt.target_title.as_deref().unwrap_or("");
+                // joined/left have no target: "joined project"
+                let target_name = match (target_desc, g_evt.target_iid) {
+                    ("", _) => "project".to_string(),
+                    (desc, Some(iid)) => format!("{} #{}", desc, iid),
+                    (desc, None) => desc.to_string(),
+                };
+                let title = format!(
+                    "{} {}{}{}",
+                    Self::capitalize(&g_evt.action_name),
+                    target_name,
+                    if target_title.is_empty() { "" } else { ": " },
+                    target_title
+                );
+                let url = match g_evt.target_iid {
+                    Some(iid) if !url_part.is_empty() => {
+                        format!("{}{}{}", project_url, url_part, iid)
+                    }
+                    _ => project_url.to_string(),
+                };
+                let body = format!(
+                    "<a href=\"{}\">Open in browser</a>\n\n{}",
+                    url,
+                    glib::markup_escape_text(&title)
+                );
+                let event = Event::new(
+                    "Gitlab",
+                    icon,
+                    g_evt.created_at.time(),
+                    if target_title.is_empty() {
+                        title.clone()
+                    } else {
+                        target_title.to_string()
+                    },
+                    title,
+                    EventBody::Markup(body, WordWrapMode::WordWrap),
+                    Some(format!("{} {}", target_name, g_evt.action_name)),
+                );
+                Some(Self::with_target_details(
+                    event,
+                    config_name,
+                    g_evt,
+                    g_evt.target_type.as_deref(),
+                    g_evt.target_iid,
+                ))
+            })
+            .collect()
+    }
+
+    fn capitalize(str: &str) -> String {
+        let mut chars = str.chars();
+        match chars.next() {
+            Some(c) => c.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    }
+
     fn get_projects_info(
         config_name: &str,
         gitlab_config: &GitlabConfig,
@@ -317,19 +621,51 @@ impl Gitlab {
             None => {
                 // either no cache or the cache doesn't know some of the
                 // projects (it's outdated) => refetch & store to cache
-                let projects = Self::call_gitlab_rest::<GitlabProject>(
+                let mut projects = Self::call_gitlab_rest::<GitlabProject>(
                     "/api/v4/projects",
-                    &[("simple", "yes"), ("membership", "yes")],
+                    &[
+                        ("simple", "yes"),
+                        ("membership", "yes"),
+                        // keyset pagination is faster for users with many projects
+                        ("pagination", "keyset"),
+                        ("order_by", "id"),
+                        ("sort", "asc"),
+                    ],
                     gitlab_config,
-                )?;
+                )?
+                .complete()?;
+                // we may have activity on projects we're not a member of,
+                // for instance comments on the issues of other projects
+                let known_ids: HashSet<_> = projects.iter().map(|p| p.id).collect();
+                for project_id in project_ids.difference(&known_ids) {
+                    match Self::get_project(gitlab_config, *project_id) {
+                        Ok(project) => projects.push(project),
+                        Err(e) => {
+                            log::warn!("Gitlab: can't get the project {}: {}", project_id, e)
+                        }
+                    }
+                }
                 Config::write_to_cache(&Gitlab, config_name, &serde_json::to_string(&projects)?)?;
-                let hash = Self::get_projects_from_json(projects, project_ids)?
-                    .ok_or("Can't find all projects?")?;
-                Ok(hash)
+                Ok(projects
+                    .into_iter()
+                    .filter(|p| project_ids.contains(&p.id))
+                    .map(|p| (p.id, p.web_url))
+                    .collect())
             }
         }
     }
 
+    fn get_project(gitlab_config: &GitlabConfig, project_id: ProjectId) -> Result<GitlabProject> {
+        let url = format!(
+            "{}/api/v4/projects/{}",
+            gitlab_config.gitlab_url, project_id
+        );
+        let json =
+            rest_api::send_with_backoff(&rest_api::http_client()?, &url, gitlab_config)?.text()?;
+        Ok(serde_json::from_str(&json)
+            .map_err(|e| format!("Failed parsing json {} {:?} -- {}", url, e, json))?)
+    }
+
     fn get_projects_from_json(
         projects: Vec<GitlabProject>,
         project_ids: &HashSet<ProjectId>,
@@ -354,59 +690,77 @@ impl Gitlab {
         }
     }
 
+    /// called when the user selects a merge request or issue event
+    pub fn fetch_target_details(
+        gitlab_config: &GitlabConfig,
+        project_id: ProjectId,
+        target_type: &str,
+        iid: usize,
+    ) -> Result<String> {
+        let (target_desc, url_part) = match target_type {
+            "MergeRequest" => ("Merge Request", "merge_requests"),
+            _ => ("Issue", "issues"),
+        };
+        let url = format!(
+            "{}/api/v4/projects/{}/{}/{}",
+            gitlab_config.gitlab_url, project_id, url_part, iid
+        );
+        let json =
+            rest_api::send_with_backoff(&rest_api::http_client()?, &url, gitlab_config)?.text()?;
+        log::debug!("{}: {}", url, json);
+        let target = serde_json::from_str::<GitlabTarget>(&json)
+            .map_err(|e| format!("Failed parsing json {} {:?} -- {}", url, e, json))?;
+        Ok(Self::target_details_markup(target_desc, iid, &target))
+    }
+
+    fn target_details_markup(target_desc: &str, iid: usize, target: &GitlabTarget) -> String {
+        let mut fields = vec![("State", target.state.clone())];
+        if let Some(pipeline) = &target.head_pipeline {
+            fields.push(("Pipeline", pipeline.status.clone()));
+        }
+        if !target.labels.is_empty() {
+            fields.push(("Labels", target.labels.join(", ")));
+        }
+        if let Some(milestone) = &target.milestone {
+            fields.push(("Milestone", milestone.title.clone()));
+        }
+        if !target.assignees.is_empty() {
+            fields.push((
+                "Assignees",
+                target.assignees.iter().map(|a| &a.name).join(", "),
+            ));
+        }
+        format!(
+            "<b>{} #{}: {}</b>\n\n{}\n\n{}",
+            target_desc,
+            iid,
+            glib::markup_escape_text(&target.title),
+            fields
+                .iter()
+                .map(|(k, v)| format!("<b>{}</b>: {}", k, glib::markup_escape_text(v)))
+                .join("\n"),
+            glib::markup_escape_text(target.description.as_deref().unwrap_or("")).trim()
+        )
+    }
+
+    /// the first page must succeed, but we return partial data if a later page fails
     fn call_gitlab_rest<T>(
         get_url: &str,
         get_params: &[(&'static str, &str)],
         gitlab_config: &GitlabConfig,
-    ) -> Result<Vec<T>>
+    ) -> Result<PagedRecords<T>>
     where
         T: serde::de::DeserializeOwned,
     {
-        let client = reqwest::blocking::ClientBuilder::new()
-            .timeout(Duration::from_secs(30))
-            .connect_timeout(Duration::from_secs(30))
-            .connection_verbose(true)
-            .build()?;
-        let get_params = if get_params.is_empty() {
-            "".to_string()
-        } else {
-            format!(
-                "{}&",
-                get_params
-                    .iter()
-                    .map(|(k, v)| format!("{}={}", k, v))
-                    .join("&")
-            )
-        };
-        let mut page_idx = 1;
-        let mut records = vec![];
-        loop {
-            let resp = client
-                .get(&format!(
-                    "{}/{}?{}page={}",
-                    gitlab_config.gitlab_url, get_url, get_params, page_idx
-                ))
-                .header("PRIVATE-TOKEN", &gitlab_config.personal_access_token)
-                .send()?
-                .error_for_status()?;
-            let page_count = resp
-                .headers()
-                .get("X-Total-Pages")
-                .ok_or("Missing X-Total-Pages header")?
-                .to_str()?
-                .parse::<usize>()?;
-            let json = resp.text()?;
-            log::debug!("{}, page count: {}, text: {}", get_url, page_count, json);
-            records.append(
-                &mut serde_json::from_str::<Vec<T>>(&json)
-                    .map_err(|e| format!("Failed parsing json {} {:?} -- {}", get_url, e, json))?,
-            );
-            if page_idx == page_count {
-                break;
-            }
-            page_idx += 1;
-        }
-        Ok(records)
+        let get_params = get_params
+            .iter()
+            .map(|(k, v)| format!("{}={}&", k, v))
+            .join("");
+        let url = format!(
+            "{}/{}?{}per_page={}",
+            gitlab_config.gitlab_url, get_url, get_params, PAGE_SIZE
+        );
+        rest_api::get_paged(gitlab_config, &url, |_| false)
     }
 }
 
@@ -485,17 +839,19 @@ impl EventProvider for Gitlab {
         let gitlab_config = &config.gitlab[config_name];
         let day_start = day.and_hms(0, 0, 0);
         let next_day_start = day_start + chrono::Duration::days(1);
-        let gitlab_events: Vec<_> = Self::call_gitlab_rest::<GitlabEvent>(
+        let gitlab_records = Self::call_gitlab_rest::<GitlabEvent>(
             "/api/v4/events",
             &[
                 ("after", &day.pred().format("%F").to_string()),
                 ("before", &day.succ().format("%F").to_string()),
             ],
             gitlab_config,
-        )?
-        .into_iter()
-        .filter(|e| e.created_at >= day_start && e.created_at < next_day_start)
-        .collect();
+        )?;
+        let gitlab_events: Vec<_> = gitlab_records
+            .records
+            .into_iter()
+            .filter(|e| e.created_at >= day_start && e.created_at < next_day_start)
+            .collect();
 
         let project_infos = Self::get_projects_info(
             config_name,
@@ -504,19 +860,131 @@ impl EventProvider for Gitlab {
         )?;
         log::debug!("project infos: {:?}", project_infos);
 
-        let mut events = Self::gather_merge_request_comments(&gitlab_events, &project_infos);
+        let mut events =
+            Self::gather_merge_request_comments(config_name, &gitlab_events, &project_infos);
         events.append(&mut Self::gather_accept_events(
+            config_name,
             &gitlab_events,
             &project_infos,
         ));
         events.append(&mut Self::gather_issue_open_events(
+            config_name,
             &gitlab_events,
             &project_infos,
         ));
         events.append(&mut Self::gather_issue_comment_events(
+            config_name,
+            &gitlab_events,
+            &project_infos,
+        ));
+        events.append(&mut Self::gather_push_events(
             &gitlab_events,
             &project_infos,
         ));
+        events.append(&mut Self::gather_other_events(
+            config_name,
+            &gitlab_events,
+            &project_infos,
+        ));
+        if let Some(err) = gitlab_records.error {
+            events.push(rest_api::missing_events_event("Gitlab", &err));
+        }
         Ok(events)
     }
 }
+
+#[test]
+fn it_builds_events_for_pushes_and_other_actions() {
+    let gitlab_events: Vec<GitlabEvent> = serde_json::from_str(
+        r#"[{"project_id":1,"action_name":"pushed to","target_type":null,"target_title":null,
+             "created_at":"2020-03-23T10:00:00.000Z","target_iid":null,
+             "push_data":{"commit_count":2,"action":"pushed","ref_type":"branch",
+                          "commit_from":"aaa","commit_to":"bbb","ref":"master",
+                          "commit_title":"Fix the build"}},
+            {"project_id":1,"action_name":"approved","target_type":"MergeRequest",
+             "target_title":"Add export","created_at":"2020-03-23T11:00:00.000Z","target_iid":12},
+            {"project_id":1,"action_name":"joined","target_type":null,"target_title":null,
+             "created_at":"2020-03-23T12:00:00.000Z","target_iid":null}]"#,
+    )
+    .unwrap();
+    let project_infos = vec![(1, "https://gitlab.com/me/proj".to_string())]
+        .into_iter()
+        .collect();
+    let pushes = Gitlab::gather_push_events(&gitlab_events, &project_infos);
+    assert_eq!(1, pushes.len());
+    assert_eq!(
+        "Pushed 2 commit(s) to master",
+        pushes[0].event_contents_header
+    );
+    assert_eq!("Fix the build", pushes[0].event_info);
+    assert!(pushes[0]
+        .event_contents_body
+        .as_str()
+        .contains("https://gitlab.com/me/proj/compare/aaa...bbb"));
+
+    let others = Gitlab::gather_other_events("gitlab", &gitlab_events, &project_infos);
+    assert_eq!(2, others.len());
+    assert_eq!(Icon::THUMBS_UP, others[0].event_type_icon);
+    assert_eq!(
+        "Approved Merge Request #12: Add export",
+        others[0].event_contents_header
+    );
+    assert_eq!(
+        Some(LazyEventDetails::GitlabTarget {
+            config_name: "gitlab".to_string(),
+            project_id: 1,
+            target_type: "MergeRequest".to_string(),
+            iid: 12
+        }),
+        others[0].event_lazy_details
+    );
+    assert_eq!("Joined project", others[1].event_contents_header);
+    assert_eq!(None, others[1].event_lazy_details);
+}
+
+#[test]
+fn it_builds_events_for_discussion_comments() {
+    let gitlab_events: Vec<GitlabEvent> = serde_json::from_str(
+        r#"[{"project_id":1,"action_name":"commented on","target_type":"DiscussionNote",
+             "target_title":"Crash on startup","created_at":"2020-03-23T10:00:00.000Z",
+             "target_iid":5,"note":{"body":"Can't reproduce","type":"DiscussionNote",
+                                    "noteable_type":"Issue","noteable_iid":3}},
+            {"project_id":2,"action_name":"commented on","target_type":"Note",
+             "target_title":"Other project","created_at":"2020-03-23T11:00:00.000Z",
+             "target_iid":6,"note":{"body":"+1","type":null,
+                                    "noteable_type":"Issue","noteable_iid":4}}]"#,
+    )
+    .unwrap();
+    // we couldn't get the info of the second project
+    let project_infos = vec![(1, "https://gitlab.com/me/proj".to_string())]
+        .into_iter()
+        .collect();
+    let comments = Gitlab::gather_issue_comment_events("gitlab", &gitlab_events, &project_infos);
+    assert_eq!(1, comments.len());
+    assert_eq!(
+        "Issue #3 Comment: Crash on startup",
+        comments[0].event_contents_header
+    );
+    assert!(comments[0]
+        .event_contents_body
+        .as_str()
+        .contains("https://gitlab.com/me/proj/issues/3"));
+    assert!(Gitlab::gather_other_events("gitlab", &gitlab_events, &project_infos).is_empty());
+}
+
+#[test]
+fn it_builds_the_merge_request_details_markup() {
+    let target: GitlabTarget = serde_json::from_str(
+        r#"{"title":"Add <export>","description":"Exports to CSV","state":"opened",
+            "labels":["feature","ui"],"milestone":{"title":"1.2"},
+            "assignees":[{"name":"Jane"},{"name":"John"}],
+            "head_pipeline":{"status":"success"},"web_url":"https://gitlab.com/me/proj/merge_requests/12"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        "<b>Merge Request #12: Add &lt;export&gt;</b>\n\n\
+         <b>State</b>: opened\n<b>Pipeline</b>: success\n<b>Labels</b>: feature, ui\n\
+         <b>Milestone</b>: 1.2\n<b>Assignees</b>: Jane, John\n\nExports to CSV",
+        Gitlab::target_details_markup("Merge Request", 12, &target)
+    );
+}
@@ -0,0 +1,664 @@
+// works with jira cloud and jira data center/server. We use the v2 REST API,
+// which both support, and where comments are plain text (v3 uses a json document format).
+use super::events::{
+    format_duration, ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode,
+};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use core::time::Duration;
+use itertools::Itertools;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const PAGE_SIZE: usize = 50;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct JiraConfig {
+    pub jira_url: String,
+    // jira cloud: the account email, together with an API token.
+    // jira data center: leave empty and use a personal access token.
+    pub username: String,
+    pub api_token: String,
+}
+
+impl JiraConfig {
+    fn is_cloud(&self) -> bool {
+        self.jira_url
+            .trim_end_matches('/')
+            .ends_with(".atlassian.net")
+    }
+
+    fn get(
+        &self,
+        client: &reqwest::blocking::Client,
+        url: &str,
+    ) -> reqwest::blocking::RequestBuilder {
+        let request = client.get(url);
+        if self.username.is_empty() {
+            request.bearer_auth(&self.api_token)
+        } else {
+            request.basic_auth(&self.username, Some(&self.api_token))
+        }
+    }
+}
+
+pub struct Jira;
+const JIRA_URL_KEY: &str = "Jira URL";
+const USERNAME_KEY: &str = "Email (cloud only)";
+const API_TOKEN_KEY: &str = "API or personal access token";
+
+// jira dates look like 2020-03-23T10:00:00.000+0100, which is not rfc3339
+mod jira_date {
+    use chrono::prelude::*;
+    use serde::Deserialize;
+
+    pub fn parse(s: &str) -> Option<DateTime<Local>> {
+        DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f%z")
+            .ok()
+            .map(|d| d.with_timezone(&Local))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Local>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        parse(&s).ok_or_else(|| serde::de::Error::custom(format!("Invalid jira date: {}", s)))
+    }
+}
+
+// cloud identifies users by account id, data center by name & key
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JiraUser {
+    account_id: Option<String>,
+    name: Option<String>,
+    key: Option<String>,
+}
+
+impl JiraUser {
+    fn is_same_user(&self, other: &JiraUser) -> bool {
+        match (&self.account_id, &other.account_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => {
+                (self.key.is_some() && self.key == other.key)
+                    || (self.name.is_some() && self.name == other.name)
+            }
+        }
+    }
+}
+
+// data center pages with startAt & total, cloud with nextPageToken
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JiraSearchResult {
+    #[serde(default)]
+    issues: Vec<JiraIssue>,
+    total: Option<usize>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct JiraIssue {
+    key: String,
+    fields: JiraIssueFields,
+    changelog: Option<JiraChangelog>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct JiraIssueFields {
+    summary: String,
+    #[serde(with = "jira_date")]
+    created: DateTime<Local>,
+    creator: Option<JiraUser>,
+    comment: Option<JiraComments>,
+    worklog: Option<JiraWorklogs>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct JiraChangelog {
+    #[serde(default)]
+    histories: Vec<JiraHistory>,
+    #[serde(default)]
+    total: usize,
+}
+
+// the paged changelog from /issue/{key}/changelog
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JiraChangelogPage {
+    #[serde(default)]
+    values: Vec<JiraHistory>,
+    total: usize,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct JiraHistory {
+    author: Option<JiraUser>,
+    #[serde(with = "jira_date")]
+    created: DateTime<Local>,
+    #[serde(default)]
+    items: Vec<JiraHistoryItem>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JiraHistoryItem {
+    field: String,
+    from_string: Option<String>,
+    to_string: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JiraComments {
+    #[serde(default)]
+    comments: Vec<JiraComment>,
+    total: usize,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct JiraComment {
+    author: Option<JiraUser>,
+    body: String,
+    #[serde(with = "jira_date")]
+    created: DateTime<Local>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JiraWorklogs {
+    #[serde(default)]
+    worklogs: Vec<JiraWorklog>,
+    total: usize,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JiraWorklog {
+    author: Option<JiraUser>,
+    comment: Option<String>,
+    #[serde(with = "jira_date")]
+    started: DateTime<Local>,
+    time_spent_seconds: u64,
+}
+
+impl Jira {
+    fn issue_link(jira_config: &JiraConfig, issue: &JiraIssue) -> String {
+        format!(
+            "<a href=\"{}/browse/{}\">Open in browser</a>",
+            jira_config.jira_url.trim_end_matches('/'),
+            issue.key
+        )
+    }
+
+    fn build_issue_events(
+        jira_config: &JiraConfig,
+        myself: &JiraUser,
+        issue: &JiraIssue,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Vec<Event> {
+        let in_day = |d: &DateTime<Local>| *d >= day_start && *d < next_day_start;
+        let is_me = |u: &Option<JiraUser>| u.as_ref().filter(|u| u.is_same_user(myself)).is_some();
+        let link = Self::issue_link(jira_config, issue);
+        let event = |icon, time: &DateTime<Local>, header: String, body: &str, extra: &str| {
+            Event::new(
+                "Jira",
+                icon,
+                time.time(),
+                issue.fields.summary.clone(),
+                header,
+                EventBody::Markup(
+                    format!("{}\n\n{}", link, glib::markup_escape_text(body)),
+                    WordWrapMode::WordWrap,
+                ),
+                Some(extra.to_string()),
+            )
+        };
+        let mut events = vec![];
+        if in_day(&issue.fields.created) && is_me(&issue.fields.creator) {
+            events.push(event(
+                Icon::TASKS,
+                &issue.fields.created,
+                format!("Created {}: {}", issue.key, issue.fields.summary),
+                &issue.fields.summary,
+                "Issue created",
+            ));
+        }
+        let histories = issue
+            .changelog
+            .as_ref()
+            .map(|c| c.histories.as_slice())
+            .unwrap_or(&[]);
+        for history in histories
+            .iter()
+            .filter(|h| in_day(&h.created) && is_me(&h.author))
+        {
+            let (status_items, other_items): (Vec<_>, Vec<_>) =
+                history.items.iter().partition(|i| i.field == "status");
+            if let Some(status) = status_items.first() {
+                let transition = format!(
+                    "{} → {}",
+                    status.from_string.as_deref().unwrap_or(""),
+                    status.to_string.as_deref().unwrap_or("")
+                );
+                events.push(event(
+                    Icon::CHECK_SQUARE,
+                    &history.created,
+                    format!("{} {}: {}", issue.key, transition, issue.fields.summary),
+                    &format!("Status: {}", transition),
+                    &format!("Status {}", transition),
+                ));
+            }
+            if !other_items.is_empty() {
+                let changes = other_items
+                    .iter()
+                    .map(|i| {
+                        format!(
+                            "{}: {} → {}",
+                            i.field,
+                            i.from_string.as_deref().unwrap_or(""),
+                            i.to_string.as_deref().unwrap_or("")
+                        )
+                    })
+                    .join("\n");
+                events.push(event(
+                    Icon::EDIT,
+                    &history.created,
+                    format!("Edited {}: {}", issue.key, issue.fields.summary),
+                    &changes,
+                    &format!(
+                        "Edited {}",
+                        other_items.iter().map(|i| &i.field).unique().join(", ")
+                    ),
+                ));
+            }
+        }
+        let comments = issue
+            .fields
+            .comment
+            .as_ref()
+            .map(|c| c.comments.as_slice())
+            .unwrap_or(&[]);
+        for comment in comments
+            .iter()
+            .filter(|c| in_day(&c.created) && is_me(&c.author))
+        {
+            events.push(event(
+                Icon::COMMENT_DOTS,
+                &comment.created,
+                format!("Comment on {}: {}", issue.key, issue.fields.summary),
+                &comment.body,
+                "Comment",
+            ));
+        }
+        let worklogs = issue
+            .fields
+            .worklog
+            .as_ref()
+            .map(|w| w.worklogs.as_slice())
+            .unwrap_or(&[]);
+        for worklog in worklogs
+            .iter()
+            .filter(|w| in_day(&w.started) && is_me(&w.author))
+        {
+            let duration =
+                format_duration(chrono::Duration::seconds(worklog.time_spent_seconds as i64));
+            events.push(event(
+                Icon::CLOCK,
+                &worklog.started,
+                format!("{} on {}: {}", duration, issue.key, issue.fields.summary),
+                worklog.comment.as_deref().unwrap_or(""),
+                &format!("{} logged", duration),
+            ));
+        }
+        events
+    }
+
+    fn get_json<T>(
+        client: &reqwest::blocking::Client,
+        jira_config: &JiraConfig,
+        url: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let full_url = format!("{}{}", jira_config.jira_url.trim_end_matches('/'), url);
+        let json = jira_config
+            .get(client, &full_url)
+            .query(params)
+            .header("Accept", "application/json")
+            .send()?
+            .error_for_status()?
+            .text()?;
+        log::debug!("{}: {}", url, json);
+        Ok(serde_json::from_str::<T>(&json)
+            .map_err(|e| format!("Failed parsing json {} {:?} -- {}", url, e, json))?)
+    }
+
+    // the search embeds only the first comments & worklogs (and jira cloud
+    // caps the changelog at 100 entries), fetch them separately when they're truncated
+    fn complete_issue(
+        client: &reqwest::blocking::Client,
+        jira_config: &JiraConfig,
+        issue: &mut JiraIssue,
+    ) -> Result<()> {
+        if let Some(comment) = issue
+            .fields
+            .comment
+            .as_mut()
+            .filter(|c| c.total > c.comments.len())
+        {
+            *comment = Self::get_json(
+                client,
+                jira_config,
+                &format!("/rest/api/2/issue/{}/comment", issue.key),
+                &[("maxResults", &comment.total.to_string())],
+            )?;
+        }
+        if let Some(worklog) = issue
+            .fields
+            .worklog
+            .as_mut()
+            .filter(|w| w.total > w.worklogs.len())
+        {
+            *worklog = Self::get_json(
+                client,
+                jira_config,
+                &format!("/rest/api/2/issue/{}/worklog", issue.key),
+                &[("maxResults", &worklog.total.to_string())],
+            )?;
+        }
+        if let Some(changelog) = issue
+            .changelog
+            .as_mut()
+            .filter(|c| c.total > c.histories.len())
+        {
+            changelog.histories = Self::get_changelog(client, jira_config, &issue.key)?;
+        }
+        Ok(())
+    }
+
+    fn get_changelog(
+        client: &reqwest::blocking::Client,
+        jira_config: &JiraConfig,
+        issue_key: &str,
+    ) -> Result<Vec<JiraHistory>> {
+        let mut histories = vec![];
+        loop {
+            let mut page: JiraChangelogPage = Self::get_json(
+                client,
+                jira_config,
+                &format!("/rest/api/2/issue/{}/changelog", issue_key),
+                &[
+                    ("startAt", &histories.len().to_string()),
+                    ("maxResults", &PAGE_SIZE.to_string()),
+                ],
+            )?;
+            let page_size = page.values.len();
+            histories.append(&mut page.values);
+            if page_size == 0 || histories.len() >= page.total {
+                break;
+            }
+        }
+        Ok(histories)
+    }
+
+    // jira cloud removed /search in favor of /search/jql, which pages with
+    // a token. It's also available in the v2 API, with plain text comments.
+    fn search_issues(
+        client: &reqwest::blocking::Client,
+        jira_config: &JiraConfig,
+        jql: &str,
+    ) -> Result<Vec<JiraIssue>> {
+        let search_url = if jira_config.is_cloud() {
+            "/rest/api/2/search/jql"
+        } else {
+            "/rest/api/2/search"
+        };
+        let page_size_str = PAGE_SIZE.to_string();
+        let mut issues = vec![];
+        let mut next_page_token: Option<String> = None;
+        loop {
+            let start_at_str = issues.len().to_string();
+            let mut params = vec![
+                ("jql", jql),
+                ("fields", "summary,created,creator,comment,worklog"),
+                ("expand", "changelog"),
+                ("maxResults", &page_size_str),
+            ];
+            match &next_page_token {
+                Some(token) => params.push(("nextPageToken", token.as_str())),
+                None if !jira_config.is_cloud() => params.push(("startAt", &start_at_str)),
+                None => {}
+            }
+            let mut page: JiraSearchResult =
+                Self::get_json(client, jira_config, search_url, &params)?;
+            let page_size = page.issues.len();
+            issues.append(&mut page.issues);
+            next_page_token = page.next_page_token;
+            let is_done = match page.total {
+                Some(total) => issues.len() >= total,
+                None => next_page_token.is_none(),
+            };
+            if page_size == 0 || is_done {
+                break;
+            }
+        }
+        Ok(issues)
+    }
+
+    fn build_jql(jira_config: &JiraConfig, day: Date<Local>) -> String {
+        let (start, end) = (
+            day.format("%Y-%m-%d").to_string(),
+            day.succ().format("%Y-%m-%d").to_string(),
+        );
+        if jira_config.is_cloud() {
+            // updatedBy covers all the changes of the user: creating, editing,
+            // commenting, transitioning... but not logging work
+            format!(
+                "issue in updatedBy(currentUser(), \"{}\", \"{}\") OR (worklogAuthor = currentUser() AND worklogDate = \"{}\") ORDER BY updated DESC",
+                start, end, start
+            )
+        } else {
+            // data center doesn't have updatedBy, and updated is the date of the
+            // last change: we need all the issues updated since that day, then
+            // we filter the changes by date. The issues created after that day
+            // can't have changes on that day.
+            format!(
+                "updated >= \"{}\" AND created < \"{}\" AND (creator = currentUser() OR assignee was currentUser() OR watcher = currentUser() OR worklogAuthor = currentUser() OR status changed by currentUser() during (\"{}\", \"{}\")) ORDER BY updated DESC",
+                start, end, start, end
+            )
+        }
+    }
+}
+
+impl EventProvider for Jira {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (JIRA_URL_KEY, ConfigType::Text("")),
+            (USERNAME_KEY, ConfigType::Text("")),
+            (API_TOKEN_KEY, ConfigType::Password),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Jira"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::TASKS
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.jira.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        _field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (JIRA_URL_KEY, config.jira[config_name].jira_url.to_string()),
+            (USERNAME_KEY, config.jira[config_name].username.to_string()),
+            (
+                API_TOKEN_KEY,
+                config.jira[config_name].api_token.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.jira.insert(
+            config_name,
+            JiraConfig {
+                jira_url: config_values.remove(JIRA_URL_KEY).unwrap(),
+                username: config_values.remove(USERNAME_KEY).unwrap(),
+                api_token: config_values.remove(API_TOKEN_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.jira.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let jira_config = &config.jira[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let client = reqwest::blocking::ClientBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(30))
+            .connection_verbose(true)
+            .build()?;
+        let myself: JiraUser = Self::get_json(&client, jira_config, "/rest/api/2/myself", &[])?;
+        let mut issues =
+            Self::search_issues(&client, jira_config, &Self::build_jql(jira_config, day))?;
+        let mut events = vec![];
+        for issue in issues.iter_mut() {
+            Self::complete_issue(&client, jira_config, issue)?;
+            events.append(&mut Self::build_issue_events(
+                jira_config,
+                &myself,
+                issue,
+                day_start,
+                next_day_start,
+            ));
+        }
+        Ok(events)
+    }
+}
+
+#[test]
+fn it_parses_jira_dates() {
+    assert_eq!(
+        Some(
+            FixedOffset::east(3600)
+                .ymd(2020, 3, 23)
+                .and_hms_milli(10, 0, 0, 123)
+                .with_timezone(&Local)
+        ),
+        jira_date::parse("2020-03-23T10:00:00.123+0100")
+    );
+}
+
+#[test]
+fn it_builds_events_for_jira_issues() {
+    let jira_config = JiraConfig {
+        jira_url: "https://mycompany.atlassian.net/".to_string(),
+        username: "me@mycompany.com".to_string(),
+        api_token: "".to_string(),
+    };
+    let myself: JiraUser = serde_json::from_str(r#"{"accountId":"123"}"#).unwrap();
+    let issue: JiraIssue = serde_json::from_str(
+        r#"{"key":"PROJ-7","fields":{"summary":"Export to <csv>","created":"2020-03-20T09:00:00.000+0000",
+             "creator":{"accountId":"123"},
+             "comment":{"total":2,"comments":[
+               {"author":{"accountId":"123"},"body":"done & tested","created":"2020-03-23T11:00:00.000+0000"},
+               {"author":{"accountId":"456"},"body":"thanks","created":"2020-03-23T12:00:00.000+0000"}]},
+             "worklog":{"total":1,"worklogs":[
+               {"author":{"accountId":"123"},"comment":"coding","started":"2020-03-23T08:00:00.000+0000","timeSpentSeconds":5400}]}},
+           "changelog":{"histories":[
+             {"author":{"accountId":"123"},"created":"2020-03-23T10:00:00.000+0000","items":[
+               {"field":"status","fromString":"To Do","toString":"In Progress"},
+               {"field":"assignee","fromString":null,"toString":"Me"}]},
+             {"author":{"accountId":"123"},"created":"2020-03-22T10:00:00.000+0000","items":[
+               {"field":"summary","fromString":"Export","toString":"Export to csv"}]}]}}"#,
+    )
+    .unwrap();
+    let day_start = Utc.ymd(2020, 3, 23).and_hms(0, 0, 0).with_timezone(&Local);
+    let events = Jira::build_issue_events(
+        &jira_config,
+        &myself,
+        &issue,
+        day_start,
+        day_start + chrono::Duration::days(1),
+    );
+    assert_eq!(
+        vec![
+            "PROJ-7 To Do → In Progress: Export to <csv>",
+            "Edited PROJ-7: Export to <csv>",
+            "Comment on PROJ-7: Export to <csv>",
+            "1h 30m on PROJ-7: Export to <csv>",
+        ],
+        events
+            .iter()
+            .map(|e| e.event_contents_header.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert!(events[1]
+        .event_contents_body
+        .as_str()
+        .contains("assignee:  → Me"));
+    assert!(events[2]
+        .event_contents_body
+        .as_str()
+        .contains("https://mycompany.atlassian.net/browse/PROJ-7"));
+    assert!(events[2]
+        .event_contents_body
+        .as_str()
+        .contains("done &amp; tested"));
+}
+
+#[test]
+fn it_bounds_the_jql_search_to_the_day() {
+    let mut jira_config = JiraConfig {
+        jira_url: "https://mycompany.atlassian.net".to_string(),
+        username: "me@mycompany.com".to_string(),
+        api_token: "".to_string(),
+    };
+    assert_eq!(
+        "issue in updatedBy(currentUser(), \"2020-03-23\", \"2020-03-24\") \
+         OR (worklogAuthor = currentUser() AND worklogDate = \"2020-03-23\") ORDER BY updated DESC",
+        Jira::build_jql(&jira_config, Local.ymd(2020, 3, 23))
+    );
+    jira_config.jira_url = "https://jira.mycompany.com".to_string();
+    let jql = Jira::build_jql(&jira_config, Local.ymd(2020, 3, 23));
+    assert!(jql.starts_with("updated >= \"2020-03-23\" AND created < \"2020-03-24\" AND "));
+    assert!(jql.contains("status changed by currentUser() during (\"2020-03-23\", \"2020-03-24\")"));
+}
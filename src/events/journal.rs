@@ -0,0 +1,432 @@
+// work logs kept by hand: org-mode files (clock entries, CLOSED timestamps,
+// logbook state changes & notes), or markdown files named by date
+// (2020-03-23.md) with timestamped bullets ("- 09:30 standup")
+use super::events::{format_duration, ConfigType, Event, EventBody, EventProvider, Result};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(
+    serde_derive::Deserialize, serde_derive::Serialize, Clone, Copy, Debug, PartialEq, Default,
+)]
+pub enum JournalFormat {
+    #[default]
+    Org,
+    Markdown,
+}
+
+impl JournalFormat {
+    fn desc(self) -> &'static str {
+        match self {
+            JournalFormat::Org => "Org-mode",
+            JournalFormat::Markdown => "Markdown (one file per day)",
+        }
+    }
+
+    fn from_desc(desc: &str) -> JournalFormat {
+        if desc == JournalFormat::Markdown.desc() {
+            JournalFormat::Markdown
+        } else {
+            JournalFormat::Org
+        }
+    }
+
+    fn matches_file(self, file_name: &str, day: NaiveDate) -> bool {
+        match self {
+            JournalFormat::Org => file_name.ends_with(".org"),
+            JournalFormat::Markdown => file_name == format!("{}.md", day.format("%Y-%m-%d")),
+        }
+    }
+}
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct JournalConfig {
+    pub format: JournalFormat,
+    pub folder: String, // Path
+}
+
+pub struct Journal;
+const FORMAT_KEY: &str = "Format";
+const FOLDER_KEY: &str = "Journal folder";
+
+impl Journal {
+    fn find_files(folder: &Path, format: JournalFormat, day: NaiveDate) -> Result<Vec<PathBuf>> {
+        let mut result = vec![];
+        for entry in std::fs::read_dir(folder)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with('.') {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                result.append(&mut Self::find_files(&entry.path(), format, day)?);
+            } else if format.matches_file(&file_name, day) {
+                result.push(entry.path());
+            }
+        }
+        Ok(result)
+    }
+
+    fn parse_org_timestamp(caps: &regex::Captures, date_idx: usize) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(
+            &format!("{} {}", &caps[date_idx], &caps[date_idx + 1]),
+            "%Y-%m-%d %H:%M",
+        )
+        .ok()
+    }
+
+    fn parse_org(contents: &str, file_name: &str, day: NaiveDate) -> Vec<Event> {
+        let timestamp = r"[\[<](\d{4}-\d\d-\d\d)(?: [^\]> ]+)? (\d\d?:\d\d)[\]>]";
+        let heading_regex = Regex::new(
+            r"^\*+\s+(?:(?:TODO|DONE|NEXT|WAITING|CANCELLED)\s+)?(.*?)(?:\s+:[\w@:]+:)?\s*$",
+        )
+        .unwrap();
+        let clock_regex =
+            Regex::new(&format!(r"^\s*CLOCK:\s*{}(?:--{})?", timestamp, timestamp)).unwrap();
+        let closed_regex = Regex::new(&format!(r"CLOSED:\s*{}", timestamp)).unwrap();
+        let state_regex = Regex::new(&format!(
+            r#"^\s*- State\s+"([^"]+)"\s+(?:from\s+"([^"]*)"\s+)?{}"#,
+            timestamp
+        ))
+        .unwrap();
+        let note_regex = Regex::new(&format!(r"^\s*- Note taken on {}", timestamp)).unwrap();
+        let is_on_day = |d: &NaiveDateTime| d.date() == day;
+
+        let mut result = vec![];
+        let mut heading = String::new();
+        let mut lines = contents.lines().peekable();
+        while let Some(line) = lines.next() {
+            if let Some(caps) = heading_regex.captures(line) {
+                heading = caps[1].to_string();
+                continue;
+            }
+            let mut event = |icon, time: NaiveDateTime, extra: Option<String>, body: String| {
+                result.push(Event::new(
+                    "Org-mode",
+                    icon,
+                    time.time(),
+                    heading.clone(),
+                    heading.clone(),
+                    EventBody::PlainText(format!("{}\n{}", body, file_name)),
+                    extra,
+                ))
+            };
+            if let Some(caps) = clock_regex.captures(line) {
+                let start = match Self::parse_org_timestamp(&caps, 1) {
+                    Some(s) if is_on_day(&s) => s,
+                    _ => continue,
+                };
+                match caps
+                    .get(3)
+                    .and_then(|_| Self::parse_org_timestamp(&caps, 3))
+                {
+                    Some(end) => event(
+                        Icon::CLOCK,
+                        start,
+                        Some(format_duration(end - start)),
+                        format!(
+                            "Clocked from {} to {}",
+                            start.format("%H:%M"),
+                            end.format("%H:%M")
+                        ),
+                    ),
+                    None => event(
+                        Icon::CLOCK,
+                        start,
+                        Some("Clocked in".to_string()),
+                        format!("Clocked in at {}", start.format("%H:%M")),
+                    ),
+                }
+            } else if let Some(caps) = state_regex.captures(line) {
+                if let Some(time) = Self::parse_org_timestamp(&caps, 3).filter(is_on_day) {
+                    let desc = match caps.get(2).map(|m| m.as_str()) {
+                        Some(from) if !from.is_empty() => format!("{} → {}", from, &caps[1]),
+                        _ => caps[1].to_string(),
+                    };
+                    event(
+                        Icon::TASKS,
+                        time,
+                        Some(desc.clone()),
+                        format!("State: {}", desc),
+                    );
+                }
+            } else if let Some(caps) = note_regex.captures(line) {
+                if let Some(time) = Self::parse_org_timestamp(&caps, 1).filter(is_on_day) {
+                    // the note text is on the following, more indented lines
+                    let indent = |l: &str| l.len() - l.trim_start().len();
+                    let note_indent = indent(line);
+                    let mut note = vec![];
+                    while let Some(next) = lines.peek() {
+                        let trimmed = next.trim();
+                        if trimmed.is_empty() || indent(next) <= note_indent {
+                            break;
+                        }
+                        note.push(trimmed);
+                        lines.next();
+                    }
+                    event(Icon::EDIT, time, Some("Note".to_string()), note.join("\n"));
+                }
+            } else if let Some(caps) = closed_regex.captures(line) {
+                if let Some(time) = Self::parse_org_timestamp(&caps, 1).filter(is_on_day) {
+                    event(
+                        Icon::CHECK_SQUARE,
+                        time,
+                        Some("Closed".to_string()),
+                        "Closed".to_string(),
+                    );
+                }
+            }
+        }
+        result
+    }
+
+    // - 09:30 standup
+    // * **14:00** - code review
+    // - [16:45]: deploy
+    fn parse_markdown(contents: &str, file_name: &str) -> Vec<Event> {
+        let bullet_regex = Regex::new(
+            r"^(\s*)[-*+]\s+(?:\*\*|\[)?(\d{1,2}:\d\d)(?:\*\*|\])?\s*(?:[-:–]\s*)?(.+)$",
+        )
+        .unwrap();
+        let heading_regex = Regex::new(r"^#+\s+(.*?)\s*$").unwrap();
+        let mut result: Vec<(NaiveTime, String, String, Vec<String>)> = vec![];
+        let mut heading = file_name.to_string();
+        // the indentation of the last timestamped bullet, to attach sub-bullets to it
+        let mut cur_indent = None;
+        for line in contents.lines() {
+            if let Some(caps) = heading_regex.captures(line) {
+                heading = caps[1].to_string();
+                cur_indent = None;
+            } else if let Some((caps, time)) = bullet_regex.captures(line).and_then(|c| {
+                let time = NaiveTime::parse_from_str(&c[2], "%H:%M").ok()?;
+                Some((c, time))
+            }) {
+                cur_indent = Some(caps[1].len());
+                result.push((time, caps[3].to_string(), heading.clone(), vec![]));
+            } else if let (Some(indent), Some(last)) = (cur_indent, result.last_mut()) {
+                let line_indent = line.len() - line.trim_start().len();
+                if line.trim().is_empty() || line_indent <= indent {
+                    cur_indent = None;
+                } else {
+                    last.3.push(line.trim().to_string());
+                }
+            }
+        }
+        result
+            .into_iter()
+            .map(|(time, text, heading, details)| {
+                Event::new(
+                    "Journal",
+                    Icon::EDIT,
+                    time,
+                    text.clone(),
+                    heading,
+                    EventBody::PlainText(
+                        std::iter::once(text)
+                            .chain(details)
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    ),
+                    None,
+                )
+            })
+            .collect()
+    }
+}
+
+impl EventProvider for Journal {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (FORMAT_KEY, ConfigType::Combo),
+            (FOLDER_KEY, ConfigType::Folder),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Journal"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::EDIT
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.journal.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        if field_name == FORMAT_KEY {
+            Ok(vec![
+                JournalFormat::Org.desc().to_string(),
+                JournalFormat::Markdown.desc().to_string(),
+            ])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                FORMAT_KEY,
+                config.journal[config_name].format.desc().to_string(),
+            ),
+            (FOLDER_KEY, config.journal[config_name].folder.to_string()),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.journal.insert(
+            config_name,
+            JournalConfig {
+                format: JournalFormat::from_desc(
+                    &config_values.remove(FORMAT_KEY).unwrap_or_default(),
+                ),
+                folder: config_values.remove(FOLDER_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.journal.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let journal_config = &config.journal[config_name];
+        let day = day.naive_local();
+        let mut result = vec![];
+        for path in Self::find_files(
+            Path::new(&journal_config.folder),
+            journal_config.format,
+            day,
+        )? {
+            let contents = std::fs::read_to_string(&path)?;
+            let file_name = path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            result.append(&mut match journal_config.format {
+                JournalFormat::Org => Self::parse_org(&contents, &file_name, day),
+                JournalFormat::Markdown => Self::parse_markdown(&contents, &file_name),
+            });
+        }
+        Ok(result)
+    }
+}
+
+#[test]
+fn it_parses_org_clock_entries_and_state_changes() {
+    let events = Journal::parse_org(
+        r#"* Projects
+** DONE Release 0.6                                          :cigale:release:
+   CLOSED: [2020-03-23 Mon 16:10]
+   :LOGBOOK:
+   - State "DONE"       from "TODO"       [2020-03-23 Mon 16:10]
+   - Note taken on [2020-03-23 Mon 15:02] \\
+     the flatpak build needs a new runtime
+   CLOCK: [2020-03-23 Mon 14:00]--[2020-03-23 Mon 15:45] =>  1:45
+   CLOCK: [2020-03-20 Fri 09:00]--[2020-03-20 Fri 10:00] =>  1:00
+   :END:
+** TODO Write the docs
+   :LOGBOOK:
+   CLOCK: [2020-03-23 Mon 17:00]
+   :END:
+"#,
+        "work.org",
+        NaiveDate::from_ymd(2020, 3, 23),
+    );
+    assert_eq!(
+        vec![
+            (
+                NaiveTime::from_hms(16, 10, 0),
+                "Release 0.6",
+                Some("Closed")
+            ),
+            (
+                NaiveTime::from_hms(16, 10, 0),
+                "Release 0.6",
+                Some("TODO → DONE")
+            ),
+            (NaiveTime::from_hms(15, 2, 0), "Release 0.6", Some("Note")),
+            (NaiveTime::from_hms(14, 0, 0), "Release 0.6", Some("1h 45m")),
+            (
+                NaiveTime::from_hms(17, 0, 0),
+                "Write the docs",
+                Some("Clocked in")
+            ),
+        ],
+        events
+            .iter()
+            .map(|e| (
+                e.event_time,
+                e.event_info.as_str(),
+                e.event_extra_details.as_deref()
+            ))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        EventBody::PlainText("the flatpak build needs a new runtime\nwork.org".to_string()),
+        events[2].event_contents_body
+    );
+}
+
+#[test]
+fn it_parses_markdown_journal_bullets() {
+    let events = Journal::parse_markdown(
+        "# Monday
+
+- 09:30 standup
+- **10:00** - code review
+  - the parser refactoring
+  - two comments left
+- a bullet without time
+## Afternoon
+* [16:45]: deploy
+",
+        "2020-03-23.md",
+    );
+    assert_eq!(
+        vec![
+            (NaiveTime::from_hms(9, 30, 0), "standup", "Monday"),
+            (NaiveTime::from_hms(10, 0, 0), "code review", "Monday"),
+            (NaiveTime::from_hms(16, 45, 0), "deploy", "Afternoon"),
+        ],
+        events
+            .iter()
+            .map(|e| (
+                e.event_time,
+                e.event_info.as_str(),
+                e.event_contents_header.as_str()
+            ))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        EventBody::PlainText(
+            "code review\n- the parser refactoring\n- two comments left".to_string()
+        ),
+        events[1].event_contents_body
+    );
+}
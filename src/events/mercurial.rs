@@ -0,0 +1,227 @@
+// the events are shaped like the git ones, but we go through the
+// hg command-line since there is no library for mercurial.
+use super::events::{
+    run_command, ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode,
+};
+use super::git::Git;
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct MercurialConfig {
+    pub repo_folder: String, // Path
+    pub commit_author: String,
+}
+
+#[derive(serde_derive::Deserialize, Debug)]
+struct HgCommit {
+    node: String,
+    branch: String,
+    // unix timestamp & timezone offset
+    date: (f64, i64),
+    p2node: String,
+    files: Vec<String>,
+    desc: String,
+}
+
+impl HgCommit {
+    fn is_merge(&self) -> bool {
+        self.p2node.chars().any(|c| c != '0')
+    }
+
+    fn summary(&self) -> &str {
+        self.desc.lines().next().unwrap_or("")
+    }
+}
+
+pub struct Mercurial;
+const REPO_FOLDER_KEY: &str = "Repository folder";
+const COMMIT_AUTHOR_KEY: &str = "Commit Author";
+
+impl Mercurial {
+    fn hg_command(repo_folder: &str) -> Command {
+        let mut command = Command::new("hg");
+        // HGPLAIN disables the user's aliases & output customizations
+        command
+            .env("HGPLAIN", "1")
+            .args(["--repository", repo_folder, "--encoding", "utf-8"]);
+        command
+    }
+
+    fn parse_log(output: &str) -> Result<Vec<HgCommit>> {
+        output
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| Ok(serde_json::from_str(l)?))
+            .collect()
+    }
+
+    fn build_event(commit: &HgCommit, diffstat: Option<&str>) -> Event {
+        let commit_date = Local.timestamp(commit.date.0 as i64, 0);
+        let contents_header = commit.summary().to_string();
+        let message_contents = glib::markup_escape_text(
+            commit
+                .desc
+                .strip_prefix(&contents_header)
+                .unwrap_or(&commit.desc),
+        )
+        .to_string();
+        let contents = format!(
+            "{}\n<span font-family=\"monospace\">{}\n\n{}</span>",
+            glib::markup_escape_text(&commit.branch),
+            message_contents,
+            glib::markup_escape_text(diffstat.unwrap_or(""))
+        );
+        let files: Vec<_> = commit.files.iter().map(PathBuf::from).collect();
+        Event::new(
+            "Mercurial",
+            if commit.is_merge() {
+                Icon::PROJECT_DIAGRAM
+            } else {
+                Icon::CODE_BRANCH
+            },
+            commit_date.time(),
+            contents_header.clone(),
+            contents_header,
+            EventBody::Markup(contents, WordWrapMode::NoWordWrap),
+            Some(Git::get_files_root(&files)).filter(|r| !r.is_empty()),
+        )
+    }
+}
+
+impl EventProvider for Mercurial {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (REPO_FOLDER_KEY, ConfigType::Folder),
+            (COMMIT_AUTHOR_KEY, ConfigType::Text("")),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Mercurial"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::CODE_BRANCH
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.mercurial.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        _field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                REPO_FOLDER_KEY,
+                config.mercurial[config_name].repo_folder.to_string(),
+            ),
+            (
+                COMMIT_AUTHOR_KEY,
+                config.mercurial[config_name].commit_author.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.mercurial.insert(
+            config_name,
+            MercurialConfig {
+                repo_folder: config_values.remove(REPO_FOLDER_KEY).unwrap(),
+                commit_author: config_values.remove(COMMIT_AUTHOR_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.mercurial.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let hg_config = &config.mercurial[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let mut command = Self::hg_command(&hg_config.repo_folder);
+        command.args([
+            "log",
+            "--date",
+            &format!(
+                "{} to {}",
+                day_start.format("%Y-%m-%d"),
+                next_day_start.format("%Y-%m-%d")
+            ),
+            "--template",
+            "{dict(node, branch, date, p2node, files, desc)|json}\\n",
+        ]);
+        if !hg_config.commit_author.is_empty() {
+            command.args(["--user", &hg_config.commit_author]);
+        }
+        // hg matches the date range in the timezone of each commit
+        Ok(Self::parse_log(&run_command(&mut command)?)?
+            .iter()
+            .filter(|c| {
+                let t = c.date.0 as i64;
+                t >= day_start.timestamp() && t < next_day_start.timestamp()
+            })
+            .map(|c| {
+                let diffstat = run_command(
+                    Self::hg_command(&hg_config.repo_folder)
+                        .args(["diff", "--stat", "--change", &c.node]),
+                )
+                .ok();
+                Self::build_event(c, diffstat.as_deref())
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn it_parses_hg_log_output() {
+    let commits = Mercurial::parse_log(
+        r#"{"author": "Me <me@example.com>", "branch": "default", "date": [1584950400.0, -3600], "desc": "fix the parser\n\ncrashed on empty files", "files": ["src/parse/lexer.c", "src/parse/parser.c"], "node": "2f1e0d8c9a0b7e6f5d4c3b2a1f0e9d8c7b6a5f4e", "p2node": "0000000000000000000000000000000000000000"}
+{"author": "Me <me@example.com>", "branch": "stable", "date": [1584954000.0, -3600], "desc": "merge default", "files": [], "node": "9a0b7e6f5d4c3b2a1f0e9d8c7b6a5f4e2f1e0d8c", "p2node": "2f1e0d8c9a0b7e6f5d4c3b2a1f0e9d8c7b6a5f4e"}
+"#,
+    )
+    .unwrap();
+    assert_eq!(2, commits.len());
+    assert!(!commits[0].is_merge());
+    assert!(commits[1].is_merge());
+    let event = Mercurial::build_event(
+        &commits[0],
+        Some(" src/parse/lexer.c  |  2 +-\n 1 files changed, 1 insertions(+), 1 deletions(-)"),
+    );
+    assert_eq!("fix the parser", event.event_info);
+    assert_eq!(Some("src/parse".to_string()), event.event_extra_details);
+    assert_eq!(Local.timestamp(1584950400, 0).time(), event.event_time);
+    assert!(matches!(
+        event.event_contents_body,
+        EventBody::Markup(ref s, _) if s.starts_with("default\n") && s.contains("crashed on empty files") && s.contains("1 files changed")
+    ));
+}
@@ -1,7 +1,25 @@
+pub mod browser_history;
+pub mod custom_command;
+pub mod desktop_session;
 pub mod email;
 pub mod events;
+pub mod file_modifications;
 pub mod git;
+pub mod git_diff;
+pub mod git_index;
+pub mod gitea;
+pub mod github;
 pub mod gitlab;
 pub mod ical;
+pub mod jira;
+pub mod journal;
+pub mod mercurial;
 pub mod redmine;
+pub mod redmine_rest;
+pub mod rest_api;
+pub mod shell_history;
 pub mod stackexchange;
+pub mod svn;
+#[cfg(test)]
+pub mod test_repo;
+pub mod timewarrior;
@@ -1,29 +1,222 @@
-// not using the redmine Rest api because
+// by default we're not using the redmine Rest api because
 // 1. unless the redmine admin greenlights it, a user may be unable to get an apikey
 // 2. the redmine rest api doesn't offer an activity API https://www.redmine.org/issues/14872
 //    without such an API, this would be very painful and very slow
+// but if the user has an API key, the REST API is the only way to get
+// the time entries, so we offer it as an alternative mode, see redmine_rest.rs
 use super::events::{ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode};
+use super::redmine_rest;
 use crate::config::Config;
 use crate::icons::*;
 use chrono::prelude::*;
 use core::time::Duration;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::sync::Arc;
+
+#[derive(
+    serde_derive::Deserialize, serde_derive::Serialize, Clone, Copy, Debug, PartialEq, Default,
+)]
+pub enum RedmineMode {
+    #[default]
+    ActivityScraping,
+    RestApi,
+}
+
+impl RedmineMode {
+    fn desc(self) -> &'static str {
+        match self {
+            RedmineMode::ActivityScraping => "Activity page (username & password)",
+            RedmineMode::RestApi => "REST API (API key)",
+        }
+    }
+
+    fn from_desc(desc: &str) -> RedmineMode {
+        if desc == RedmineMode::RestApi.desc() {
+            RedmineMode::RestApi
+        } else {
+            RedmineMode::ActivityScraping
+        }
+    }
+}
 
 #[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
 pub struct RedmineConfig {
     pub server_url: String,
     pub username: String,
     pub password: String,
+    #[serde(default)] // mode was added later, after 0.5.4
+    pub mode: RedmineMode,
+    #[serde(default)] // api_key was added later, after 0.5.4
+    pub api_key: String,
+    // the fields below are for instances where the login form
+    // doesn't work (SSO, proxies...) or with custom certificates.
+    #[serde(default)] // session_cookie was added later, after 0.5.4
+    pub session_cookie: String,
+    #[serde(default)] // basic_auth was added later, after 0.5.4
+    pub basic_auth: bool,
+    #[serde(default)] // ca_bundle_path was added later, after 0.5.4
+    pub ca_bundle_path: String,
+    #[serde(default)] // accept_invalid_certs was added later, after 0.5.4
+    pub accept_invalid_certs: bool,
+}
+
+impl RedmineConfig {
+    pub fn http_client(&self) -> Result<reqwest::blocking::Client> {
+        let mut builder = reqwest::blocking::ClientBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(30))
+            .connection_verbose(true)
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        if !self.ca_bundle_path.is_empty() {
+            let pem = std::fs::read(&self.ca_bundle_path)?;
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+        }
+        // the cookie jar keeps the session cookie we get from the login form,
+        // or the session cookie that the user copied from the browser
+        let jar = reqwest::cookie::Jar::default();
+        if !self.session_cookie.is_empty() {
+            jar.add_cookie_str(
+                &Self::session_cookie_str(&self.session_cookie),
+                &self.server_url.parse()?,
+            );
+        }
+        Ok(builder.cookie_provider(Arc::new(jar)).build()?)
+    }
+
+    // users may paste either the cookie value or name=value
+    fn session_cookie_str(session_cookie: &str) -> String {
+        let session_cookie = session_cookie.trim();
+        if session_cookie.contains('=') {
+            session_cookie.to_string()
+        } else {
+            format!("_redmine_session={}", session_cookie)
+        }
+    }
+
+    /// a GET request with the authentication headers for this redmine
+    pub fn get(
+        &self,
+        client: &reqwest::blocking::Client,
+        url: &str,
+    ) -> reqwest::blocking::RequestBuilder {
+        let mut request = client.get(url);
+        if !self.api_key.is_empty() {
+            request = request.header("X-Redmine-API-Key", &self.api_key);
+        }
+        if self.basic_auth {
+            request = request.basic_auth(&self.username, Some(&self.password));
+        }
+        request
+    }
+
+    // redmine only honors the API key & basic auth on the .json/.xml
+    // API requests, the HTML pages need a session. The basic auth may
+    // still be needed for a proxy in front of redmine.
+    fn uses_login_form(&self) -> bool {
+        self.session_cookie.is_empty()
+    }
 }
 
 pub struct Redmine;
 const SERVER_URL_KEY: &str = "Server URL";
+const MODE_KEY: &str = "Mode";
 const USERNAME_KEY: &str = "Username";
 const PASSWORD_KEY: &str = "Password";
+const API_KEY_KEY: &str = "API key";
+const SESSION_COOKIE_KEY: &str = "Session cookie (instead of the login form)";
+const BASIC_AUTH_KEY: &str = "Use HTTP basic authentication";
+const CA_BUNDLE_KEY: &str = "CA certificates bundle";
+const ACCEPT_INVALID_CERTS_KEY: &str = "Accept invalid certificates";
+
+struct ActivityPage {
+    days: Vec<(NaiveDate, Vec<RedmineActivity>)>,
+    // first day covered by the page, None if there's no previous page
+    range_start: Option<NaiveDate>,
+}
+
+// we persist the parsed activity per day, so that we don't fetch the
+// activity page again and again when browsing through past days
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Default, Debug)]
+struct ActivityCache {
+    days: HashMap<NaiveDate, CachedDay>,
+}
 
-enum ActivityData {
-    Done(Vec<Event>),
-    ReachedEndOfPage(Option<String>), // link to the previous page or None if no previous
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug)]
+struct CachedDay {
+    fetched_at: DateTime<Local>,
+    activities: Vec<RedmineActivity>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+enum ActivityKind {
+    IssueCreated,
+    IssueEdit,
+    IssueClosed,
+    Changeset,
+    WikiPage,
+    News,
+    Document,
+    TimeEntry,
+    Other,
+}
+
+impl ActivityKind {
+    fn desc(self) -> &'static str {
+        match self {
+            ActivityKind::IssueCreated => "Issue created",
+            ActivityKind::IssueEdit => "Issue updated",
+            ActivityKind::IssueClosed => "Issue closed",
+            ActivityKind::Changeset => "Changeset",
+            ActivityKind::WikiPage => "Wiki edit",
+            ActivityKind::News => "News",
+            ActivityKind::Document => "Document",
+            ActivityKind::TimeEntry => "Time logged",
+            ActivityKind::Other => "Activity",
+        }
+    }
+
+    fn icon(self) -> Icon {
+        match self {
+            ActivityKind::IssueCreated => Icon::TASKS,
+            ActivityKind::IssueEdit => Icon::EDIT,
+            ActivityKind::IssueClosed => Icon::CHECK_SQUARE,
+            ActivityKind::Changeset => Icon::CODE_BRANCH,
+            ActivityKind::WikiPage => Icon::BOOK,
+            ActivityKind::News => Icon::NEWSPAPER,
+            ActivityKind::Document => Icon::FILE_ALT,
+            ActivityKind::TimeEntry => Icon::CLOCK,
+            ActivityKind::Other => Icon::TASKS,
+        }
+    }
+
+    fn is_issue(self) -> bool {
+        matches!(
+            self,
+            ActivityKind::IssueCreated | ActivityKind::IssueEdit | ActivityKind::IssueClosed
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde_derive::Deserialize, serde_derive::Serialize)]
+struct IssueInfo {
+    tracker: String,
+    issue_number: usize,
+    // the status after the change, the activity page doesn't give the previous one
+    status: Option<String>,
+    subject: String,
+}
+
+#[derive(Clone, Debug, serde_derive::Deserialize, serde_derive::Serialize)]
+struct RedmineActivity {
+    kind: ActivityKind,
+    time: NaiveTime,
+    project: Option<String>,
+    issue: Option<IssueInfo>,
+    title: String,
+    href: String,
+    description: String,
 }
 
 #[derive(Debug)]
@@ -78,127 +271,188 @@ impl Redmine {
     }
 
     fn redmine_locales() -> HashMap<&'static str, LocaleInfo> {
-        // the contents of this function are generated by the helpers/redmine_locales helper app
-        vec![
-            ("lv", LocaleInfo::new("%d.%m.%Y", "šodien")),
-            ("th", LocaleInfo::new("%Y-%m-%d", "วันนี้")),
-            ("zh", LocaleInfo::new("%Y-%m-%d", "今天")),
-            ("da", LocaleInfo::new("%d.%m.%Y", "i dag")),
-            ("pt", LocaleInfo::new("%d/%m/%Y", "hoje")),
-            ("ja", LocaleInfo::new("%Y/%m/%d", "今日")),
-            ("pl", LocaleInfo::new("%Y-%m-%d", "dzisiaj")),
-            ("lt", LocaleInfo::new("%m/%d/%Y", "šiandien")),
-            ("fa", LocaleInfo::new("%Y/%m/%d", "امروز")),
-            ("gl", LocaleInfo::new("%e/%m/%Y", "hoxe")),
-            ("uk", LocaleInfo::new("%Y-%m-%d", "сьогодні")),
-            ("vi", LocaleInfo::new("%d-%m-%Y", "hôm nay")),
-            ("mn", LocaleInfo::new("%Y/%m/%d", "өнөөдөр")),
-            ("cs", LocaleInfo::new("%Y-%m-%d", "dnes")),
-            ("en-GB", LocaleInfo::new("%d/%m/%Y", "today")),
-            ("fr", LocaleInfo::new("%d/%m/%Y", "aujourd'hui")),
-            ("sr", LocaleInfo::new("%d.%m.%Y.", "данас")),
-            ("fi", LocaleInfo::new("%e. %Bta %Y", "tänään")),
-            ("no", LocaleInfo::new("%d.%m.%Y", "idag")),
-            ("mk", LocaleInfo::new("%d/%m/%Y", "денес")),
-            ("hu", LocaleInfo::new("%Y.%m.%d.", "ma")),
-            ("ro", LocaleInfo::new("%d-%m-%Y", "astăzi")),
-            ("it", LocaleInfo::new("%d-%m-%Y", "oggi")),
-            ("he", LocaleInfo::new("%d/%m/%Y", "היום")),
-            ("es", LocaleInfo::new("%Y-%m-%d", "hoy")),
-            ("en", LocaleInfo::new("%m/%d/%Y", "today")),
-            ("sq", LocaleInfo::new("%m/%d/%Y", "sot")),
-            ("eu", LocaleInfo::new("%Y/%m/%d", "gaur")),
-            ("id", LocaleInfo::new("%d-%m-%Y", "hari ini")),
-            ("de", LocaleInfo::new("%d.%m.%Y", "heute")),
-            ("bg", LocaleInfo::new("%d-%m-%Y", "днес")),
-            ("sv", LocaleInfo::new("%Y-%m-%d", "idag")),
-            ("sk", LocaleInfo::new("%Y-%m-%d", "dnes")),
-            ("ko", LocaleInfo::new("%Y/%m/%d", "오늘")),
-            ("et", LocaleInfo::new("%d.%m.%Y", "täna")),
-            ("hr", LocaleInfo::new("%m/%d/%Y", "danas")),
-            ("el", LocaleInfo::new("%m/%d/%Y", "σήμερα")),
-            ("zh-TW", LocaleInfo::new("%Y-%m-%d", "今天")),
-            ("sr-YU", LocaleInfo::new("%d.%m.%Y.", "danas")),
-            ("bs", LocaleInfo::new("%d.%m.%Y", "danas")),
-            ("tr", LocaleInfo::new("%d.%m.%Y", "bugün")),
-            ("ru", LocaleInfo::new("%d.%m.%Y", "сегодня")),
-            ("es-PA", LocaleInfo::new("%Y-%m-%d", "hoy")),
-            ("ar", LocaleInfo::new("%m/%d/%Y", "اليوم")),
-            ("sl", LocaleInfo::new("%d.%m.%Y", "danes")),
-            ("az", LocaleInfo::new("%d.%m.%Y", "bu gün")),
-            ("ca", LocaleInfo::new("%d-%m-%Y", "avui")),
-            ("pt-BR", LocaleInfo::new("%d/%m/%Y", "hoje")),
-            ("nl", LocaleInfo::new("%d-%m-%Y", "vandaag")),
-        ]
-        .into_iter()
-        .collect()
+        // generated by build.rs from resources/redmine_locales
+        let locales: Vec<(&'static str, LocaleInfo)> =
+            include!(concat!(env!("OUT_DIR"), "/redmine_locales.rs"));
+        locales.into_iter().collect()
     }
 
-    fn parse_events<'a>(
-        redmine_config: &RedmineConfig,
-        contents_elt: &scraper::element_ref::ElementRef<'a>,
-    ) -> Result<Vec<Event>> {
-        let description_sel = scraper::Selector::parse("span.description").unwrap();
-        let link_sel = scraper::Selector::parse("dt.icon a").unwrap();
+    // the css classes of the <dt> tell us which kind of activity it is
+    fn parse_activity_kind(dt_classes: &[&str]) -> ActivityKind {
+        let kinds = [
+            ("issue-closed", ActivityKind::IssueClosed),
+            ("issue-edit", ActivityKind::IssueEdit),
+            ("issue-note", ActivityKind::IssueEdit),
+            ("issue", ActivityKind::IssueCreated),
+            ("changeset", ActivityKind::Changeset),
+            ("wiki-page", ActivityKind::WikiPage),
+            ("news", ActivityKind::News),
+            ("document", ActivityKind::Document),
+            ("time-entry", ActivityKind::TimeEntry),
+        ];
+        kinds
+            .iter()
+            .find(|(class, _)| dt_classes.contains(class))
+            .map(|(_, kind)| *kind)
+            .unwrap_or(ActivityKind::Other)
+    }
+
+    // Bug #123 (Resolved): Crash on startup
+    fn parse_issue_title(title: &str) -> Option<IssueInfo> {
+        let issue_title_regex = regex::Regex::new(
+            r"^(?P<tracker>.+?) #(?P<id>\d+)(?: \((?P<status>[^)]+)\))?: (?P<subject>.*)$",
+        )
+        .unwrap();
+        let caps = issue_title_regex.captures(title)?;
+        Some(IssueInfo {
+            tracker: caps["tracker"].to_string(),
+            issue_number: caps["id"].parse().ok()?,
+            status: caps.name("status").map(|s| s.as_str().to_string()),
+            subject: caps["subject"].to_string(),
+        })
+    }
+
+    fn parse_activity(
+        dt_elt: &scraper::element_ref::ElementRef,
+        dd_elt: Option<&scraper::element_ref::ElementRef>,
+    ) -> Result<RedmineActivity> {
         let time_sel = scraper::Selector::parse("span.time").unwrap();
-        let mut it_descriptions = contents_elt.select(&description_sel);
-        let mut it_links = contents_elt.select(&link_sel);
-        let mut it_times = contents_elt.select(&time_sel);
-        let mut day_has_data = true;
-        let mut result = vec![];
-        while day_has_data {
-            let next_time = it_times.next();
-            day_has_data = next_time.is_some();
-            if day_has_data {
-                let time_elt = &next_time.unwrap();
-                let time_str = time_elt.inner_html();
-                let time = Self::parse_time(&time_str)?;
-                let description_elt = &it_descriptions
-                    .next()
-                    .ok_or("Redmine event: no description?")?;
-                let link_elt = &it_links.next().ok_or("Redmine event: no link?")?;
-                result.push(Event::new(
-                    "Redmine",
-                    Icon::TASKS,
-                    time,
-                    link_elt.inner_html(),
-                    link_elt.inner_html(),
-                    EventBody::Markup(
-                        format!(
-                            "<a href=\"{}{}\">Open in the browser</a>\n{}",
-                            redmine_config.server_url,
-                            link_elt.value().attr("href").unwrap_or(""),
-                            glib::markup_escape_text(&description_elt.inner_html()),
-                        ),
-                        WordWrapMode::WordWrap,
-                    ),
-                    None,
-                ));
+        let project_sel = scraper::Selector::parse("span.project").unwrap();
+        let description_sel = scraper::Selector::parse("span.description").unwrap();
+        let time_str = dt_elt
+            .select(&time_sel)
+            .next()
+            .ok_or("Redmine event: no time?")?
+            .inner_html();
+        // the link is a direct child of the dt, the gravatar and project
+        // may also contain links
+        let link_elt = dt_elt
+            .children()
+            .filter_map(scraper::element_ref::ElementRef::wrap)
+            .find(|e| e.value().name() == "a")
+            .ok_or("Redmine event: no link?")?;
+        let title = link_elt.text().collect::<String>();
+        let dt_classes: Vec<&str> = dt_elt.value().classes().collect();
+        let kind = Self::parse_activity_kind(&dt_classes);
+        Ok(RedmineActivity {
+            kind,
+            time: Self::parse_time(&time_str)?,
+            project: dt_elt
+                .select(&project_sel)
+                .next()
+                .map(|p| p.text().collect::<String>().trim().to_string()),
+            // changeset messages often mention issues: "Revision 12 (cigale): fix #3: crash"
+            issue: if kind.is_issue() {
+                Self::parse_issue_title(&title)
+            } else {
+                None
+            },
+            title,
+            href: link_elt.value().attr("href").unwrap_or("").to_string(),
+            description: dd_elt
+                .and_then(|dd| dd.select(&description_sel).next())
+                .map(|d| d.text().collect::<String>())
+                .unwrap_or_default(),
+        })
+    }
+
+    fn build_event(redmine_config: &RedmineConfig, activity: RedmineActivity) -> Event {
+        let (header, info) = match &activity.issue {
+            Some(issue) => (
+                format!(
+                    "{} #{}: {}",
+                    issue.tracker, issue.issue_number, issue.subject
+                ),
+                issue.subject.clone(),
+            ),
+            None => (activity.title.clone(), activity.title.clone()),
+        };
+        let extra_details = match activity.issue.as_ref().and_then(|i| i.status.as_ref()) {
+            Some(status) if activity.kind != ActivityKind::Other => {
+                format!("{} → {}", activity.kind.desc(), status)
             }
+            _ => activity.kind.desc().to_string(),
+        };
+        let mut body = format!(
+            "<a href=\"{}{}\">Open in the browser</a>\n",
+            redmine_config.server_url, activity.href,
+        );
+        if let Some(project) = &activity.project {
+            body.push_str(&format!(
+                "<b>Project:</b> {}\n",
+                glib::markup_escape_text(project)
+            ));
+        }
+        if let Some(status) = activity.issue.as_ref().and_then(|i| i.status.as_ref()) {
+            body.push_str(&format!(
+                "<b>Status:</b> {}\n",
+                glib::markup_escape_text(status)
+            ));
         }
-        Ok(result)
+        body.push_str(&glib::markup_escape_text(&activity.description));
+        Event::new(
+            "Redmine",
+            activity.kind.icon(),
+            activity.time,
+            info,
+            header,
+            EventBody::Markup(body, WordWrapMode::WordWrap),
+            Some(extra_details),
+        )
+    }
+
+    fn parse_activities<'a>(
+        contents_elt: &scraper::element_ref::ElementRef<'a>,
+    ) -> Result<Vec<RedmineActivity>> {
+        let dt_sel = scraper::Selector::parse("dt").unwrap();
+        let dd_sel = scraper::Selector::parse("dd").unwrap();
+        let dds: Vec<_> = contents_elt.select(&dd_sel).collect();
+        contents_elt
+            .select(&dt_sel)
+            .enumerate()
+            .map(|(idx, dt_elt)| Self::parse_activity(&dt_elt, dds.get(idx)))
+            .collect()
     }
 
     fn init_client(redmine_config: &RedmineConfig) -> Result<(reqwest::blocking::Client, String)> {
-        let client = reqwest::blocking::ClientBuilder::new()
-            .cookie_store(true)
-            .timeout(Duration::from_secs(30))
-            .connect_timeout(Duration::from_secs(30))
-            .connection_verbose(true)
-            .build()?;
+        let client = redmine_config.http_client()?;
 
-        let html = client
-            .get(&redmine_config.server_url)
+        let html = redmine_config
+            .get(&client, &redmine_config.server_url)
             .send()?
             .error_for_status()?
             .text()?;
         log::debug!("Got back html {}", html);
+        let html = if redmine_config.uses_login_form() {
+            Self::login(redmine_config, &client, &html)?
+        } else {
+            // we should be logged in already
+            html
+        };
         let doc = scraper::Html::parse_document(&html);
+        let user_sel = scraper::Selector::parse("a.user.active").unwrap();
+        let user_id = doc
+            .select(&user_sel)
+            .next()
+            .ok_or("Failed getting the user id#1, are the credentials correct?")?
+            .value()
+            .attr("href")
+            .ok_or("Failed getting the user id#2")?
+            .replace("/users/", "");
+        Ok((client, user_id))
+    }
+
+    fn login(
+        redmine_config: &RedmineConfig,
+        client: &reqwest::blocking::Client,
+        html: &str,
+    ) -> Result<String> {
+        let doc = scraper::Html::parse_document(html);
         let sel = scraper::Selector::parse("input[name=authenticity_token]").unwrap();
         let auth_token_node = doc.select(&sel).next().unwrap();
         let auth_token = auth_token_node.value().attr("value").unwrap();
 
-        let html = client
+        Ok(client
             .post(&format!("{}/login", redmine_config.server_url))
             .form(&[
                 ("username", &redmine_config.username),
@@ -210,44 +464,33 @@ impl Redmine {
             ])
             .send()?
             .error_for_status()?
-            .text()?;
-        let doc = scraper::Html::parse_document(&html);
-        let user_sel = scraper::Selector::parse("a.user.active").unwrap();
-        let user_id = doc
-            .select(&user_sel)
-            .next()
-            .ok_or("Failed getting the user id#1")?
-            .value()
-            .attr("href")
-            .ok_or("Failed getting the user id#2")?
-            .replace("/users/", "");
-        Ok((client, user_id))
+            .text()?)
     }
 
-    fn fetch_activity_html(
-        config_name: &str,
-        redmine_config: &RedmineConfig,
-    ) -> Result<(reqwest::blocking::Client, String)> {
+    // redmine shows the activity for a number of days (30 by default, an admin
+    // setting) ending at the 'from' date => we get all these days in one go
+    fn fetch_activity_html(redmine_config: &RedmineConfig, day: Date<Local>) -> Result<String> {
         let (client, user_id) = Self::init_client(redmine_config)?;
 
-        let html = client
-            .get(&format!(
-                "{}/activity?user_id={}",
-                redmine_config.server_url, user_id
-            ))
+        Ok(redmine_config
+            .get(
+                &client,
+                &format!(
+                    "{}/activity?user_id={}&from={}",
+                    redmine_config.server_url,
+                    user_id,
+                    day.format("%F")
+                ),
+            )
             .send()?
             .error_for_status()?
-            .text()?;
-        Config::write_to_cache(&Redmine, config_name, &html)?;
-        Ok((client, html))
+            .text()?)
     }
 
     fn parse_html(
-        redmine_config: &RedmineConfig,
         redmine_locales: &HashMap<&'static str, LocaleInfo>,
-        day: Date<Local>,
         activity_html: &str,
-    ) -> Result<ActivityData> {
+    ) -> Result<ActivityPage> {
         let doc = scraper::Html::parse_document(activity_html);
         let locale_str = doc
             .root_element()
@@ -261,68 +504,91 @@ impl Redmine {
         let day_sel = scraper::Selector::parse("div#content div#activity h3").unwrap();
         let day_contents_sel =
             scraper::Selector::parse("div#content div#activity h3 + dl").unwrap();
-        let mut it_day = doc.select(&day_sel);
-        let mut it_contents = doc.select(&day_contents_sel);
-        loop {
-            let next_day = it_day.next();
-            let contents = it_contents.next();
-            match (next_day, contents) {
-                (Some(day_elt), Some(contents_elt)) => {
-                    let cur_date = Self::parse_date(locale, &day_elt.inner_html())?;
-                    if cur_date < day {
-                        // passed the day, won't be any events this time.
-                        return Ok(ActivityData::Done(vec![]));
-                    }
-                    if cur_date == day {
-                        return Self::parse_events(redmine_config, &contents_elt)
-                            .map(ActivityData::Done);
-                    }
-                }
-                _ => {
-                    break;
-                }
-            }
-        }
-        // no matches in this page, search for the 'previous' paging link
+        let days = doc
+            .select(&day_sel)
+            .zip(doc.select(&day_contents_sel))
+            .map(|(day_elt, contents_elt)| {
+                Ok((
+                    Self::parse_date(locale, &day_elt.inner_html())?.naive_local(),
+                    Self::parse_activities(&contents_elt)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // the 'previous' paging link points to the day before the first
+        // day of this page: /activity?from=2020-02-22&user_id=5
         let previous_sel = scraper::Selector::parse("li.previous.page a").unwrap();
-        let previous_url = doc
+        let previous_from_regex = regex::Regex::new(r"from=(\d{4}-\d{2}-\d{2})").unwrap();
+        let range_start = doc
             .select(&previous_sel)
             .next()
-            .and_then(|p| p.value().attr("href"));
-        Ok(ActivityData::ReachedEndOfPage(
-            previous_url.map(|s| redmine_config.server_url.clone() + s),
-        ))
+            .and_then(|p| p.value().attr("href"))
+            .and_then(|href| previous_from_regex.captures(href))
+            .and_then(|caps| NaiveDate::parse_from_str(&caps[1], "%Y-%m-%d").ok())
+            .map(|d| d.succ());
+        Ok(ActivityPage { days, range_start })
     }
 
-    fn get_events_with_paging(
-        day: Date<Local>,
-        activity_html: String,
-        redmine_config: &RedmineConfig,
-        redmine_locales: &HashMap<&'static str, LocaleInfo>,
-        client_opt: Option<reqwest::blocking::Client>,
-    ) -> Result<Vec<Event>> {
-        match Self::parse_html(redmine_config, redmine_locales, day, &activity_html) {
-            Ok(ActivityData::Done(events)) => Ok(events),
-            Err(e) => Err(e),
-            Ok(ActivityData::ReachedEndOfPage(None)) => Ok(vec![]),
-            Ok(ActivityData::ReachedEndOfPage(Some(new_url))) => {
-                // recursively check for the previous page
-                let client = match client_opt {
-                    Some(c) => c,
-                    None => Self::init_client(redmine_config)?.0,
-                };
-                println!("Fetching {}", new_url);
-                let html = client.get(&new_url).send()?.error_for_status()?.text()?;
-                Self::get_events_with_paging(
-                    day,
-                    html,
-                    redmine_config,
-                    redmine_locales,
-                    Some(client),
-                )
-            }
+    fn activity_cache_path(config_name: &str) -> Result<std::path::PathBuf> {
+        Ok(Config::config_folder()?.join(format!(
+            "Redmine_{}.days",
+            Config::sanitize_for_filename(config_name)
+        )))
+    }
+
+    fn load_activity_cache(config_name: &str) -> Result<ActivityCache> {
+        let cache_path = Self::activity_cache_path(config_name)?;
+        if !cache_path.is_file() {
+            return Ok(ActivityCache::default());
+        }
+        Ok(
+            serde_json::from_reader(BufReader::new(File::open(&cache_path)?)).unwrap_or_else(|e| {
+                log::warn!("Invalid redmine cache {:?}, ignoring: {}", cache_path, e);
+                ActivityCache::default()
+            }),
+        )
+    }
+
+    fn save_activity_cache(config_name: &str, cache: &ActivityCache) -> Result<()> {
+        let cache_path = Self::activity_cache_path(config_name)?;
+        let tmp_path = cache_path.with_extension("days.tmp");
+        serde_json::to_writer(BufWriter::new(File::create(&tmp_path)?), cache)?;
+        std::fs::rename(tmp_path, cache_path)?;
+        Ok(())
+    }
+}
+
+impl ActivityCache {
+    /// record all the days covered by the page, including the days without activity
+    fn add_page(&mut self, page: ActivityPage, to: NaiveDate, fetched_at: DateTime<Local>) {
+        // without a 'previous' link we reached the start of the history, but
+        // there's no point in recording empty days all the way back
+        let range_start = page
+            .range_start
+            .or_else(|| page.days.iter().map(|(d, _)| *d).min())
+            .unwrap_or(to);
+        let mut activities_by_day: HashMap<NaiveDate, Vec<RedmineActivity>> =
+            page.days.into_iter().collect();
+        let mut day = range_start;
+        while day <= to {
+            self.days.insert(
+                day,
+                CachedDay {
+                    fetched_at,
+                    activities: activities_by_day.remove(&day).unwrap_or_default(),
+                },
+            );
+            day = day.succ();
         }
     }
+
+    /// days are final once we fetched them after they ended
+    fn get_final_day(&self, day: NaiveDate) -> Option<&Vec<RedmineActivity>> {
+        self.days
+            .get(&day)
+            .filter(|d| d.fetched_at.naive_local().date() > day)
+            .map(|d| &d.activities)
+    }
 }
 
 impl EventProvider for Redmine {
@@ -341,17 +607,30 @@ impl EventProvider for Redmine {
     fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
         vec![
             (SERVER_URL_KEY, ConfigType::Text("")),
+            (MODE_KEY, ConfigType::Combo),
             (USERNAME_KEY, ConfigType::Text("")),
             (PASSWORD_KEY, ConfigType::Password),
+            (API_KEY_KEY, ConfigType::Password),
+            (SESSION_COOKIE_KEY, ConfigType::Password),
+            (BASIC_AUTH_KEY, ConfigType::Checkbox),
+            (CA_BUNDLE_KEY, ConfigType::File),
+            (ACCEPT_INVALID_CERTS_KEY, ConfigType::Checkbox),
         ]
     }
 
     fn field_values(
         &self,
         _cur_values: &HashMap<&'static str, String>,
-        _field_name: &'static str,
+        field_name: &'static str,
     ) -> Result<Vec<String>> {
-        Ok(Vec::new())
+        if field_name == MODE_KEY {
+            Ok(vec![
+                RedmineMode::ActivityScraping.desc().to_string(),
+                RedmineMode::RestApi.desc().to_string(),
+            ])
+        } else {
+            Ok(Vec::new())
+        }
     }
 
     fn get_config_values(
@@ -372,6 +651,27 @@ impl EventProvider for Redmine {
                 PASSWORD_KEY,
                 config.redmine[config_name].password.to_string(),
             ),
+            (
+                MODE_KEY,
+                config.redmine[config_name].mode.desc().to_string(),
+            ),
+            (API_KEY_KEY, config.redmine[config_name].api_key.to_string()),
+            (
+                SESSION_COOKIE_KEY,
+                config.redmine[config_name].session_cookie.to_string(),
+            ),
+            (
+                BASIC_AUTH_KEY,
+                config.redmine[config_name].basic_auth.to_string(),
+            ),
+            (
+                CA_BUNDLE_KEY,
+                config.redmine[config_name].ca_bundle_path.to_string(),
+            ),
+            (
+                ACCEPT_INVALID_CERTS_KEY,
+                config.redmine[config_name].accept_invalid_certs.to_string(),
+            ),
         ]
         .into_iter()
         .collect()
@@ -389,6 +689,13 @@ impl EventProvider for Redmine {
                 server_url: config_values.remove(SERVER_URL_KEY).unwrap(),
                 username: config_values.remove(USERNAME_KEY).unwrap(),
                 password: config_values.remove(PASSWORD_KEY).unwrap(),
+                mode: RedmineMode::from_desc(&config_values.remove(MODE_KEY).unwrap_or_default()),
+                api_key: config_values.remove(API_KEY_KEY).unwrap_or_default(),
+                session_cookie: config_values.remove(SESSION_COOKIE_KEY).unwrap_or_default(),
+                basic_auth: config_values.remove(BASIC_AUTH_KEY).as_deref() == Some("true"),
+                ca_bundle_path: config_values.remove(CA_BUNDLE_KEY).unwrap_or_default(),
+                accept_invalid_certs: config_values.remove(ACCEPT_INVALID_CERTS_KEY).as_deref()
+                    == Some("true"),
             },
         );
     }
@@ -405,16 +712,28 @@ impl EventProvider for Redmine {
     ) -> Result<Vec<Event>> {
         log::debug!("redmine::get_events");
         let redmine_config = &config.redmine[config_name];
-        let redmine_locales = Self::redmine_locales();
-        let day_start = day.and_hms(0, 0, 0);
-        let next_day_start = day_start + chrono::Duration::days(1);
-        let (client, activity_html) =
-            match Config::get_cached_contents(&Redmine, config_name, &next_day_start)? {
-                Some(t) => Ok((None, t)),
-                None => Self::fetch_activity_html(config_name, redmine_config)
-                    .map(|(a, b)| (Some(a), b)),
-            }?;
-        Self::get_events_with_paging(day, activity_html, redmine_config, &redmine_locales, client)
+        if redmine_config.mode == RedmineMode::RestApi {
+            return redmine_rest::get_events(redmine_config, day);
+        }
+        let mut cache = Self::load_activity_cache(config_name)?;
+        let activities = match cache.get_final_day(day.naive_local()) {
+            Some(activities) => activities.clone(),
+            None => {
+                let activity_html = Self::fetch_activity_html(redmine_config, day)?;
+                let page = Self::parse_html(&Self::redmine_locales(), &activity_html)?;
+                cache.add_page(page, day.naive_local(), Local::now());
+                Self::save_activity_cache(config_name, &cache)?;
+                cache
+                    .days
+                    .get(&day.naive_local())
+                    .map(|d| d.activities.clone())
+                    .unwrap_or_default()
+            }
+        };
+        Ok(activities
+            .into_iter()
+            .map(|a| Self::build_event(redmine_config, a))
+            .collect())
     }
 }
 
@@ -464,3 +783,146 @@ fn it_parses_iso_times_correctly() {
         Redmine::parse_time("13:30").unwrap()
     );
 }
+
+#[test]
+fn it_parses_redmine_activities() {
+    let html = scraper::Html::parse_fragment(
+        r#"<dl>
+<dt class="issue-closed icon icon-issue-closed me">
+  <img class="gravatar" src="x.png" />
+  <span class="time">13:30</span>
+  <span class="project">Cigale</span>
+  <a href="/issues/123#change-456">Bug #123 (Resolved): Crash &amp; burn</a>
+</dt>
+<dd class="me"><span class="description">Fixed in r12</span></dd>
+<dt class="wiki-page icon icon-wiki-page">
+  <span class="time">14:00</span>
+  <a href="/projects/cigale/wiki/Home">Wiki edit: Home (#3)</a>
+</dt>
+<dd><span class="description"></span></dd>
+<dt class="changeset icon icon-changeset me">
+  <span class="time">15:00</span>
+  <a href="/projects/cigale/repository/revisions/1234">Revision 1234 (cigale): fix #12: crash</a>
+</dt>
+<dd class="me"><span class="description">fix #12: crash</span></dd>
+</dl>"#,
+    );
+    let redmine_config = RedmineConfig {
+        server_url: "https://redmine.example.com".to_string(),
+        username: "".to_string(),
+        password: "".to_string(),
+        mode: RedmineMode::ActivityScraping,
+        api_key: "".to_string(),
+        session_cookie: "".to_string(),
+        basic_auth: false,
+        ca_bundle_path: "".to_string(),
+        accept_invalid_certs: false,
+    };
+    let dl_sel = scraper::Selector::parse("dl").unwrap();
+    let events: Vec<Event> = Redmine::parse_activities(&html.select(&dl_sel).next().unwrap())
+        .unwrap()
+        .into_iter()
+        .map(|a| Redmine::build_event(&redmine_config, a))
+        .collect();
+    assert_eq!(3, events.len());
+    assert_eq!(Icon::CHECK_SQUARE, events[0].event_type_icon);
+    assert_eq!("Bug #123: Crash & burn", events[0].event_contents_header);
+    assert_eq!("Crash & burn", events[0].event_info);
+    assert_eq!(
+        Some("Issue closed → Resolved".to_string()),
+        events[0].event_extra_details
+    );
+    assert!(events[0]
+        .event_contents_body
+        .as_str()
+        .contains("<b>Project:</b> Cigale"));
+    assert_eq!(Icon::BOOK, events[1].event_type_icon);
+    assert_eq!("Wiki edit: Home (#3)", events[1].event_contents_header);
+    assert_eq!(Icon::CODE_BRANCH, events[2].event_type_icon);
+    assert_eq!(
+        "Revision 1234 (cigale): fix #12: crash",
+        events[2].event_contents_header
+    );
+    assert_eq!(Some("Changeset".to_string()), events[2].event_extra_details);
+}
+
+#[test]
+fn it_parses_issue_titles() {
+    assert_eq!(
+        Some(IssueInfo {
+            tracker: "Feature".to_string(),
+            issue_number: 42,
+            status: None,
+            subject: "Export: to CSV".to_string(),
+        }),
+        Redmine::parse_issue_title("Feature #42: Export: to CSV")
+    );
+    assert_eq!(None, Redmine::parse_issue_title("Revision 1234: fix"));
+}
+
+#[test]
+fn it_caches_all_the_days_of_an_activity_page() {
+    let html = r#"<html lang="en"><body><div id="content"><div id="activity">
+<h3>03/23/2020</h3>
+<dl>
+<dt class="issue icon icon-issue"><span class="time">09:10</span>
+<a href="/issues/5">Bug #5 (New): Broken</a></dt>
+<dd><span class="description">It's broken</span></dd>
+</dl>
+<h3>03/20/2020</h3>
+<dl>
+<dt class="news icon icon-news"><span class="time">11:00</span>
+<a href="/news/2">Release 1.0</a></dt>
+<dd><span class="description"></span></dd>
+</dl>
+</div>
+<ul class="pages"><li class="previous page"><a href="/activity?from=2020-03-15&amp;user_id=5">Previous</a></li></ul>
+</div></body></html>"#;
+    let page = Redmine::parse_html(&Redmine::redmine_locales(), html).unwrap();
+    assert_eq!(Some(NaiveDate::from_ymd(2020, 3, 16)), page.range_start);
+    assert_eq!(2, page.days.len());
+
+    let mut cache = ActivityCache::default();
+    cache.add_page(
+        page,
+        NaiveDate::from_ymd(2020, 3, 24),
+        Local.ymd(2020, 3, 24).and_hms(10, 0, 0),
+    );
+    assert_eq!(9, cache.days.len());
+    assert_eq!(
+        1,
+        cache
+            .get_final_day(NaiveDate::from_ymd(2020, 3, 23))
+            .unwrap()
+            .len()
+    );
+    assert_eq!(
+        0,
+        cache
+            .get_final_day(NaiveDate::from_ymd(2020, 3, 22))
+            .unwrap()
+            .len()
+    );
+    // the day was still in progress when we fetched it
+    assert!(cache
+        .get_final_day(NaiveDate::from_ymd(2020, 3, 24))
+        .is_none());
+    let json = serde_json::to_string(&cache).unwrap();
+    let cache: ActivityCache = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        "Release 1.0",
+        cache.days[&NaiveDate::from_ymd(2020, 3, 20)].activities[0].title
+    );
+}
+
+#[test]
+fn it_accepts_session_cookies_with_or_without_name() {
+    assert_eq!(
+        "_redmine_session=abc123",
+        RedmineConfig::session_cookie_str(" abc123\n")
+    );
+    assert_eq!(
+        "_sso_session=abc123",
+        RedmineConfig::session_cookie_str("_sso_session=abc123")
+    );
+}
@@ -0,0 +1,428 @@
+// the REST API mode of the redmine event source. The REST API has no
+// activity endpoint, so we rebuild the activity from the issues which were
+// updated on the day (and their journals), but we also get the time entries,
+// which are not available when scraping the activity page.
+// We can't ask redmine for the issues that the user updated, so we look at
+// the issues the user created, is assigned to or watches, and the issues
+// the user logged time on. Asking for all the issues updated on the day
+// would mean fetching the journals of every issue of the instance.
+use super::events::{Event, EventBody, Result, WordWrapMode};
+use super::redmine::RedmineConfig;
+use crate::icons::*;
+use chrono::prelude::*;
+use serde_derive::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+
+const PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize, Clone, Debug)]
+struct RedmineRef {
+    id: usize,
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct RedmineTimeEntry {
+    id: usize,
+    project: RedmineRef,
+    issue: Option<RedmineRef>,
+    activity: RedmineRef,
+    hours: f32,
+    #[serde(default)]
+    comments: String,
+    spent_on: NaiveDate,
+    created_on: DateTime<Local>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct RedmineIssue {
+    id: usize,
+    project: RedmineRef,
+    tracker: RedmineRef,
+    author: RedmineRef,
+    subject: String,
+    created_on: DateTime<Local>,
+    #[serde(default)]
+    journals: Vec<RedmineJournal>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct RedmineJournal {
+    user: RedmineRef,
+    notes: Option<String>,
+    created_on: DateTime<Local>,
+    #[serde(default)]
+    details: Vec<RedmineJournalDetail>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct RedmineJournalDetail {
+    property: String,
+    name: String,
+    old_value: Option<String>,
+    new_value: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct RedmineCurrentUser {
+    user: RedmineRef,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct RedmineIssueStatuses {
+    issue_statuses: Vec<RedmineRef>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct RedmineIssueWrapper {
+    issue: RedmineIssue,
+}
+
+pub fn get_events(redmine_config: &RedmineConfig, day: Date<Local>) -> Result<Vec<Event>> {
+    let client = redmine_config.http_client()?;
+    let day_start = day.and_hms(0, 0, 0);
+    let next_day_start = day_start + chrono::Duration::days(1);
+    let day_str = day.format("%F").to_string();
+    let user_id =
+        get_json::<RedmineCurrentUser>(&client, redmine_config, "/users/current.json", &[])?
+            .user
+            .id;
+
+    let time_entries = call_redmine_rest::<RedmineTimeEntry>(
+        &client,
+        redmine_config,
+        "/time_entries.json",
+        &[
+            ("user_id", &user_id.to_string()),
+            ("from", &day_str),
+            ("to", &day_str),
+        ],
+        "time_entries",
+    )?;
+    let mut events: Vec<Event> = time_entries
+        .iter()
+        .map(|t| build_time_entry_event(redmine_config, t, &day_start, &next_day_start))
+        .collect();
+
+    let mut issue_ids: BTreeSet<usize> = time_entries
+        .iter()
+        .filter_map(|t| t.issue.as_ref().map(|i| i.id))
+        .collect();
+    for user_filter in &["author_id", "assigned_to_id", "watcher_id"] {
+        issue_ids.extend(
+            call_redmine_rest::<RedmineIssue>(
+                &client,
+                redmine_config,
+                "/issues.json",
+                &[
+                    ("status_id", "*"),
+                    ("updated_on", &format!("><{}|{}", day_str, day_str)),
+                    (user_filter, "me"),
+                ],
+                "issues",
+            )?
+            .iter()
+            .map(|i| i.id),
+        );
+    }
+    // journals give us status ids, we want to display the status names
+    let statuses: HashMap<String, String> =
+        get_json::<RedmineIssueStatuses>(&client, redmine_config, "/issue_statuses.json", &[])?
+            .issue_statuses
+            .into_iter()
+            .map(|s| (s.id.to_string(), s.name))
+            .collect();
+    // the issues list doesn't include the journals, we must fetch
+    // them for each issue, to find out whether we updated the issue
+    for issue_id in issue_ids {
+        let issue = get_json::<RedmineIssueWrapper>(
+            &client,
+            redmine_config,
+            &format!("/issues/{}.json", issue_id),
+            &[("include", "journals")],
+        )?
+        .issue;
+        if issue.author.id == user_id
+            && issue.created_on >= day_start
+            && issue.created_on < next_day_start
+        {
+            events.push(build_issue_created_event(redmine_config, &issue));
+        }
+        events.extend(
+            issue
+                .journals
+                .iter()
+                .filter(|j| {
+                    j.user.id == user_id
+                        && j.created_on >= day_start
+                        && j.created_on < next_day_start
+                })
+                .map(|j| build_journal_event(redmine_config, &statuses, &issue, j)),
+        );
+    }
+    Ok(events)
+}
+
+fn get_json<T>(
+    client: &reqwest::blocking::Client,
+    redmine_config: &RedmineConfig,
+    path: &str,
+    params: &[(&str, &str)],
+) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let json = redmine_config
+        .get(client, &format!("{}{}", redmine_config.server_url, path))
+        .query(params)
+        .send()?
+        .error_for_status()?
+        .text()?;
+    log::debug!("{}: {}", path, json);
+    Ok(serde_json::from_str(&json)
+        .map_err(|e| format!("Failed parsing json {} {:?} -- {}", path, e, json))?)
+}
+
+// redmine returns paged lists as {"<root_key>": [...], "total_count": x, "offset": y, "limit": z}
+fn call_redmine_rest<T>(
+    client: &reqwest::blocking::Client,
+    redmine_config: &RedmineConfig,
+    path: &str,
+    params: &[(&str, &str)],
+    root_key: &str,
+) -> Result<Vec<T>>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let mut records = vec![];
+    let mut offset = 0;
+    loop {
+        let limit_str = PAGE_SIZE.to_string();
+        let offset_str = offset.to_string();
+        let mut page_params = params.to_vec();
+        page_params.push(("limit", &limit_str));
+        page_params.push(("offset", &offset_str));
+        let mut page: serde_json::Value = get_json(client, redmine_config, path, &page_params)?;
+        let total_count = page["total_count"].as_u64().unwrap_or(0) as usize;
+        let mut page_records: Vec<T> = serde_json::from_value(page[root_key].take())?;
+        let page_size = page_records.len();
+        records.append(&mut page_records);
+        offset += page_size;
+        if page_size == 0 || offset >= total_count {
+            break;
+        }
+    }
+    Ok(records)
+}
+
+fn issue_link(redmine_config: &RedmineConfig, issue_id: usize) -> String {
+    format!(
+        "<a href=\"{}/issues/{}\">Open in the browser</a>",
+        redmine_config.server_url, issue_id
+    )
+}
+
+fn build_time_entry_event(
+    redmine_config: &RedmineConfig,
+    time_entry: &RedmineTimeEntry,
+    day_start: &DateTime<Local>,
+    next_day_start: &DateTime<Local>,
+) -> Event {
+    // time entries have a date but no time. If the entry was logged
+    // on the day it was spent, the creation time is a good approximation,
+    // otherwise (logged the day after for instance) put it at the start of the day.
+    let time = if time_entry.created_on >= *day_start && time_entry.created_on < *next_day_start {
+        time_entry.created_on.time()
+    } else {
+        NaiveTime::from_hms(0, 0, 0)
+    };
+    let issue_desc = time_entry
+        .issue
+        .as_ref()
+        .map(|i| format!(" #{}", i.id))
+        .unwrap_or_default();
+    let header = format!(
+        "{}h {} on {}{}",
+        time_entry.hours, time_entry.activity.name, time_entry.project.name, issue_desc
+    );
+    let link = match &time_entry.issue {
+        Some(i) => issue_link(redmine_config, i.id),
+        None => format!(
+            "<a href=\"{}/time_entries/{}/edit\">Open in the browser</a>",
+            redmine_config.server_url, time_entry.id
+        ),
+    };
+    Event::new(
+        "Redmine",
+        Icon::CLOCK,
+        time,
+        if time_entry.comments.is_empty() {
+            header.clone()
+        } else {
+            time_entry.comments.clone()
+        },
+        header,
+        EventBody::Markup(
+            format!(
+                "{}\n\nSpent on {}\n{}",
+                link,
+                time_entry.spent_on,
+                glib::markup_escape_text(&time_entry.comments)
+            ),
+            WordWrapMode::WordWrap,
+        ),
+        Some(format!("{}h logged", time_entry.hours)),
+    )
+}
+
+fn build_issue_created_event(redmine_config: &RedmineConfig, issue: &RedmineIssue) -> Event {
+    let header = format!(
+        "{} #{} created: {}",
+        issue.tracker.name, issue.id, issue.subject
+    );
+    Event::new(
+        "Redmine",
+        Icon::TASKS,
+        issue.created_on.time(),
+        issue.subject.clone(),
+        header.clone(),
+        EventBody::Markup(
+            format!(
+                "{}\n\n{}\n{}",
+                issue_link(redmine_config, issue.id),
+                glib::markup_escape_text(&issue.project.name),
+                glib::markup_escape_text(&header)
+            ),
+            WordWrapMode::WordWrap,
+        ),
+        Some(format!("{} created", issue.tracker.name)),
+    )
+}
+
+fn journal_detail_desc(
+    statuses: &HashMap<String, String>,
+    detail: &RedmineJournalDetail,
+) -> String {
+    let name = if detail.property == "attr" {
+        detail.name.trim_end_matches("_id").replace('_', " ")
+    } else {
+        detail.name.clone()
+    };
+    let value_desc = |v: &Option<String>| match v {
+        Some(id) if detail.name == "status_id" => {
+            Some(statuses.get(id).cloned().unwrap_or_else(|| id.clone()))
+        }
+        _ => v.clone(),
+    };
+    match (
+        &value_desc(&detail.old_value),
+        &value_desc(&detail.new_value),
+    ) {
+        (Some(o), Some(n)) => format!("{}: {} → {}", name, o, n),
+        (None, Some(n)) => format!("{}: set to {}", name, n),
+        (Some(o), None) => format!("{}: {} deleted", name, o),
+        (None, None) => name,
+    }
+}
+
+fn build_journal_event(
+    redmine_config: &RedmineConfig,
+    statuses: &HashMap<String, String>,
+    issue: &RedmineIssue,
+    journal: &RedmineJournal,
+) -> Event {
+    let header = format!("{} #{}: {}", issue.tracker.name, issue.id, issue.subject);
+    let changes = journal
+        .details
+        .iter()
+        .map(|d| journal_detail_desc(statuses, d))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Event::new(
+        "Redmine",
+        Icon::TASKS,
+        journal.created_on.time(),
+        issue.subject.clone(),
+        header,
+        EventBody::Markup(
+            format!(
+                "{}\n\n{}\n{}",
+                issue_link(redmine_config, issue.id),
+                glib::markup_escape_text(&changes),
+                glib::markup_escape_text(journal.notes.as_deref().unwrap_or(""))
+            ),
+            WordWrapMode::WordWrap,
+        ),
+        Some(format!("{} updated", issue.tracker.name)),
+    )
+}
+
+#[test]
+fn it_builds_time_entry_events() {
+    let time_entries: Vec<RedmineTimeEntry> = serde_json::from_str(
+        r#"[{"id":12,"project":{"id":1,"name":"Cigale"},"issue":{"id":345},
+            "user":{"id":3,"name":"Jane Doe"},"activity":{"id":9,"name":"Development"},
+            "hours":1.5,"comments":"parsing the activity","spent_on":"2020-03-23",
+            "created_on":"2020-03-23T15:30:00Z","updated_on":"2020-03-23T15:30:00Z"}]"#,
+    )
+    .unwrap();
+    let redmine_config = RedmineConfig {
+        server_url: "https://redmine.example.com".to_string(),
+        username: "".to_string(),
+        password: "".to_string(),
+        mode: super::redmine::RedmineMode::RestApi,
+        api_key: "key".to_string(),
+        session_cookie: "".to_string(),
+        basic_auth: false,
+        ca_bundle_path: "".to_string(),
+        accept_invalid_certs: false,
+    };
+    let created_on = time_entries[0].created_on;
+    let day_start = created_on.date().and_hms(0, 0, 0);
+    let event = build_time_entry_event(
+        &redmine_config,
+        &time_entries[0],
+        &day_start,
+        &(day_start + chrono::Duration::days(1)),
+    );
+    assert_eq!(created_on.time(), event.event_time);
+    assert_eq!(
+        "1.5h Development on Cigale #345",
+        event.event_contents_header
+    );
+    assert_eq!("parsing the activity", event.event_info);
+    assert_eq!(Some("1.5h logged".to_string()), event.event_extra_details);
+    assert!(event
+        .event_contents_body
+        .as_str()
+        .contains("https://redmine.example.com/issues/345"));
+}
+
+#[test]
+fn it_describes_journal_changes() {
+    let detail = |name: &str, old: Option<&str>, new: Option<&str>| RedmineJournalDetail {
+        property: "attr".to_string(),
+        name: name.to_string(),
+        old_value: old.map(|s| s.to_string()),
+        new_value: new.map(|s| s.to_string()),
+    };
+    let statuses = vec![
+        ("1".to_string(), "New".to_string()),
+        ("5".to_string(), "Closed".to_string()),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+        "status: New → Closed",
+        journal_detail_desc(&statuses, &detail("status_id", Some("1"), Some("5")))
+    );
+    assert_eq!(
+        "done ratio: 0 → 50",
+        journal_detail_desc(&statuses, &detail("done_ratio", Some("0"), Some("50")))
+    );
+    assert_eq!(
+        "assigned to: set to 3",
+        journal_detail_desc(&statuses, &detail("assigned_to_id", None, Some("3")))
+    );
+}
@@ -0,0 +1,241 @@
+// helpers shared by the event sources calling paged REST APIs (gitlab,
+// github, gitea): backing off on rate limits, following the next page
+// links, and keeping the data of the pages we could fetch.
+use super::events::{Event, EventBody, Result};
+use crate::icons::*;
+use chrono::prelude::*;
+use core::time::Duration;
+
+const MAX_RETRIES: u32 = 5;
+const MAX_RETRY_DELAY_SECS: u64 = 60;
+
+pub trait RestApi {
+    /// the event source name, for the logs and the events
+    fn source_name(&self) -> &'static str;
+
+    /// add the authentication & other headers to a request
+    fn prepare_request(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder;
+
+    /// the url of the next page, given the headers of the current page
+    fn next_page_url(
+        &self,
+        headers: &reqwest::header::HeaderMap,
+        _first_url: &str,
+    ) -> Option<String> {
+        next_page_link(headers)
+    }
+}
+
+pub struct PagedRecords<T> {
+    pub records: Vec<T>,
+    // set if we failed fetching some pages
+    pub error: Option<String>,
+}
+
+impl<T> PagedRecords<T> {
+    pub fn complete(self) -> Result<Vec<T>> {
+        match self.error {
+            Some(e) => Err(e.into()),
+            None => Ok(self.records),
+        }
+    }
+}
+
+pub fn http_client() -> Result<reqwest::blocking::Client> {
+    Ok(reqwest::blocking::ClientBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .connect_timeout(Duration::from_secs(30))
+        .connection_verbose(true)
+        // the github API rejects requests without user agent
+        .user_agent("cigale")
+        .build()?)
+}
+
+fn header_str<'a>(headers: &'a reqwest::header::HeaderMap, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name))
+        .and_then(|h| h.to_str().ok())
+}
+
+// the servers tell us how long to wait when we hit the rate limit:
+// Retry-After is in seconds, (X-)RateLimit-Reset is a unix timestamp
+pub fn retry_delay(headers: &reqwest::header::HeaderMap, attempt: u32) -> Duration {
+    let header_u64 =
+        |names: &[&str]| header_str(headers, names).and_then(|h| h.parse::<u64>().ok());
+    header_u64(&["Retry-After"])
+        .map(Duration::from_secs)
+        .or_else(|| {
+            header_u64(&["RateLimit-Reset", "X-RateLimit-Reset"]).map(|reset| {
+                Duration::from_secs(reset.saturating_sub(Utc::now().timestamp() as u64))
+            })
+        })
+        .unwrap_or_else(|| Duration::from_secs(2u64.pow(attempt)))
+        .min(Duration::from_secs(MAX_RETRY_DELAY_SECS))
+}
+
+fn no_requests_remaining(headers: &reqwest::header::HeaderMap) -> bool {
+    header_str(headers, &["RateLimit-Remaining", "X-RateLimit-Remaining"]) == Some("0")
+}
+
+// github answers 403 instead of 429 for the primary rate limit
+fn is_rate_limited(resp: &reqwest::blocking::Response) -> bool {
+    resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || (resp.status() == reqwest::StatusCode::FORBIDDEN
+            && (resp.headers().contains_key("Retry-After")
+                || no_requests_remaining(resp.headers())))
+}
+
+pub fn send_with_backoff(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    api: &dyn RestApi,
+) -> Result<reqwest::blocking::Response> {
+    let mut attempt = 0;
+    loop {
+        let resp = api.prepare_request(client.get(url)).send()?;
+        if is_rate_limited(&resp) && attempt < MAX_RETRIES {
+            let delay = retry_delay(resp.headers(), attempt);
+            log::warn!(
+                "{} rate limit hit for {}, waiting {:?}",
+                api.source_name(),
+                url,
+                delay
+            );
+            std::thread::sleep(delay);
+            attempt += 1;
+            continue;
+        }
+        let resp = resp.error_for_status()?;
+        if no_requests_remaining(resp.headers()) {
+            // don't wait for the 429 on the next request
+            let delay = retry_delay(resp.headers(), attempt);
+            log::warn!(
+                "{} rate limit reached, waiting {:?}",
+                api.source_name(),
+                delay
+            );
+            std::thread::sleep(delay);
+        }
+        return Ok(resp);
+    }
+}
+
+// <https://gitlab.com/api/v4/projects?page=2>; rel="next", <https://...>; rel="first"
+pub fn next_page_link(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let link_header = headers.get("Link")?.to_str().ok()?;
+    link_header
+        .split(',')
+        .map(|l| l.trim())
+        .find(|l| l.ends_with("rel=\"next\""))
+        .and_then(|l| l.split(';').next())
+        .map(|l| {
+            l.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string()
+        })
+}
+
+/// the first page must succeed, but we return partial data if a later page fails.
+/// We stop paging once `is_done` returns true for the records fetched so far.
+pub fn get_paged<T>(
+    api: &dyn RestApi,
+    first_url: &str,
+    is_done: impl Fn(&[T]) -> bool,
+) -> Result<PagedRecords<T>>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let client = http_client()?;
+    let mut url = first_url.to_string();
+    let mut records = vec![];
+    let mut page_idx = 1;
+    loop {
+        let page = send_with_backoff(&client, &url, api).and_then(|resp| {
+            let next_url = api.next_page_url(resp.headers(), first_url);
+            let json = resp.text()?;
+            log::debug!("{}, page {}, text: {}", first_url, page_idx, json);
+            let page_records = serde_json::from_str::<Vec<T>>(&json)
+                .map_err(|e| format!("Failed parsing json {} {:?} -- {}", first_url, e, json))?;
+            Ok((page_records, next_url))
+        });
+        match page {
+            Ok((mut page_records, next_url)) => {
+                records.append(&mut page_records);
+                match next_url {
+                    Some(u) if !is_done(&records) => url = u,
+                    _ => break,
+                }
+            }
+            Err(e) if page_idx > 1 => {
+                log::error!("Failed fetching page {} of {}: {}", page_idx, first_url, e);
+                return Ok(PagedRecords {
+                    records,
+                    error: Some(format!("page {}: {}", page_idx, e)),
+                });
+            }
+            Err(e) => return Err(e),
+        }
+        page_idx += 1;
+    }
+    Ok(PagedRecords {
+        records,
+        error: None,
+    })
+}
+
+/// warn the user that we failed fetching some pages
+pub fn missing_events_event(source_name: &'static str, error: &str) -> Event {
+    Event::new(
+        source_name,
+        Icon::EXCLAMATION_TRIANGLE,
+        NaiveTime::from_hms(0, 0, 0),
+        "Some events are missing".to_string(),
+        format!("{}: some events are missing", source_name),
+        EventBody::PlainText(format!(
+            "Failed fetching some of the events, this list is incomplete.\n\n{}",
+            error
+        )),
+        Some("Incomplete data".to_string()),
+    )
+}
+
+#[test]
+fn it_follows_the_next_page_link() {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        "Link",
+        "<https://gitlab.com/api/v4/projects?id_after=42&per_page=100>; rel=\"next\", \
+         <https://gitlab.com/api/v4/projects?per_page=100>; rel=\"first\""
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(
+        Some("https://gitlab.com/api/v4/projects?id_after=42&per_page=100".to_string()),
+        next_page_link(&headers)
+    );
+    headers.insert(
+        "Link",
+        "<https://gitlab.com/api/v4/projects?per_page=100>; rel=\"first\""
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(None, next_page_link(&headers));
+}
+
+#[test]
+fn it_computes_the_retry_delay() {
+    let mut headers = reqwest::header::HeaderMap::new();
+    assert_eq!(Duration::from_secs(4), retry_delay(&headers, 2));
+    headers.insert("Retry-After", "7".parse().unwrap());
+    assert_eq!(Duration::from_secs(7), retry_delay(&headers, 2));
+    headers.insert("Retry-After", "3600".parse().unwrap());
+    assert_eq!(
+        Duration::from_secs(MAX_RETRY_DELAY_SECS),
+        retry_delay(&headers, 0)
+    );
+}
@@ -0,0 +1,522 @@
+// zsh, bash and fish only record the time of the commands, not the folder
+// where they were run, so we follow the 'cd' commands to guess it. Atuin
+// records the folder in its database.
+use super::events::{ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+// commands further apart than this are in different sessions
+const SESSION_GAP_MINS: i64 = 30;
+
+#[derive(
+    serde_derive::Deserialize, serde_derive::Serialize, Clone, Copy, Debug, PartialEq, Default,
+)]
+pub enum ShellKind {
+    #[default]
+    Zsh,
+    Bash,
+    Fish,
+    Atuin,
+}
+
+impl ShellKind {
+    const ALL: [ShellKind; 4] = [
+        ShellKind::Zsh,
+        ShellKind::Bash,
+        ShellKind::Fish,
+        ShellKind::Atuin,
+    ];
+
+    fn desc(self) -> &'static str {
+        match self {
+            ShellKind::Zsh => "zsh (extended history)",
+            ShellKind::Bash => "bash (with HISTTIMEFORMAT)",
+            ShellKind::Fish => "fish",
+            ShellKind::Atuin => "atuin (history.db)",
+        }
+    }
+
+    fn from_desc(desc: &str) -> ShellKind {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|k| k.desc() == desc)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct ShellHistoryConfig {
+    pub shell: ShellKind,
+    pub history_file: String,
+    // commands matching this regex are not displayed (passwords, tokens...)
+    pub ignore_regex: String,
+}
+
+pub struct ShellHistory;
+const SHELL_KEY: &str = "Shell";
+const HISTORY_FILE_KEY: &str = "History file";
+const IGNORE_REGEX_KEY: &str = "Ignore commands matching (regex)";
+
+#[derive(Clone, Debug, PartialEq)]
+struct HistoryEntry {
+    time: DateTime<Local>,
+    command: String,
+    cwd: Option<String>,
+}
+
+impl HistoryEntry {
+    fn new(timestamp: i64, command: String) -> HistoryEntry {
+        HistoryEntry {
+            time: Local.timestamp(timestamp, 0),
+            command,
+            cwd: None,
+        }
+    }
+}
+
+// follows the 'cd' commands, to guess in which folder the commands were run
+#[derive(Default)]
+struct CwdTracker {
+    cwd: Option<PathBuf>,
+    previous: Option<PathBuf>,
+}
+
+impl CwdTracker {
+    fn apply(&mut self, command: &str) {
+        for part in command.split(&[';', '&', '|', '\n'][..]) {
+            let mut words = part.split_whitespace();
+            if words.next() != Some("cd") {
+                continue;
+            }
+            let target = match words.next() {
+                None => Some(PathBuf::from("~")),
+                Some("-") => self.previous.clone(),
+                Some(dir) if dir.starts_with('/') || dir.starts_with('~') => {
+                    Some(PathBuf::from(dir))
+                }
+                // relative to a folder we don't know
+                Some(_) if self.cwd.is_none() => None,
+                Some(dir) => self.cwd.as_ref().map(|c| c.join(dir)),
+            };
+            self.previous = self.cwd.take();
+            self.cwd = target.map(|t| Self::normalize(&t));
+        }
+    }
+
+    fn normalize(path: &Path) -> PathBuf {
+        let mut result = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::ParentDir => {
+                    result.pop();
+                }
+                Component::CurDir => {}
+                c => result.push(c),
+            }
+        }
+        result
+    }
+
+    fn cwd(&self) -> Option<String> {
+        self.cwd.as_ref().map(|c| c.to_string_lossy().to_string())
+    }
+}
+
+impl ShellHistory {
+    // zsh "metafies" some bytes in its history file: 0x83 followed
+    // by the byte xor 32
+    fn unmetafy(contents: &[u8]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(contents.len());
+        let mut bytes = contents.iter();
+        while let Some(b) = bytes.next() {
+            match (b, bytes.as_slice().first()) {
+                (0x83, Some(next)) => {
+                    result.push(next ^ 32);
+                    bytes.next();
+                }
+                _ => result.push(*b),
+            }
+        }
+        result
+    }
+
+    // : 1584957600:0;git status
+    // multi-line commands have a trailing backslash on all lines but the last
+    fn parse_zsh_history(contents: &[u8]) -> Vec<HistoryEntry> {
+        let line_regex = Regex::new(r"^: *(\d+):\d+;(.*)$").unwrap();
+        let contents = String::from_utf8_lossy(&Self::unmetafy(contents)).to_string();
+        let mut entries: Vec<HistoryEntry> = vec![];
+        for line in contents.lines() {
+            if let Some(last) = entries.last_mut().filter(|e| e.command.ends_with('\\')) {
+                last.command.pop();
+                last.command.push('\n');
+                last.command.push_str(line);
+            } else if let Some(caps) = line_regex.captures(line) {
+                if let Ok(timestamp) = caps[1].parse() {
+                    entries.push(HistoryEntry::new(timestamp, caps[2].to_string()));
+                }
+            }
+        }
+        entries
+    }
+
+    // #1584957600
+    // git status
+    // lines before the first timestamp (written before HISTTIMEFORMAT was set) are skipped
+    fn parse_bash_history(contents: &str) -> Vec<HistoryEntry> {
+        let mut entries: Vec<HistoryEntry> = vec![];
+        let mut in_entry = false;
+        for line in contents.lines() {
+            let timestamp = line
+                .strip_prefix('#')
+                .filter(|t| !t.is_empty() && t.chars().all(|c| c.is_ascii_digit()))
+                .and_then(|t| t.parse().ok());
+            match (timestamp, entries.last_mut()) {
+                (Some(timestamp), _) => {
+                    entries.push(HistoryEntry::new(timestamp, String::new()));
+                    in_entry = true;
+                }
+                (None, Some(last)) if in_entry => {
+                    if !last.command.is_empty() {
+                        last.command.push('\n');
+                    }
+                    last.command.push_str(line);
+                }
+                _ => {}
+            }
+        }
+        entries.retain(|e| !e.command.is_empty());
+        entries
+    }
+
+    // - cmd: git status
+    //   when: 1584957600
+    //   paths:
+    //     - src
+    fn parse_fish_history(contents: &str) -> Vec<HistoryEntry> {
+        let mut entries = vec![];
+        let mut command: Option<String> = None;
+        for line in contents.lines() {
+            if let Some(cmd) = line.strip_prefix("- cmd: ") {
+                command = Some(cmd.replace("\\n", "\n").replace("\\\\", "\\"));
+            } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+                if let (Some(cmd), Ok(timestamp)) = (command.take(), when.trim().parse()) {
+                    entries.push(HistoryEntry::new(timestamp, cmd));
+                }
+            }
+        }
+        entries
+    }
+
+    fn read_atuin_history(
+        db_path: &str,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Result<Vec<HistoryEntry>> {
+        let conn = rusqlite::Connection::open_with_flags(
+            db_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        // atuin timestamps are in nanoseconds
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, command, cwd FROM history \
+             WHERE timestamp >= ?1 AND timestamp < ?2 ORDER BY timestamp",
+        )?;
+        let rows = stmt.query_map(
+            rusqlite::params![
+                day_start.timestamp_nanos(),
+                next_day_start.timestamp_nanos()
+            ],
+            |row| {
+                let timestamp: i64 = row.get(0)?;
+                Ok(HistoryEntry {
+                    time: Local.timestamp_nanos(timestamp),
+                    command: row.get(1)?,
+                    cwd: row.get(2)?,
+                })
+            },
+        )?;
+        let mut entries = vec![];
+        for row in rows {
+            let entry = row?;
+            // deleted entries are blanked
+            if !entry.command.is_empty() {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn track_cwd(entries: &mut [HistoryEntry]) {
+        let mut tracker = CwdTracker::default();
+        for entry in entries.iter_mut() {
+            entry.cwd = tracker.cwd();
+            tracker.apply(&entry.command);
+        }
+    }
+
+    fn read_history(
+        shell_config: &ShellHistoryConfig,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Result<Vec<HistoryEntry>> {
+        let mut entries = match shell_config.shell {
+            ShellKind::Atuin => {
+                return Self::read_atuin_history(
+                    &shell_config.history_file,
+                    day_start,
+                    next_day_start,
+                )
+            }
+            ShellKind::Zsh => Self::parse_zsh_history(&std::fs::read(&shell_config.history_file)?),
+            ShellKind::Bash => {
+                Self::parse_bash_history(&std::fs::read_to_string(&shell_config.history_file)?)
+            }
+            ShellKind::Fish => {
+                Self::parse_fish_history(&std::fs::read_to_string(&shell_config.history_file)?)
+            }
+        };
+        // the history is chronological, but with several terminals open, entries can
+        // be a little out of order
+        entries.sort_by_key(|e| e.time);
+        Self::track_cwd(&mut entries);
+        entries.retain(|e| e.time >= day_start && e.time < next_day_start);
+        Ok(entries)
+    }
+
+    // consecutive commands in the same folder, without long pauses
+    fn group_sessions(entries: Vec<HistoryEntry>) -> Vec<Vec<HistoryEntry>> {
+        let mut sessions: Vec<Vec<HistoryEntry>> = vec![];
+        for entry in entries {
+            match sessions.last_mut() {
+                Some(session)
+                    if session.last().map(|e| &e.cwd) == Some(&entry.cwd)
+                        && entry.time - session.last().unwrap().time
+                            <= chrono::Duration::minutes(SESSION_GAP_MINS) =>
+                {
+                    session.push(entry)
+                }
+                _ => sessions.push(vec![entry]),
+            }
+        }
+        sessions
+    }
+
+    fn display_folder(cwd: &Option<String>) -> String {
+        let home = dirs::home_dir().map(|h| h.to_string_lossy().to_string());
+        match (cwd, home) {
+            (Some(cwd), Some(home)) if cwd.starts_with(&home) => cwd.replacen(&home, "~", 1),
+            (Some(cwd), _) => cwd.clone(),
+            (None, _) => "unknown folder".to_string(),
+        }
+    }
+
+    fn build_session_event(session: &[HistoryEntry]) -> Event {
+        let first = session.first().unwrap();
+        let last = session.last().unwrap();
+        let folder = Self::display_folder(&first.cwd);
+        let commands = session
+            .iter()
+            .map(|e| {
+                format!(
+                    "{}  {}",
+                    e.time.format("%H:%M"),
+                    glib::markup_escape_text(&e.command)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Event::new(
+            "Shell",
+            Icon::TERMINAL,
+            first.time.time(),
+            folder.clone(),
+            format!("{} command(s) in {}", session.len(), folder),
+            EventBody::Markup(
+                format!("<span font-family=\"monospace\">{}</span>", commands),
+                WordWrapMode::NoWordWrap,
+            ),
+            Some(format!("Until {}", last.time.format("%H:%M"))),
+        )
+    }
+}
+
+impl EventProvider for ShellHistory {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (SHELL_KEY, ConfigType::Combo),
+            (HISTORY_FILE_KEY, ConfigType::File),
+            (IGNORE_REGEX_KEY, ConfigType::Text("")),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Shell history"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::TERMINAL
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.shell_history.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        if field_name == SHELL_KEY {
+            Ok(ShellKind::ALL
+                .iter()
+                .map(|k| k.desc().to_string())
+                .collect())
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                SHELL_KEY,
+                config.shell_history[config_name].shell.desc().to_string(),
+            ),
+            (
+                HISTORY_FILE_KEY,
+                config.shell_history[config_name].history_file.to_string(),
+            ),
+            (
+                IGNORE_REGEX_KEY,
+                config.shell_history[config_name].ignore_regex.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.shell_history.insert(
+            config_name,
+            ShellHistoryConfig {
+                shell: ShellKind::from_desc(&config_values.remove(SHELL_KEY).unwrap_or_default()),
+                history_file: config_values.remove(HISTORY_FILE_KEY).unwrap(),
+                ignore_regex: config_values.remove(IGNORE_REGEX_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.shell_history.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let shell_config = &config.shell_history[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let ignore_regex = if shell_config.ignore_regex.is_empty() {
+            None
+        } else {
+            Some(Regex::new(&shell_config.ignore_regex)?)
+        };
+        let mut entries = Self::read_history(shell_config, day_start, next_day_start)?;
+        if let Some(regex) = ignore_regex {
+            entries.retain(|e| !regex.is_match(&e.command));
+        }
+        Ok(Self::group_sessions(entries)
+            .iter()
+            .map(|s| Self::build_session_event(s))
+            .collect())
+    }
+}
+
+#[test]
+fn it_parses_zsh_history() {
+    let entries = ShellHistory::parse_zsh_history(
+        b": 1584957600:0;cd /tmp\n: 1584957660:2;echo a \\\nb\n: 1584957720:0;ls \xc4\x83\xa3\n",
+    );
+    assert_eq!(
+        vec!["cd /tmp", "echo a \nb", "ls ă"],
+        entries
+            .iter()
+            .map(|e| e.command.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Local.timestamp(1584957660, 0), entries[1].time);
+}
+
+#[test]
+fn it_parses_bash_history() {
+    let entries =
+        ShellHistory::parse_bash_history("ls\n#1584957600\ngit status\n#1584957660\nmake\n");
+    assert_eq!(
+        vec!["git status", "make"],
+        entries
+            .iter()
+            .map(|e| e.command.as_str())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn it_parses_fish_history() {
+    let entries = ShellHistory::parse_fish_history(
+        "- cmd: cd src\n  when: 1584957600\n- cmd: echo a\\nb\n  when: 1584957660\n  paths:\n    - b\n",
+    );
+    assert_eq!(
+        vec!["cd src", "echo a\nb"],
+        entries
+            .iter()
+            .map(|e| e.command.as_str())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn it_groups_commands_by_folder() {
+    let mut entries = vec![
+        HistoryEntry::new(1584957600, "cd /home/me/proj".to_string()),
+        HistoryEntry::new(1584957660, "make".to_string()),
+        HistoryEntry::new(1584957720, "cd ../other && ls".to_string()),
+        HistoryEntry::new(1584957780, "git status".to_string()),
+        // more than 30 minutes later
+        HistoryEntry::new(1584960000, "git log".to_string()),
+        HistoryEntry::new(1584960060, "cd -".to_string()),
+        HistoryEntry::new(1584960120, "make".to_string()),
+    ];
+    ShellHistory::track_cwd(&mut entries);
+    let sessions = ShellHistory::group_sessions(entries);
+    assert_eq!(
+        vec![
+            (None, 1),
+            (Some("/home/me/proj".to_string()), 2),
+            (Some("/home/me/other".to_string()), 1),
+            (Some("/home/me/other".to_string()), 2),
+            (Some("/home/me/proj".to_string()), 1),
+        ],
+        sessions
+            .iter()
+            .map(|s| (s[0].cwd.clone(), s.len()))
+            .collect::<Vec<_>>()
+    );
+}
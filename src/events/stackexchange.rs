@@ -4,6 +4,8 @@
 // so I have to scrap the website
 // my understand is that scraping is acceptable if there is no alternative:
 // https://meta.stackexchange.com/a/446/218504
+// Everything else (questions, answers, comments, edits...) comes from the API,
+// and votes are an optional extra, if the user gives us the login.
 use super::events::{ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode};
 use crate::config::Config;
 use crate::icons::*;
@@ -11,19 +13,211 @@ use chrono::prelude::*;
 use core::time::Duration;
 use std::collections::HashMap;
 
+const API_URL: &str = "https://api.stackexchange.com/2.3";
+const PAGE_SIZE: usize = 100;
+
 #[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
 pub struct StackExchangeConfig {
+    // the network-wide account id, covers all the sites of the user
+    #[serde(default)] // account_id was added later, after 0.5.4
+    pub account_id: String,
+    // comma-separated site domains, empty for all the sites of the account
+    #[serde(default)] // sites was added later, after 0.5.4
+    pub sites: String,
+    // the site url, username & password are only used to scrape the votes
     pub exchange_site_url: String,
     pub username: String,
     pub password: String,
 }
 
 pub struct StackExchange;
-const EXCHANGE_SITE_URL: &str = "Stack Exchange site url";
-const USERNAME_KEY: &str = "username";
-const PASSWORD_KEY: &str = "password";
+const ACCOUNT_ID_KEY: &str = "Account id";
+const SITES_KEY: &str = "Sites (empty for all)";
+const EXCHANGE_SITE_URL: &str = "Stack Exchange site url (for votes)";
+const USERNAME_KEY: &str = "username (optional, for votes)";
+const PASSWORD_KEY: &str = "password (optional, for votes)";
+
+#[derive(serde_derive::Deserialize, Debug)]
+struct ApiResponse<T> {
+    items: Vec<T>,
+    #[serde(default)]
+    has_more: bool,
+    // seconds to wait before calling the same method again
+    backoff: Option<u64>,
+}
+
+#[derive(serde_derive::Deserialize, Clone, Debug)]
+struct AssociatedAccount {
+    site_name: String,
+    site_url: String,
+    user_id: u64,
+}
+
+#[derive(serde_derive::Deserialize, Clone, Debug)]
+struct TimelineItem {
+    timeline_type: String,
+    post_type: Option<String>,
+    creation_date: i64,
+    title: Option<String>,
+    detail: Option<String>,
+    link: Option<String>,
+}
 
 impl StackExchange {
+    // the API always compresses its responses, reqwest decodes them for us
+    fn api_get<T>(client: &reqwest::blocking::Client, url: &str) -> Result<ApiResponse<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let json = client.get(url).send()?.error_for_status()?.text()?;
+        log::debug!("{}: {}", url, json);
+        let resp = serde_json::from_str::<ApiResponse<T>>(&json)
+            .map_err(|e| format!("Failed parsing json {} {:?} -- {}", url, e, json))?;
+        if let Some(backoff) = resp.backoff {
+            log::warn!("Stack Exchange API asks to wait {}s", backoff);
+            std::thread::sleep(Duration::from_secs(backoff));
+        }
+        Ok(resp)
+    }
+
+    fn call_api<T>(client: &reqwest::blocking::Client, url_path: &str) -> Result<Vec<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let separator = if url_path.contains('?') { '&' } else { '?' };
+        let mut records = vec![];
+        let mut page = 1;
+        loop {
+            let mut resp = Self::api_get::<T>(
+                client,
+                &format!(
+                    "{}{}{}pagesize={}&page={}",
+                    API_URL, url_path, separator, PAGE_SIZE, page
+                ),
+            )?;
+            records.append(&mut resp.items);
+            if !resp.has_more {
+                break;
+            }
+            page += 1;
+        }
+        Ok(records)
+    }
+
+    fn site_domain(site_url: &str) -> &str {
+        site_url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/')
+    }
+
+    fn is_selected_site(stackexchange_config: &StackExchangeConfig, site_url: &str) -> bool {
+        let domain = Self::site_domain(site_url);
+        stackexchange_config.sites.trim().is_empty()
+            || stackexchange_config
+                .sites
+                .split(',')
+                .any(|s| Self::site_domain(s.trim()) == domain)
+    }
+
+    // the API returns html-encoded text
+    fn html_decode(text: &str) -> String {
+        text.replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&")
+    }
+
+    fn build_timeline_event(site_name: &str, item: &TimelineItem) -> Option<Event> {
+        let post_desc = match item.post_type.as_deref() {
+            Some("question") => "question",
+            _ => "answer",
+        };
+        let (icon, desc) = match item.timeline_type.as_str() {
+            "asked" => (Icon::COMMENT_DOTS, "Asked".to_string()),
+            "answered" => (Icon::COMMENT_DOTS, "Answered".to_string()),
+            "commented" => (Icon::COMMENT_DOTS, format!("Commented on {}", post_desc)),
+            "revision" => (Icon::EDIT, format!("Edited {}", post_desc)),
+            "suggested" => (Icon::EDIT, format!("Suggested edit on {}", post_desc)),
+            "accepted" => (Icon::CHECK_SQUARE, "Accepted answer".to_string()),
+            "reviewed" => (Icon::TASKS, "Reviewed".to_string()),
+            "badge" => (Icon::THUMBS_UP, "Badge".to_string()),
+            _ => return None,
+        };
+        let time = Local.timestamp(item.creation_date, 0).time();
+        let title = Self::html_decode(item.title.as_deref().unwrap_or(""));
+        let detail = Self::html_decode(item.detail.as_deref().unwrap_or(""));
+        let link = item
+            .link
+            .as_ref()
+            .map(|l| {
+                format!(
+                    "<a href=\"{}\">Open in the browser</a>\n\n",
+                    glib::markup_escape_text(l)
+                )
+            })
+            .unwrap_or_default();
+        Some(Event::new(
+            "S.Exch",
+            icon,
+            time,
+            title.clone(),
+            format!("{}: {}", desc, title),
+            EventBody::Markup(
+                format!(
+                    "{}{}\n{}",
+                    link,
+                    glib::markup_escape_text(site_name),
+                    glib::markup_escape_text(&detail)
+                ),
+                WordWrapMode::WordWrap,
+            ),
+            Some(desc),
+        ))
+    }
+
+    fn get_api_events(
+        stackexchange_config: &StackExchangeConfig,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Result<Vec<Event>> {
+        let client = reqwest::blocking::ClientBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(30))
+            .connection_verbose(true)
+            .build()?;
+        let accounts = Self::call_api::<AssociatedAccount>(
+            &client,
+            &format!(
+                "/users/{}/associated",
+                stackexchange_config.account_id.trim()
+            ),
+        )?;
+        let mut events = vec![];
+        for account in accounts
+            .iter()
+            .filter(|a| Self::is_selected_site(stackexchange_config, &a.site_url))
+        {
+            let items = Self::call_api::<TimelineItem>(
+                &client,
+                &format!(
+                    "/users/{}/timeline?site={}&fromdate={}&todate={}",
+                    account.user_id,
+                    Self::site_domain(&account.site_url),
+                    day_start.timestamp(),
+                    next_day_start.timestamp() - 1
+                ),
+            )?;
+            events.extend(
+                items
+                    .iter()
+                    .filter_map(|i| Self::build_timeline_event(&account.site_name, i)),
+            );
+        }
+        Ok(events)
+    }
+
     fn url_escape(msg: &str) -> String {
         msg.replace("/", "%2f").replace(":", "%3a")
     }
@@ -223,6 +417,8 @@ impl EventProvider for StackExchange {
 
     fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
         vec![
+            (ACCOUNT_ID_KEY, ConfigType::Text("")),
+            (SITES_KEY, ConfigType::Text("")),
             (
                 EXCHANGE_SITE_URL,
                 ConfigType::Text("https://stackoverflow.com"),
@@ -246,6 +442,14 @@ impl EventProvider for StackExchange {
         config_name: &str,
     ) -> HashMap<&'static str, String> {
         vec![
+            (
+                ACCOUNT_ID_KEY,
+                config.stackexchange[config_name].account_id.to_string(),
+            ),
+            (
+                SITES_KEY,
+                config.stackexchange[config_name].sites.to_string(),
+            ),
             (
                 EXCHANGE_SITE_URL,
                 config.stackexchange[config_name]
@@ -274,6 +478,8 @@ impl EventProvider for StackExchange {
         config.stackexchange.insert(
             config_name,
             StackExchangeConfig {
+                account_id: config_values.remove(ACCOUNT_ID_KEY).unwrap(),
+                sites: config_values.remove(SITES_KEY).unwrap(),
                 exchange_site_url: config_values.remove(EXCHANGE_SITE_URL).unwrap(),
                 username: config_values.remove(USERNAME_KEY).unwrap(),
                 password: config_values.remove(PASSWORD_KEY).unwrap(),
@@ -296,17 +502,96 @@ impl EventProvider for StackExchange {
         let day_start = day.and_hms(0, 0, 0);
         let next_day_start = day_start + chrono::Duration::days(1);
 
-        let votes_page_html =
+        let mut events = if stackexchange_config.account_id.trim().is_empty() {
+            vec![]
+        } else {
+            Self::get_api_events(stackexchange_config, day_start, next_day_start)?
+        };
+        if stackexchange_config.username.is_empty() {
+            return Ok(events);
+        }
+
+        // the votes are an optional extra, and scraping is fragile:
+        // report the failure, but keep the events from the API
+        let votes =
             match Config::get_cached_contents(&StackExchange, config_name, &next_day_start)? {
                 Some(t) => Ok(t),
                 None => Self::get_votes_page_html(config_name, stackexchange_config),
-            }?;
-
-        Self::get_votes(
-            &votes_page_html,
-            stackexchange_config,
-            day_start,
-            next_day_start,
-        )
+            }
+            .and_then(|votes_page_html| {
+                Self::get_votes(
+                    &votes_page_html,
+                    stackexchange_config,
+                    day_start,
+                    next_day_start,
+                )
+            });
+        match votes {
+            Ok(mut votes) => events.append(&mut votes),
+            Err(e) if !stackexchange_config.account_id.trim().is_empty() => {
+                log::error!("Failed fetching the stack exchange votes: {}", e);
+                events.push(Event::new(
+                    "S.Exch",
+                    Icon::EXCLAMATION_TRIANGLE,
+                    NaiveTime::from_hms(0, 0, 0),
+                    "Votes are missing".to_string(),
+                    "Stack Exchange: votes are missing".to_string(),
+                    EventBody::PlainText(format!("Failed fetching the votes.\n\n{}", e)),
+                    Some("Incomplete data".to_string()),
+                ));
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(events)
     }
 }
+
+#[test]
+fn it_builds_events_from_the_timeline() {
+    let items: Vec<TimelineItem> = serde_json::from_str(
+        r#"[{"timeline_type":"commented","post_type":"question","creation_date":1584957600,
+             "title":"How to parse &quot;dates&quot; in Rust?","detail":"use chrono &amp; co",
+             "link":"https://stackoverflow.com/questions/1/x#comment2_1"},
+            {"timeline_type":"accepted","post_type":"answer","creation_date":1584961200,
+             "title":"Sort a vec","link":"https://stackoverflow.com/q/3"},
+            {"timeline_type":"unknown_type","creation_date":1584961200}]"#,
+    )
+    .unwrap();
+    let events: Vec<_> = items
+        .iter()
+        .filter_map(|i| StackExchange::build_timeline_event("Stack Overflow", i))
+        .collect();
+    assert_eq!(2, events.len());
+    assert_eq!(
+        "Commented on question: How to parse \"dates\" in Rust?",
+        events[0].event_contents_header
+    );
+    assert!(events[0]
+        .event_contents_body
+        .as_str()
+        .contains("Stack Overflow\nuse chrono &amp; co"));
+    assert_eq!(Icon::CHECK_SQUARE, events[1].event_type_icon);
+}
+
+#[test]
+fn it_filters_the_selected_sites() {
+    let config = |sites: &str| StackExchangeConfig {
+        account_id: "1".to_string(),
+        sites: sites.to_string(),
+        exchange_site_url: "".to_string(),
+        username: "".to_string(),
+        password: "".to_string(),
+    };
+    assert!(StackExchange::is_selected_site(
+        &config(""),
+        "https://superuser.com"
+    ));
+    assert!(StackExchange::is_selected_site(
+        &config("stackoverflow.com, superuser.com"),
+        "https://superuser.com"
+    ));
+    assert!(!StackExchange::is_selected_site(
+        &config("stackoverflow.com"),
+        "https://superuser.com"
+    ));
+}
@@ -0,0 +1,320 @@
+// the events are shaped like the git ones. svn log doesn't give
+// line counts, so we list the changed paths instead of a diffstat.
+// note that svn log contacts the server of the working copy.
+use super::events::{
+    run_command, ConfigType, Event, EventBody, EventProvider, Result, WordWrapMode,
+};
+use super::git::Git;
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct SvnConfig {
+    pub working_copy: String, // Path
+    pub commit_author: String,
+}
+
+#[derive(PartialEq, Debug)]
+struct SvnCommit {
+    revision: u64,
+    author: String,
+    date: DateTime<Local>,
+    // (action, path) with action being A, D, M or R
+    paths: Vec<(String, String)>,
+    msg: String,
+}
+
+impl SvnCommit {
+    fn summary(&self) -> &str {
+        self.msg.lines().next().unwrap_or("")
+    }
+}
+
+pub struct Svn;
+const WORKING_COPY_KEY: &str = "Working copy folder";
+const COMMIT_AUTHOR_KEY: &str = "Commit Author";
+
+impl Svn {
+    fn xml_decode(text: &str) -> String {
+        text.replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&")
+    }
+
+    // the svn xml output is simple & regular enough that regexes will do
+    fn parse_log(xml: &str) -> Vec<SvnCommit> {
+        let entry_regex =
+            Regex::new(r#"(?s)<logentry\s+revision="(\d+)"\s*>(.*?)</logentry>"#).unwrap();
+        let tag_regex = |tag: &str| Regex::new(&format!(r"(?s)<{}>(.*?)</{}>", tag, tag)).unwrap();
+        let author_regex = tag_regex("author");
+        let date_regex = tag_regex("date");
+        let msg_regex = tag_regex("msg");
+        let path_regex = Regex::new(r"(?s)<path\s([^>]*)>(.*?)</path>").unwrap();
+        let action_regex = Regex::new(r#"action="(\w)""#).unwrap();
+        entry_regex
+            .captures_iter(xml)
+            .filter_map(|entry| {
+                let contents = &entry[2];
+                let tag_contents = |regex: &Regex| {
+                    regex
+                        .captures(contents)
+                        .map(|c| Self::xml_decode(&c[1]))
+                        .unwrap_or_default()
+                };
+                Some(SvnCommit {
+                    revision: entry[1].parse().ok()?,
+                    author: tag_contents(&author_regex),
+                    date: DateTime::parse_from_rfc3339(&tag_contents(&date_regex))
+                        .ok()?
+                        .with_timezone(&Local),
+                    paths: path_regex
+                        .captures_iter(contents)
+                        .map(|p| {
+                            (
+                                action_regex
+                                    .captures(&p[1])
+                                    .map(|a| a[1].to_string())
+                                    .unwrap_or_default(),
+                                Self::xml_decode(&p[2]),
+                            )
+                        })
+                        .collect(),
+                    msg: tag_contents(&msg_regex),
+                })
+            })
+            .collect()
+    }
+
+    // following the standard trunk/branches/tags layout
+    fn branch_name(path: &str) -> Option<&str> {
+        let mut components = path.split('/');
+        while let Some(component) = components.next() {
+            match component {
+                "trunk" => return Some("trunk"),
+                "branches" | "tags" => return components.next(),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn build_event(commit: &SvnCommit) -> Event {
+        let contents_header = commit.summary().to_string();
+        let message_contents = glib::markup_escape_text(
+            commit
+                .msg
+                .strip_prefix(&contents_header)
+                .unwrap_or(&commit.msg),
+        )
+        .to_string();
+        let branch = commit
+            .paths
+            .first()
+            .and_then(|(_, p)| Self::branch_name(p))
+            .unwrap_or("");
+        let changed_paths = commit
+            .paths
+            .iter()
+            .map(|(action, path)| format!("{} {}", action, path))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let contents = format!(
+            "r{} {}\n<span font-family=\"monospace\">{}\n\n{}</span>",
+            commit.revision,
+            glib::markup_escape_text(branch),
+            message_contents,
+            glib::markup_escape_text(&changed_paths)
+        );
+        let files: Vec<_> = commit
+            .paths
+            .iter()
+            .map(|(_, p)| PathBuf::from(p.trim_start_matches('/')))
+            .collect();
+        Event::new(
+            "SVN",
+            Icon::CODE_BRANCH,
+            commit.date.time(),
+            contents_header.clone(),
+            contents_header,
+            EventBody::Markup(contents, WordWrapMode::NoWordWrap),
+            Some(Git::get_files_root(&files)).filter(|r| !r.is_empty()),
+        )
+    }
+}
+
+impl EventProvider for Svn {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (WORKING_COPY_KEY, ConfigType::Folder),
+            (COMMIT_AUTHOR_KEY, ConfigType::Text("")),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "SVN"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::CODE_BRANCH
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.svn.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        _field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                WORKING_COPY_KEY,
+                config.svn[config_name].working_copy.to_string(),
+            ),
+            (
+                COMMIT_AUTHOR_KEY,
+                config.svn[config_name].commit_author.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.svn.insert(
+            config_name,
+            SvnConfig {
+                working_copy: config_values.remove(WORKING_COPY_KEY).unwrap(),
+                commit_author: config_values.remove(COMMIT_AUTHOR_KEY).unwrap(),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.svn.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let svn_config = &config.svn[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let svn_date_format = "{%Y-%m-%dT%H:%M:%S%z}";
+        let output = run_command(Command::new("svn").args([
+            "log",
+            "--xml",
+            "--verbose",
+            "--non-interactive",
+            "--revision",
+            &format!(
+                "{}:{}",
+                day_start.format(svn_date_format),
+                next_day_start.format(svn_date_format)
+            ),
+            &svn_config.working_copy,
+        ]))?;
+        // svn log has no author filter, and a date revision range
+        // includes the last commit before the start date
+        Ok(Self::parse_log(&output)
+            .iter()
+            .filter(|c| {
+                (svn_config.commit_author.is_empty() || c.author == svn_config.commit_author)
+                    && c.date >= day_start
+                    && c.date < next_day_start
+            })
+            .map(Self::build_event)
+            .collect())
+    }
+}
+
+#[test]
+fn it_parses_svn_log_xml() {
+    let commits = Svn::parse_log(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<log>
+<logentry
+   revision="1234">
+<author>me</author>
+<date>2020-03-23T08:00:00.123456Z</date>
+<paths>
+<path
+   prop-mods="false"
+   text-mods="true"
+   kind="file"
+   action="M">/branches/release-2.1/src/parse/lexer.c</path>
+<path
+   prop-mods="false"
+   text-mods="true"
+   kind="file"
+   action="A">/branches/release-2.1/src/parse/tokens.h</path>
+</paths>
+<msg>fix the lexer &amp; add tokens
+
+crashed on empty files</msg>
+</logentry>
+<logentry
+   revision="1235">
+<author>someone</author>
+<date>2020-03-23T09:00:00.000000Z</date>
+<paths>
+<path
+   action="D"
+   kind="dir">/trunk/old</path>
+</paths>
+<msg></msg>
+</logentry>
+</log>"#,
+    );
+    assert_eq!(2, commits.len());
+    assert_eq!(1234, commits[0].revision);
+    assert_eq!("me", commits[0].author);
+    assert_eq!(
+        vec![
+            (
+                "M".to_string(),
+                "/branches/release-2.1/src/parse/lexer.c".to_string()
+            ),
+            (
+                "A".to_string(),
+                "/branches/release-2.1/src/parse/tokens.h".to_string()
+            )
+        ],
+        commits[0].paths
+    );
+    assert_eq!(Some("trunk"), Svn::branch_name(&commits[1].paths[0].1));
+    let event = Svn::build_event(&commits[0]);
+    assert_eq!("fix the lexer & add tokens", event.event_info);
+    assert_eq!(
+        Some("branches/release-2.1/src/parse".to_string()),
+        event.event_extra_details
+    );
+    assert!(matches!(
+        event.event_contents_body,
+        EventBody::Markup(ref s, _) if s.starts_with("r1234 release-2.1\n") && s.contains("M /branches/release-2.1/src/parse/lexer.c")
+    ));
+}
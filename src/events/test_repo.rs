@@ -0,0 +1,70 @@
+// the git event tests build small throwaway repositories
+use git2::{Commit, Oid, Repository, Signature};
+use std::path::{Path, PathBuf};
+
+/// a folder in the temp directory, removed on drop
+pub struct TempFolder {
+    pub path: PathBuf,
+}
+
+impl TempFolder {
+    pub fn new(name: &str) -> TempFolder {
+        let path = std::env::temp_dir().join(format!("cigale_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        TempFolder { path }
+    }
+}
+
+impl Drop for TempFolder {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+pub struct TestRepo {
+    pub repo: Repository,
+    pub folder: TempFolder,
+}
+
+impl TestRepo {
+    pub fn new(name: &str) -> TestRepo {
+        let folder = TempFolder::new(name);
+        let repo = Repository::init(&folder.path).unwrap();
+        TestRepo { repo, folder }
+    }
+
+    pub fn path_str(&self) -> &str {
+        self.folder.path.to_str().unwrap()
+    }
+
+    /// all the commits are by Jane, at the same time
+    pub fn signature() -> Signature<'static> {
+        Signature::new(
+            "Jane",
+            "jane@example.com",
+            &git2::Time::new(1_600_000_000, 0),
+        )
+        .unwrap()
+    }
+
+    /// commit the current index, without updating any reference
+    pub fn commit(&self, parents: &[&Commit], msg: &str) -> Commit<'_> {
+        let sig = Self::signature();
+        let tree_id = self.repo.index().unwrap().write_tree().unwrap();
+        let tree = self.repo.find_tree(tree_id).unwrap();
+        let oid: Oid = self
+            .repo
+            .commit(None, &sig, &sig, msg, &tree, parents)
+            .unwrap();
+        self.repo.find_commit(oid).unwrap()
+    }
+
+    /// write the file, add it to the index, and commit
+    pub fn commit_file(&self, path: &str, contents: &str, parents: &[&Commit]) -> Commit<'_> {
+        std::fs::write(self.folder.path.join(path), contents).unwrap();
+        let mut index = self.repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+        self.commit(parents, "commit")
+    }
+}
@@ -0,0 +1,466 @@
+// timewarrior records intervals with a start & an end, so unlike most
+// providers we know how long we worked on something. We read its data
+// files directly, and ask taskwarrior for the tasks through `task export`.
+use super::events::{
+    format_duration, run_command, ConfigType, Event, EventBody, EventProvider, Result,
+};
+use crate::config::Config;
+use crate::icons::*;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug)]
+pub struct TimewarriorConfig {
+    pub data_folder: String, // Path, may be empty to only get tasks
+    pub include_tasks: bool,
+}
+
+pub struct Timewarrior;
+const DATA_FOLDER_KEY: &str = "Timewarrior data folder";
+const INCLUDE_TASKS_KEY: &str = "Include Taskwarrior tasks";
+
+// both timewarrior & taskwarrior store UTC dates in this format
+const WARRIOR_DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(PartialEq, Debug)]
+struct Interval {
+    start: DateTime<Local>,
+    // None for the interval being currently tracked
+    end: Option<DateTime<Local>>,
+    tags: Vec<String>,
+    annotation: Option<String>,
+}
+
+#[derive(serde_derive::Deserialize, Debug)]
+struct Task {
+    description: String,
+    status: String,
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(deserialize_with = "warrior_date::deserialize")]
+    entry: DateTime<Local>,
+    #[serde(default, deserialize_with = "warrior_date::deserialize_opt")]
+    modified: Option<DateTime<Local>>,
+    #[serde(default, deserialize_with = "warrior_date::deserialize_opt")]
+    end: Option<DateTime<Local>>,
+}
+
+mod warrior_date {
+    use chrono::prelude::*;
+    use serde::{self, Deserialize, Deserializer};
+
+    pub fn parse(s: &str) -> Option<DateTime<Local>> {
+        Utc.datetime_from_str(s, super::WARRIOR_DATE_FORMAT)
+            .ok()
+            .map(|d| d.with_timezone(&Local))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Local>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        parse(&s).ok_or_else(|| serde::de::Error::custom(format!("Invalid date: {}", s)))
+    }
+
+    pub fn deserialize_opt<'de, D>(deserializer: D) -> Result<Option<DateTime<Local>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize(deserializer).map(Some)
+    }
+}
+
+impl Timewarrior {
+    // split on whitespace, except in double-quoted strings,
+    // returning whether each token was quoted
+    fn tokenize(line: &str) -> Vec<(String, bool)> {
+        let mut tokens = vec![];
+        let mut chars = line.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '"' {
+                chars.next();
+                let mut token = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => token.extend(chars.next()),
+                        _ => token.push(c),
+                    }
+                }
+                tokens.push((token, true));
+            } else {
+                let mut token = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push((token, false));
+            }
+        }
+        tokens
+    }
+
+    // inc 20200323T080000Z - 20200323T093000Z # cigale "code review" # "fix the parser"
+    fn parse_interval(line: &str) -> Option<Interval> {
+        let tokens = Self::tokenize(line);
+        let mut sections = tokens.split(|(t, quoted)| !quoted && t == "#");
+        let times: Vec<_> = sections.next()?.iter().map(|(t, _)| t.as_str()).collect();
+        let tags = sections
+            .next()
+            .map(|s| s.iter().map(|(t, _)| t.clone()).collect())
+            .unwrap_or_default();
+        let annotation = sections
+            .next()
+            .map(|s| {
+                s.iter()
+                    .map(|(t, _)| t.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|a| !a.is_empty());
+        let (start, end) = match times.as_slice() {
+            ["inc", start] => (warrior_date::parse(start)?, None),
+            ["inc", start, "-", end] => {
+                (warrior_date::parse(start)?, Some(warrior_date::parse(end)?))
+            }
+            _ => return None,
+        };
+        Some(Interval {
+            start,
+            end,
+            tags,
+            annotation,
+        })
+    }
+
+    // one file per month. An interval could start in the previous month's file.
+    fn read_intervals(
+        data_folder: &Path,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Result<Vec<Interval>> {
+        // the files are named after the UTC month
+        let utc_day_start = day_start.with_timezone(&Utc).date();
+        let first_of_month = utc_day_start.with_day(1).unwrap();
+        let mut months = vec![
+            first_of_month.pred().format("%Y-%m").to_string(),
+            first_of_month.format("%Y-%m").to_string(),
+            // the local day may end in the next UTC month
+            next_day_start
+                .with_timezone(&Utc)
+                .format("%Y-%m")
+                .to_string(),
+        ];
+        months.dedup();
+        let mut intervals = vec![];
+        for month in &months {
+            let path = data_folder.join(format!("{}.data", month));
+            if !path.exists() {
+                continue;
+            }
+            intervals.extend(
+                std::fs::read_to_string(path)?
+                    .lines()
+                    .filter_map(Self::parse_interval)
+                    .filter(|i| {
+                        i.start < next_day_start && i.end.map(|e| e > day_start).unwrap_or(true)
+                    }),
+            );
+        }
+        intervals.sort_by_key(|i| i.start);
+        intervals.dedup();
+        Ok(intervals)
+    }
+
+    fn build_interval_event(
+        interval: &Interval,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Event {
+        // an interval spanning midnight is cut to the day
+        let start = interval.start.max(day_start);
+        let end = interval.end.unwrap_or_else(Local::now).min(next_day_start);
+        let tags = interval.tags.join(", ");
+        let title = interval.annotation.clone().unwrap_or_else(|| tags.clone());
+        let end_desc = match interval.end {
+            Some(_) => end.format("%H:%M").to_string(),
+            None => "now (still tracking)".to_string(),
+        };
+        Event::new(
+            "Timewarrior",
+            Icon::CLOCK,
+            start.time(),
+            title.clone(),
+            title,
+            EventBody::PlainText(format!(
+                "Tags: {}\nFrom {} to {}",
+                tags,
+                start.format("%H:%M"),
+                end_desc
+            )),
+            Some(format_duration(end - start)),
+        )
+    }
+
+    fn read_tasks(
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Result<Vec<Task>> {
+        let date_filter = |field: &str| {
+            vec![
+                "(".to_string(),
+                format!("{}.after:{}", field, day_start.format("%Y-%m-%dT%H:%M:%S")),
+                "and".to_string(),
+                format!(
+                    "{}.before:{}",
+                    field,
+                    next_day_start.format("%Y-%m-%dT%H:%M:%S")
+                ),
+                ")".to_string(),
+            ]
+        };
+        let output = run_command(
+            Command::new("task")
+                .args([
+                    "rc.verbose=nothing",
+                    "rc.json.array=on",
+                    "rc.confirmation=off",
+                ])
+                .args(date_filter("end"))
+                .arg("or")
+                .args(date_filter("modified"))
+                .arg("export"),
+        )?;
+        Ok(serde_json::from_str(&output)?)
+    }
+
+    fn build_task_event(
+        task: &Task,
+        day_start: DateTime<Local>,
+        next_day_start: DateTime<Local>,
+    ) -> Option<Event> {
+        let is_on_day = |d: &DateTime<Local>| *d >= day_start && *d < next_day_start;
+        let (icon, desc, time) = match (task.status.as_str(), task.end) {
+            ("completed", Some(end)) if is_on_day(&end) => (Icon::CHECK_SQUARE, "Completed", end),
+            ("deleted", Some(end)) if is_on_day(&end) => (Icon::TRASH_ALT, "Deleted", end),
+            _ if is_on_day(&task.entry) => (Icon::TASKS, "Created", task.entry),
+            _ => (Icon::TASKS, "Modified", task.modified.filter(is_on_day)?),
+        };
+        let project = task.project.as_deref().unwrap_or("");
+        Some(Event::new(
+            "Taskwarrior",
+            icon,
+            time.time(),
+            task.description.clone(),
+            format!("{}: {}", desc, task.description),
+            EventBody::PlainText(format!(
+                "Project: {}\nTags: {}",
+                project,
+                task.tags.join(", ")
+            )),
+            Some(project.to_string()).filter(|p| !p.is_empty()),
+        ))
+    }
+}
+
+impl EventProvider for Timewarrior {
+    fn get_config_fields(&self) -> Vec<(&'static str, ConfigType)> {
+        vec![
+            (DATA_FOLDER_KEY, ConfigType::Folder),
+            (INCLUDE_TASKS_KEY, ConfigType::Checkbox),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        "Timewarrior & Taskwarrior"
+    }
+
+    fn default_icon(&self) -> Icon {
+        Icon::CLOCK
+    }
+
+    fn get_config_names<'a>(&self, config: &'a Config) -> Vec<&'a String> {
+        config.timewarrior.keys().collect()
+    }
+
+    fn field_values(
+        &self,
+        _cur_values: &HashMap<&'static str, String>,
+        _field_name: &'static str,
+    ) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn get_config_values(
+        &self,
+        config: &Config,
+        config_name: &str,
+    ) -> HashMap<&'static str, String> {
+        vec![
+            (
+                DATA_FOLDER_KEY,
+                config.timewarrior[config_name].data_folder.to_string(),
+            ),
+            (
+                INCLUDE_TASKS_KEY,
+                config.timewarrior[config_name].include_tasks.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn add_config_values(
+        &self,
+        config: &mut Config,
+        config_name: String,
+        mut config_values: HashMap<&'static str, String>,
+    ) {
+        config.timewarrior.insert(
+            config_name,
+            TimewarriorConfig {
+                data_folder: config_values.remove(DATA_FOLDER_KEY).unwrap(),
+                include_tasks: config_values.remove(INCLUDE_TASKS_KEY).as_deref() == Some("true"),
+            },
+        );
+    }
+
+    fn remove_config(&self, config: &mut Config, config_name: String) {
+        config.timewarrior.remove(&config_name);
+    }
+
+    fn get_events(
+        &self,
+        config: &Config,
+        config_name: &str,
+        day: Date<Local>,
+    ) -> Result<Vec<Event>> {
+        let timew_config = &config.timewarrior[config_name];
+        let day_start = day.and_hms(0, 0, 0);
+        let next_day_start = day_start + chrono::Duration::days(1);
+        let mut result = vec![];
+        if !timew_config.data_folder.is_empty() {
+            result.extend(
+                Self::read_intervals(
+                    Path::new(&timew_config.data_folder),
+                    day_start,
+                    next_day_start,
+                )?
+                .iter()
+                .map(|i| Self::build_interval_event(i, day_start, next_day_start)),
+            );
+        }
+        if timew_config.include_tasks {
+            result.extend(
+                Self::read_tasks(day_start, next_day_start)?
+                    .iter()
+                    .filter_map(|t| Self::build_task_event(t, day_start, next_day_start)),
+            );
+        }
+        Ok(result)
+    }
+}
+
+#[test]
+fn it_parses_timewarrior_intervals() {
+    let utc = |s: &str| warrior_date::parse(s).unwrap();
+    assert_eq!(
+        Some(Interval {
+            start: utc("20200323T080000Z"),
+            end: Some(utc("20200323T093000Z")),
+            tags: vec!["cigale".to_string(), "code review".to_string()],
+            annotation: Some("fix the \"parser\"".to_string()),
+        }),
+        Timewarrior::parse_interval(
+            r#"inc 20200323T080000Z - 20200323T093000Z # cigale "code review" # "fix the \"parser\"""#
+        )
+    );
+    assert_eq!(
+        Some(Interval {
+            start: utc("20200323T100000Z"),
+            end: None,
+            tags: vec![],
+            annotation: None,
+        }),
+        Timewarrior::parse_interval("inc 20200323T100000Z")
+    );
+    assert_eq!(None, Timewarrior::parse_interval("garbage"));
+}
+
+#[test]
+fn it_cuts_intervals_to_the_day() {
+    let day_start = Local.ymd(2020, 3, 23).and_hms(0, 0, 0);
+    let event = Timewarrior::build_interval_event(
+        &Interval {
+            start: day_start - chrono::Duration::hours(1),
+            end: Some(day_start + chrono::Duration::minutes(90)),
+            tags: vec!["cigale".to_string(), "ops".to_string()],
+            annotation: None,
+        },
+        day_start,
+        day_start + chrono::Duration::days(1),
+    );
+    assert_eq!(NaiveTime::from_hms(0, 0, 0), event.event_time);
+    assert_eq!("cigale, ops", event.event_info);
+    assert_eq!(Some("1h 30m".to_string()), event.event_extra_details);
+}
+
+#[test]
+fn it_reads_the_intervals_of_the_previous_month_file() {
+    let data_folder =
+        std::env::temp_dir().join(format!("cigale_timewarrior_{}", std::process::id()));
+    std::fs::create_dir_all(&data_folder).unwrap();
+    let day_start = Local.ymd(2020, 3, 1).and_hms(0, 0, 0);
+    // started the day before, in the file of its (UTC) month
+    let start = (day_start - chrono::Duration::hours(1)).with_timezone(&Utc);
+    std::fs::write(
+        data_folder.join(start.format("%Y-%m.data").to_string()),
+        format!(
+            "inc {} - {} # cigale\n",
+            start.format("%Y%m%dT%H%M%SZ"),
+            (start + chrono::Duration::hours(2)).format("%Y%m%dT%H%M%SZ")
+        ),
+    )
+    .unwrap();
+    let intervals = Timewarrior::read_intervals(
+        &data_folder,
+        day_start,
+        day_start + chrono::Duration::days(1),
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&data_folder).unwrap();
+    assert_eq!(1, intervals.len());
+    assert_eq!(vec!["cigale".to_string()], intervals[0].tags);
+}
+
+#[test]
+fn it_builds_taskwarrior_events() {
+    let tasks: Vec<Task> = serde_json::from_str(
+        r#"[{"id":0,"description":"Release 0.6","end":"20200323T101500Z","entry":"20200301T090000Z","modified":"20200323T101500Z","project":"cigale","status":"completed","uuid":"6c1f2a5e-58c5-4b5f-9a3b-2f6f6e2f6d1a","tags":["release"]},
+{"id":3,"description":"Write the docs","entry":"20200301T090000Z","modified":"20200323T140000Z","status":"pending","uuid":"7d2f3b6f-69d6-4c6f-8b4c-3f7f7f3f7e2b"}]"#,
+    )
+    .unwrap();
+    let day_start = Local.ymd(2020, 3, 23).and_hms(0, 0, 0);
+    let next_day_start = day_start + chrono::Duration::days(1);
+    let events: Vec<_> = tasks
+        .iter()
+        .filter_map(|t| Timewarrior::build_task_event(t, day_start, next_day_start))
+        .map(|e| (e.event_type_icon, e.event_contents_header))
+        .collect();
+    assert_eq!(
+        vec![
+            (Icon::CHECK_SQUARE, "Completed: Release 0.6".to_string()),
+            (Icon::TASKS, "Modified: Write the docs".to_string())
+        ],
+        events
+    );
+}
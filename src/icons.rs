@@ -10,13 +10,34 @@ impl Icon {
     pub const ANGLE_RIGHT: Icon = Icon("angle-right-symbolic");
     pub const CALENDAR_ALT: Icon = Icon("calendar-alt-symbolic");
     pub const TASKS: Icon = Icon("tasks-symbolic");
+    pub const CLOCK: Icon = Icon("clock-symbolic");
+    pub const EDIT: Icon = Icon("edit-symbolic");
+    pub const BOOK: Icon = Icon("book-symbolic");
+    pub const NEWSPAPER: Icon = Icon("newspaper-symbolic");
+    pub const FILE_ALT: Icon = Icon("file-alt-symbolic");
+    pub const TRASH_ALT: Icon = Icon("trash-alt-symbolic");
+    pub const USERS: Icon = Icon("users-symbolic");
     pub const COMMENT_DOTS: Icon = Icon("comment-dots-symbolic");
     pub const CODE_BRANCH: Icon = Icon("code-branch-symbolic");
+    pub const HISTORY: Icon = Icon("history-symbolic");
+    pub const PROJECT_DIAGRAM: Icon = Icon("project-diagram-symbolic");
+    pub const TAG: Icon = Icon("tag-symbolic");
+    pub const UPLOAD: Icon = Icon("upload-symbolic");
     pub const ENVELOPE: Icon = Icon("envelope-symbolic");
     pub const THUMBS_UP: Icon = Icon("thumbs-up-symbolic");
     pub const CHECK_SQUARE: Icon = Icon("check-square-symbolic");
     pub const COPY: Icon = Icon("copy-symbolic");
     pub const COG: Icon = Icon("cog-symbolic");
+    pub const TERMINAL: Icon = Icon("terminal-symbolic");
+    pub const GLOBE: Icon = Icon("globe-symbolic");
+    pub const POWER_OFF: Icon = Icon("power-off-symbolic");
+    pub const SIGN_IN_ALT: Icon = Icon("sign-in-alt-symbolic");
+    pub const SIGN_OUT_ALT: Icon = Icon("sign-out-alt-symbolic");
+    pub const LOCK: Icon = Icon("lock-symbolic");
+    pub const UNLOCK: Icon = Icon("unlock-symbolic");
+    pub const MOON: Icon = Icon("moon-symbolic");
+    pub const SUN: Icon = Icon("sun-symbolic");
+    pub const FOLDER_OPEN: Icon = Icon("folder-open-symbolic");
     pub const EXCLAMATION_TRIANGLE: Icon = Icon("exclamation-triangle-symbolic");
     pub const APP_ICON: Icon = Icon("com.github.emmanueltouzery.cigale");
 }
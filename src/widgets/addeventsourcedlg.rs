@@ -192,6 +192,12 @@ impl Widget for AddEventSourceDialog {
                 .active_text()
                 .map(|s| s.to_string())
                 .unwrap_or_else(|| "".to_string()),
+            ConfigType::Checkbox => entry
+                .clone()
+                .dynamic_cast::<gtk::CheckButton>()
+                .unwrap()
+                .is_active()
+                .to_string(),
         }
     }
 
@@ -302,6 +308,9 @@ impl Widget for AddEventSourceDialog {
         let combo = combo_widget
             .dynamic_cast::<gtk::ComboBoxText>()
             .expect("upcast combobox");
+        // other fields were updated, the combo values may change,
+        // but we must keep the user's choice if it's still valid
+        let previous_value = combo.active_text();
         combo.remove_all();
         let values = self
             .model
@@ -316,6 +325,14 @@ impl Widget for AddEventSourceDialog {
         for value in &values {
             combo.append_text(value);
         }
+        if let Some(previous_value) = previous_value {
+            combo.set_active(
+                values
+                    .iter()
+                    .position(|v| v.as_str() == previous_value.as_str())
+                    .map(|p| p as u32),
+            );
+        }
         values
     }
 
@@ -396,6 +413,10 @@ impl Widget for AddEventSourceDialog {
                     );
                     combo.upcast::<gtk::Widget>()
                 }
+                ConfigType::Checkbox => CheckButtonBuilder::new()
+                    .active(field_val == Some("true"))
+                    .build()
+                    .upcast::<gtk::Widget>(),
             };
             entry_components.insert(field.0, entry_widget.clone());
             self.widgets
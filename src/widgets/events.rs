@@ -2,7 +2,7 @@ use super::datepicker::DatePickerMsg::DayPicked as DatePickerDayPickedMsg;
 use super::datepicker::*;
 use super::event::EventListItem;
 use crate::config::Config;
-use crate::events::events::Event;
+use crate::events::events::{Event, EventDetails, LazyEventDetails};
 use crate::icons::*;
 use chrono::prelude::*;
 use gtk::builders::*;
@@ -15,6 +15,7 @@ pub enum Msg {
     EventSelected(Option<usize>),
     DayChange(Date<Local>),
     GotEvents(Result<Vec<Event>, String>),
+    GotEventDetails(LazyEventDetails, Result<EventDetails, String>),
     ConfigUpdate(Box<Config>), // box to prevent large size difference between variants
     CopyHeader,
     CopyAllHeaders,
@@ -63,6 +64,9 @@ impl Widget for EventView {
         for child in self.widgets.event_list.children() {
             self.widgets.event_list.remove(&child);
         }
+        for child in self.widgets.event_details_box.children() {
+            self.widgets.event_details_box.remove(&child);
+        }
         match &self.model.events {
             Some(Ok(events)) => {
                 log::info!("Fetched events: no errors");
@@ -106,6 +110,108 @@ impl Widget for EventView {
             });
     }
 
+    fn fetch_event_details(&self, lazy_details: LazyEventDetails) {
+        let stream = self.model.relm.stream().clone();
+        let (_channel, sender) = Channel::new(move |(lazy_details, details)| {
+            stream.emit(Msg::GotEventDetails(lazy_details, details));
+        });
+        let c = self.model.config.clone();
+        std::thread::spawn(move || {
+            let details = lazy_details.fetch(&c).map_err(|e| e.to_string());
+            sender
+                .send((lazy_details, details))
+                .unwrap_or_else(|err| println!("Thread communication error: {}", err));
+        });
+    }
+
+    fn display_event_details(&self, details: Result<EventDetails, String>) {
+        let details_box = &self.widgets.event_details_box;
+        // the same event may have been selected again while we were fetching
+        // its details, then we get the details twice
+        for child in details_box.children() {
+            details_box.remove(&child);
+        }
+        match details {
+            Ok(EventDetails::GitCommitDiff(files)) => {
+                let files_box = BoxBuilder::new()
+                    .orientation(gtk::Orientation::Vertical)
+                    .build();
+                for file in files {
+                    let hunks_box = BoxBuilder::new()
+                        .orientation(gtk::Orientation::Vertical)
+                        .margin_start(10)
+                        .build();
+                    if file.is_binary {
+                        hunks_box.add(
+                            &LabelBuilder::new()
+                                .label("Binary file")
+                                .halign(gtk::Align::Start)
+                                .build(),
+                        );
+                    }
+                    for hunk in file.hunks {
+                        let hunk_expander = ExpanderBuilder::new()
+                            .label(&hunk.header)
+                            .expanded(true)
+                            .build();
+                        hunk_expander.add(
+                            &LabelBuilder::new()
+                                .label(&format!(
+                                    "<span font-family=\"monospace\">{}</span>",
+                                    hunk.markup
+                                ))
+                                .use_markup(true)
+                                .selectable(true)
+                                .halign(gtk::Align::Start)
+                                .xalign(0.0)
+                                .build(),
+                        );
+                        hunks_box.add(&hunk_expander);
+                    }
+                    if file.truncated {
+                        hunks_box.add(
+                            &LabelBuilder::new()
+                                .label("Diff too big, truncated")
+                                .halign(gtk::Align::Start)
+                                .build(),
+                        );
+                    }
+                    let file_expander = ExpanderBuilder::new()
+                        .label(&file.path)
+                        .expanded(true)
+                        .build();
+                    file_expander.add(&hunks_box);
+                    files_box.add(&file_expander);
+                }
+                let diff_expander = ExpanderBuilder::new().label("Full diff").build();
+                diff_expander.add(&files_box);
+                details_box.add(&diff_expander);
+            }
+            Ok(EventDetails::Markup(markup)) => {
+                details_box.add(
+                    &LabelBuilder::new()
+                        .label(&markup)
+                        .use_markup(true)
+                        .wrap(true)
+                        .selectable(true)
+                        .halign(gtk::Align::Start)
+                        .xalign(0.0)
+                        .build(),
+                );
+            }
+            Err(err) => {
+                log::error!("Error fetching the event details: {}", err);
+                details_box.add(
+                    &LabelBuilder::new()
+                        .label(&format!("Error fetching the details: {}", err))
+                        .halign(gtk::Align::Start)
+                        .build(),
+                );
+            }
+        }
+        details_box.show_all();
+    }
+
     fn fetch_events(config: &Config, relm: &relm::Relm<Self>, day: Date<Local>) {
         let stream = relm.stream().clone();
         let (_channel, sender) = Channel::new(move |events| {
@@ -125,6 +231,29 @@ impl Widget for EventView {
                 if let Some(Ok(events)) = &self.model.events {
                     self.model.current_event = row_idx.and_then(|idx| events.get(idx)).cloned();
                 }
+                for child in self.widgets.event_details_box.children() {
+                    self.widgets.event_details_box.remove(&child);
+                }
+                if let Some(lazy_details) = self
+                    .model
+                    .current_event
+                    .as_ref()
+                    .and_then(|e| e.event_lazy_details.clone())
+                {
+                    self.fetch_event_details(lazy_details);
+                }
+            }
+            Msg::GotEventDetails(lazy_details, details) => {
+                // the user may have selected another event in the meantime
+                if self
+                    .model
+                    .current_event
+                    .as_ref()
+                    .and_then(|e| e.event_lazy_details.as_ref())
+                    == Some(&lazy_details)
+                {
+                    self.display_event_details(details);
+                }
             }
             Msg::DayChange(day) => {
                 self.model.events = None;
@@ -268,59 +397,70 @@ impl Widget for EventView {
                             },
                             propagate_natural_height: true,
                             gtk::Box {
-                                // two labels: one in case we have markup, one in case we have plain text.
-                                // I used to have a single label for both,  using use_markup and text, and it worked,
-                                // but there was no guarantee on the other in which both fields were updated. If the text
-                                // was updated before 'use_markup', i could get text interpreted as markup which was not markup,
-                                // then GtkLabel would fail and never recover displaying markup.
-                                gtk::Label {
-                                    // text label, not used when we display markup
-                                    child: {
-                                        pack_type: gtk::PackType::Start,
-                                        fill: true,
-                                        expand: true,
-                                        padding: 10,
+                                orientation: gtk::Orientation::Vertical,
+                                gtk::Box {
+                                    // two labels: one in case we have markup, one in case we have plain text.
+                                    // I used to have a single label for both,  using use_markup and text, and it worked,
+                                    // but there was no guarantee on the other in which both fields were updated. If the text
+                                    // was updated before 'use_markup', i could get text interpreted as markup which was not markup,
+                                    // then GtkLabel would fail and never recover displaying markup.
+                                    gtk::Label {
+                                        // text label, not used when we display markup
+                                        child: {
+                                            pack_type: gtk::PackType::Start,
+                                            fill: true,
+                                            expand: true,
+                                            padding: 10,
+                                        },
+                                        halign: gtk::Align::Start,
+                                        valign: gtk::Align::Start,
+                                        selectable: true,
+                                        xalign: 0.0,
+                                        yalign: 0.0,
+                                        line_wrap: true,
+                                        visible: self.model.current_event.as_ref()
+                                                                         .filter(|e| e.event_contents_body.is_markup())
+                                                                         .is_none(),
+                                        text: self.model
+                                                  .current_event
+                                                  .as_ref()
+                                                  .filter(|e| !e.event_contents_body.is_markup())
+                                                  .map(|e| e.event_contents_body.as_str())
+                                                  .unwrap_or(""),
                                     },
-                                    halign: gtk::Align::Start,
-                                    valign: gtk::Align::Start,
-                                    selectable: true,
-                                    xalign: 0.0,
-                                    yalign: 0.0,
-                                    line_wrap: true,
-                                    visible: self.model.current_event.as_ref()
-                                                                     .filter(|e| e.event_contents_body.is_markup())
-                                                                     .is_none(),
-                                    text: self.model
-                                              .current_event
-                                              .as_ref()
-                                              .filter(|e| !e.event_contents_body.is_markup())
-                                              .map(|e| e.event_contents_body.as_str())
-                                              .unwrap_or(""),
+                                    gtk::Label {
+                                        // markup label, not used when we display text
+                                        child: {
+                                            pack_type: gtk::PackType::Start,
+                                            fill: true,
+                                            expand: true,
+                                            padding: 10,
+                                        },
+                                        halign: gtk::Align::Start,
+                                        valign: gtk::Align::Start,
+                                        selectable: true,
+                                        xalign: 0.0,
+                                        yalign: 0.0,
+                                        line_wrap: self.model.current_event.as_ref()
+                                                                           .filter(|e| e.event_contents_body.is_markup())
+                                                                           .map(|e| e.event_contents_body.is_word_wrap())
+                                                                           .unwrap_or(false),
+                                        visible: self.model.current_event.as_ref()
+                                                                         .filter(|e| e.event_contents_body.is_markup())
+                                                                         .is_some(),
+                                        markup: self.model.current_event.as_ref()
+                                                                        .filter(|e| e.event_contents_body.is_markup())
+                                                                        .map(|e| e.event_contents_body.as_str())
+                                                                        .unwrap_or(""),
+                                    }
                                 },
-                                gtk::Label {
-                                    // markup label, not used when we display text
-                                    child: {
-                                        pack_type: gtk::PackType::Start,
-                                        fill: true,
-                                        expand: true,
-                                        padding: 10,
-                                    },
-                                    halign: gtk::Align::Start,
-                                    valign: gtk::Align::Start,
-                                    selectable: true,
-                                    xalign: 0.0,
-                                    yalign: 0.0,
-                                    line_wrap: self.model.current_event.as_ref()
-                                                                       .filter(|e| e.event_contents_body.is_markup())
-                                                                       .map(|e| e.event_contents_body.is_word_wrap())
-                                                                       .unwrap_or(false),
-                                    visible: self.model.current_event.as_ref()
-                                                                     .filter(|e| e.event_contents_body.is_markup())
-                                                                     .is_some(),
-                                    markup: self.model.current_event.as_ref()
-                                                                    .filter(|e| e.event_contents_body.is_markup())
-                                                                    .map(|e| e.event_contents_body.as_str())
-                                                                    .unwrap_or(""),
+                                // details which we fetch only when the event gets selected,
+                                // for instance the full diff of git commits
+                                #[name="event_details_box"]
+                                gtk::Box {
+                                    orientation: gtk::Orientation::Vertical,
+                                    margin_start: 10,
+                                    margin_end: 10,
                                 }
                             }
                         }